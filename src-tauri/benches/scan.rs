@@ -0,0 +1,121 @@
+//! Scan and query benchmarks over a synthetic tree. Its shape is set by
+//! `MAKA_BENCH_BREADTH` (entries of each kind per directory, default 6) and
+//! `MAKA_BENCH_DEPTH` (directory levels, default 4).
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use maka_lib::disk_scanner::{DiskScanner, ProgressTracker, ScanOptions};
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// A directory tree in the temp directory, removed when dropped
+struct SyntheticTree(PathBuf);
+
+impl SyntheticTree {
+    /// Gives every directory `breadth` files of 1 KiB and, above `depth`
+    /// levels, `breadth` subdirectories
+    fn create(breadth: usize, depth: usize) -> io::Result<Self> {
+        let root = env::temp_dir().join(format!("maka-bench-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        Self::fill(&root, breadth, depth)?;
+        Ok(Self(root))
+    }
+
+    fn fill(dir: &Path, breadth: usize, depth: usize) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        for index in 0..breadth {
+            fs::write(dir.join(format!("file{index}.bin")), [0u8; 1024])?;
+            if depth > 0 {
+                Self::fill(&dir.join(format!("dir{index}")), breadth, depth - 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().expect("Temp directory path is not UTF-8")
+    }
+}
+
+impl Drop for SyntheticTree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn build_cache(scanner: &DiskScanner, path: &str) {
+    scanner
+        .build_cache_tracked(
+            path,
+            ScanOptions::default(),
+            Arc::new(ProgressTracker::silent()),
+        )
+        .expect("Failed to scan the synthetic tree");
+}
+
+fn scan_benchmarks(c: &mut Criterion) {
+    let breadth = env_or("MAKA_BENCH_BREADTH", 6);
+    let depth = env_or("MAKA_BENCH_DEPTH", 4);
+    let tree = SyntheticTree::create(breadth, depth).expect("Failed to create the synthetic tree");
+    let scanner = DiskScanner::new();
+
+    let mut group = c.benchmark_group(format!("tree {breadth}x{depth}"));
+    group.sample_size(20);
+    group.bench_function("build_cache", |b| {
+        b.iter(|| build_cache(&scanner, tree.path()))
+    });
+
+    build_cache(&scanner, tree.path());
+    // Every level has `breadth` files per directory and `breadth` times as
+    // many directories as the one above
+    let expected_files: usize = (0..=depth).map(|level| breadth.pow(level as u32 + 1)).sum();
+    assert_eq!(
+        scanner.with_node(tree.path(), |node| node.total_files),
+        Ok(expected_files as u64),
+        "The scanned tree does not have the generated shape"
+    );
+    group.bench_function("get_result_with_depth", |b| {
+        b.iter(|| {
+            scanner
+                .get_result_with_depth(tree.path(), 2, None, None)
+                .unwrap()
+        })
+    });
+    group.bench_function("limit_depth", |b| {
+        b.iter(|| {
+            scanner
+                .with_node(tree.path(), |node| node.limit_depth(u32::MAX))
+                .unwrap()
+        })
+    });
+    // The same JSON, from a cloned tree and from a view borrowing the cache
+    group.bench_function("serialize cloned tree", |b| {
+        b.iter(|| {
+            let node = scanner
+                .get_result_with_depth(tree.path(), u32::MAX, None, None)
+                .unwrap();
+            serde_json::to_string(&node).unwrap()
+        })
+    });
+    group.bench_function("result_json", |b| {
+        b.iter(|| {
+            scanner
+                .result_json(tree.path(), u32::MAX, 0, None, false)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scan_benchmarks);
+criterion_main!(benches);
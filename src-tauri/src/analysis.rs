@@ -0,0 +1,1725 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use tauri::{AppHandle, State};
+
+use crate::disk_scanner::{is_excluded, DiskScanner, FileNode};
+use crate::drives;
+use crate::error::ScanError;
+use crate::paths::{decode_path, extended_length, is_separator, relative_to_root};
+use crate::settings::{self, SizeUnits};
+
+/// Orders nodes by size, breaking ties by path so results are deterministic
+struct BySize<'a>(&'a FileNode);
+
+impl Ord for BySize<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .size
+            .cmp(&other.0.size)
+            .then_with(|| other.0.path.cmp(&self.0.path))
+    }
+}
+
+impl PartialOrd for BySize<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for BySize<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BySize<'_> {}
+
+/// Broad kind of file, guessed from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Category {
+    Images,
+    Videos,
+    Documents,
+    Audio,
+    Code,
+    Archives,
+    Other,
+}
+
+impl Category {
+    /// Category of a file with the (lowercased) `extension`, see
+    /// `FileNode::extension`
+    fn of(extension: Option<&str>) -> Self {
+        let Some(extension) = extension else {
+            return Category::Other;
+        };
+        match extension {
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif"
+            | "svg" | "ico" | "raw" | "cr2" | "nef" | "arw" | "dng" | "psd" => Category::Images,
+            "mp4" | "m4v" | "mov" | "avi" | "mkv" | "webm" | "wmv" | "flv" | "mpg" | "mpeg"
+            | "3gp" => Category::Videos,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "odp"
+            | "rtf" | "txt" | "md" | "csv" | "epub" | "pages" | "numbers" | "key" => {
+                Category::Documents
+            }
+            "mp3" | "wav" | "flac" | "aac" | "m4a" | "ogg" | "opus" | "wma" | "aiff" | "mid" => {
+                Category::Audio
+            }
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "kt" | "swift"
+            | "py" | "rb" | "php" | "js" | "jsx" | "ts" | "tsx" | "html" | "css" | "scss"
+            | "json" | "toml" | "yaml" | "yml" | "xml" | "sh" | "sql" | "lua" => Category::Code,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" | "dmg" | "iso"
+            | "jar" | "deb" | "rpm" => Category::Archives,
+            _ => Category::Other,
+        }
+    }
+}
+
+/// Space taken by one category, as returned by `usage_by_category`
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryStat {
+    pub category: Category,
+    pub total_bytes: u64,
+    pub file_count: u64,
+}
+
+/// Space owned by one user, as returned by `usage_by_owner`
+#[derive(Debug, Clone, Serialize)]
+pub struct OwnerStat {
+    pub uid: u32,
+    /// Login name from `/etc/passwd`, `None` for unknown users
+    pub owner_name: Option<String>,
+    pub total_bytes: u64,
+    pub file_count: u64,
+}
+
+/// Maps user ids to login names as listed in `/etc/passwd`
+fn user_names(passwd: &str) -> HashMap<u32, String> {
+    passwd
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+/// A directory name that marks regenerable data, and what to call it
+#[derive(Debug, Clone, Deserialize)]
+pub struct JunkRule {
+    pub name: String,
+    pub label: String,
+}
+
+/// Directory names `find_junk` looks for unless given others
+const DEFAULT_JUNK_RULES: &[(&str, &str)] = &[
+    ("node_modules", "Node.js dependencies"),
+    (".gradle", "Gradle cache"),
+    ("target", "Build output"),
+    ("__pycache__", "Python bytecode"),
+    (".cache", "Cache"),
+    ("DerivedData", "Xcode build data"),
+    ("Cache", "Browser cache"),
+    ("Code Cache", "Browser cache"),
+    ("GPUCache", "Browser cache"),
+    ("cache2", "Browser cache"),
+];
+
+/// A directory flagged by `find_junk`
+#[derive(Debug, Clone, Serialize)]
+pub struct JunkDir {
+    pub path: String,
+    pub size: u64,
+    pub total_files: u64,
+    pub label: String,
+}
+
+/// Files within one size range, as returned by `size_histogram`
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub range_label: String,
+    pub count: u64,
+    pub total_bytes: u64,
+}
+
+/// Bucket boundaries used by `size_histogram` unless others are given
+const DEFAULT_HISTOGRAM_BOUNDARIES: [u64; 4] = [1 << 10, 1 << 20, 100 << 20, 1 << 30];
+
+/// Returns the `n` largest nodes, largest first. A bounded min-heap keeps
+/// this O(total log n) instead of sorting every node.
+fn top_by_size<'a>(nodes: impl Iterator<Item = &'a FileNode>, n: usize) -> Vec<&'a FileNode> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    for node in nodes {
+        heap.push(Reverse(BySize(node)));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(BySize(node))| node)
+        .collect()
+}
+
+#[tauri::command]
+pub async fn largest_files(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    n: usize,
+) -> Result<Vec<FileNode>, ScanError> {
+    scanner.with_node(&path, |node| top_files(node, n))
+}
+
+/// The `n` largest files below `node`, largest first
+fn top_files(node: &FileNode, n: usize) -> Vec<FileNode> {
+    top_by_size(
+        node.iter()
+            .filter(|node| !node.is_directory && !node.in_archive),
+        n,
+    )
+    .into_iter()
+    .cloned()
+    .collect()
+}
+
+/// Returns the `n` largest directories anywhere below `path` (not counting
+/// `path` itself) by aggregated size, largest first and without children
+#[tauri::command]
+pub async fn largest_dirs(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    n: usize,
+) -> Result<Vec<FileNode>, ScanError> {
+    scanner.with_node(&path, |node| top_dirs(node, n))
+}
+
+/// The `n` largest directories below `node`, largest first
+fn top_dirs(node: &FileNode, n: usize) -> Vec<FileNode> {
+    top_by_size(
+        node.iter()
+            .skip(1)
+            .filter(|node| node.is_directory && !node.in_archive),
+        n,
+    )
+    .into_iter()
+    .map(FileNode::detached)
+    .collect()
+}
+
+/// Finds files below `path` between `min` and `max` bytes (both included),
+/// largest first. A `max` of 0 leaves the size unbounded above.
+#[tauri::command]
+pub async fn files_in_size_range(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    min: u64,
+    max: u64,
+) -> Result<Vec<FileNode>, ScanError> {
+    scanner.with_node(&path, |node| files_sized_between(node, min, max))
+}
+
+/// Files below `node` sized `min` to `max` inclusive, `max` 0 for no upper
+/// bound, largest first
+fn files_sized_between(node: &FileNode, min: u64, max: u64) -> Vec<FileNode> {
+    let max = if max == 0 { u64::MAX } else { max };
+    let mut files: Vec<&FileNode> = node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive)
+        .filter(|node| (min..=max).contains(&node.size))
+        .collect();
+    files.sort_by(|a, b| BySize(b).cmp(&BySize(a)));
+    files.into_iter().cloned().collect()
+}
+
+/// Finds every node below `path` whose name contains `query`. Matches are
+/// returned without their children but with full paths.
+#[tauri::command]
+pub async fn search(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    query: String,
+    case_sensitive: bool,
+) -> Result<Vec<FileNode>, ScanError> {
+    scanner.with_node(&path, |node| search_names(node, &query, case_sensitive))
+}
+
+/// The nodes below `node` whose name contains `query`, see `search`
+fn search_names(node: &FileNode, query: &str, case_sensitive: bool) -> Vec<FileNode> {
+    let query = if case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    };
+    let nodes: Vec<&FileNode> = node.iter().skip(1).collect();
+    nodes
+        .into_par_iter()
+        .filter(|node| {
+            if case_sensitive {
+                node.name.contains(&query)
+            } else {
+                node.name.to_lowercase().contains(&query)
+            }
+        })
+        .map(FileNode::detached)
+        .collect()
+}
+
+/// How `search_advanced` matches its pattern
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum MatchMode {
+    /// Names containing the pattern, ignoring case, as `search` does
+    Substring,
+    /// Shell-style glob such as `*.log` or `**/cache/*`. Patterns with a
+    /// `/` are matched against the path below the searched directory,
+    /// others against the name alone.
+    Glob,
+    /// Regular expression found anywhere in the path below the searched
+    /// directory, with `/` separators
+    Regex,
+}
+
+enum Matcher {
+    Substring(String),
+    Glob {
+        pattern: glob::Pattern,
+        whole_path: bool,
+    },
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &str, mode: MatchMode) -> Result<Self, ScanError> {
+        let invalid =
+            |e: &dyn std::fmt::Display| ScanError::InvalidPattern(format!("{}: {}", pattern, e));
+        Ok(match mode {
+            MatchMode::Substring => Matcher::Substring(pattern.to_lowercase()),
+            MatchMode::Glob => Matcher::Glob {
+                pattern: glob::Pattern::new(pattern).map_err(|e| invalid(&e))?,
+                whole_path: pattern.contains('/'),
+            },
+            MatchMode::Regex => {
+                Matcher::Regex(regex::Regex::new(pattern).map_err(|e| invalid(&e))?)
+            }
+        })
+    }
+
+    /// Whether the node called `name` at `relative_path` below the searched
+    /// directory matches
+    fn matches(&self, name: &str, relative_path: &str) -> bool {
+        match self {
+            Matcher::Substring(query) => name.to_lowercase().contains(query),
+            Matcher::Glob {
+                pattern,
+                whole_path: true,
+            } => pattern.matches(relative_path),
+            Matcher::Glob { pattern, .. } => pattern.matches(name),
+            Matcher::Regex(regex) => regex.is_match(relative_path),
+        }
+    }
+}
+
+/// Finds every node below `path` matching `pattern` as `mode` says. Matches
+/// are returned without their children but with full paths. An invalid
+/// glob or regular expression fails with `InvalidPattern`.
+#[tauri::command]
+pub async fn search_advanced(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    pattern: String,
+    mode: MatchMode,
+) -> Result<Vec<FileNode>, ScanError> {
+    let matcher = Matcher::new(&pattern, mode)?;
+    scanner.with_node(&path, |node| search_matching(node, &matcher))
+}
+
+/// Nodes below `node` that `matcher` matches, detached from their children
+fn search_matching(node: &FileNode, matcher: &Matcher) -> Vec<FileNode> {
+    let root = node.path.to_string();
+    let nodes: Vec<&FileNode> = node.iter().skip(1).collect();
+    nodes
+        .into_par_iter()
+        .filter(|node| {
+            let path = node.path.to_string();
+            let relative_path = relative_to_root(&path, &root)
+                .unwrap_or(&path)
+                .trim_start_matches(is_separator);
+            if cfg!(windows) {
+                matcher.matches(&node.name, &relative_path.replace('\\', "/"))
+            } else {
+                matcher.matches(&node.name, relative_path)
+            }
+        })
+        .map(FileNode::detached)
+        .collect()
+}
+
+/// Maps `path` and its descendants down to `max_depth` levels below it to
+/// their aggregated sizes, for views that only need sizes and not the tree
+#[tauri::command]
+pub async fn size_map(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+) -> Result<HashMap<String, u64>, ScanError> {
+    scanner.with_node(&path, |node| sizes_by_path(node, max_depth))
+}
+
+/// Maps `node` and its descendants down to `max_depth` levels to their sizes
+fn sizes_by_path(node: &FileNode, max_depth: u32) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    let mut stack = vec![(node, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        sizes.insert(node.path.to_string(), node.size);
+        if depth < max_depth {
+            stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+        }
+    }
+    sizes
+}
+
+/// Seconds since the Unix epoch `days` days before now
+fn days_ago(days: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    now.saturating_sub(days.saturating_mul(24 * 60 * 60))
+}
+
+/// Finds files below `path` last modified more than `older_than_days` ago
+/// and larger than `min_size`, largest first
+#[tauri::command]
+pub async fn stale_files(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    older_than_days: u64,
+    min_size: u64,
+) -> Result<Vec<FileNode>, ScanError> {
+    let cutoff = days_ago(older_than_days);
+    scanner.with_node(&path, |node| modified_before(node, cutoff, min_size))
+}
+
+/// Files below `node` last modified before `cutoff` (in seconds since the
+/// Unix epoch) and larger than `min_size`, largest first
+fn modified_before(node: &FileNode, cutoff: u64, min_size: u64) -> Vec<FileNode> {
+    let mut stale: Vec<&FileNode> = node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive && node.size > min_size)
+        .filter(|node| node.modified.is_some_and(|modified| modified < cutoff))
+        .collect();
+    stale.sort_by(|a, b| BySize(b).cmp(&BySize(a)));
+    stale.into_iter().cloned().collect()
+}
+
+/// Finds files below `path` modified within the last `within_days` days,
+/// newest first, at most `limit` of them
+#[tauri::command]
+pub async fn recent_files(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    within_days: u64,
+    limit: usize,
+) -> Result<Vec<FileNode>, ScanError> {
+    let cutoff = days_ago(within_days);
+    scanner.with_node(&path, |node| modified_since(node, cutoff, limit))
+}
+
+/// Up to `limit` files below `node` modified at or after `cutoff`, in
+/// seconds since the Unix epoch, newest first
+fn modified_since(node: &FileNode, cutoff: u64, limit: usize) -> Vec<FileNode> {
+    let mut recent: Vec<&FileNode> = node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive)
+        .filter(|node| node.modified.is_some_and(|modified| modified >= cutoff))
+        .collect();
+    recent.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    recent.into_iter().take(limit).cloned().collect()
+}
+
+/// Files found by `never_opened`
+#[derive(Debug, Clone, Serialize)]
+pub struct NeverOpened {
+    pub files: Vec<FileNode>,
+    /// Why nothing could be checked, e.g. the drive does not record access
+    /// times
+    pub note: Option<String>,
+}
+
+/// Whether the file at `path` has not been read since it was created or
+/// last modified
+fn never_read(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(extended_length(path)) else {
+        return false;
+    };
+    let Ok(accessed) = metadata.accessed() else {
+        return false;
+    };
+    [metadata.created().ok(), metadata.modified().ok()]
+        .into_iter()
+        .flatten()
+        .max()
+        .is_some_and(|written| accessed <= written)
+}
+
+/// Finds files below `path` of at least `min_size` that nobody has opened
+/// since they were created or last modified, largest first. This relies on
+/// access times, so nothing is reported on drives that do not record them.
+#[tauri::command]
+pub async fn never_opened(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    min_size: u64,
+) -> Result<NeverOpened, ScanError> {
+    if !drives::tracks_access_time(&decode_path(&path)) {
+        return Ok(NeverOpened {
+            files: Vec::new(),
+            note: Some("This drive does not record when files are opened".to_string()),
+        });
+    }
+
+    // Files are only looked at once the cache is no longer held
+    let candidates = scanner.with_node(&path, |node| files_at_least(node, min_size))?;
+    Ok(NeverOpened {
+        files: unread(candidates),
+        note: None,
+    })
+}
+
+/// Files on disk below `node` of at least `min_size`
+fn files_at_least(node: &FileNode, min_size: u64) -> Vec<FileNode> {
+    node.iter()
+        .filter(|node| !node.is_directory && !node.in_archive && !node.is_symlink)
+        .filter(|node| node.size >= min_size)
+        .map(FileNode::detached)
+        .collect()
+}
+
+/// The `files` never read since they were written, largest first
+fn unread(files: Vec<FileNode>) -> Vec<FileNode> {
+    let mut files: Vec<FileNode> = files
+        .into_par_iter()
+        .filter(|file| never_read(&decode_path(&file.path.to_string())))
+        .collect();
+    files.sort_by(|a, b| BySize(b).cmp(&BySize(a)));
+    files
+}
+
+/// Finds directories below `path` named like well-known caches and build
+/// output (`rules`, or a built-in list), largest first. Nothing inside a
+/// flagged directory is reported separately.
+#[tauri::command]
+pub async fn find_junk(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    rules: Option<Vec<JunkRule>>,
+) -> Result<Vec<JunkDir>, ScanError> {
+    let rules = junk_rules(rules);
+    scanner.with_node(&path, |node| {
+        junk_dirs(node, |node| rules.get(&node.name).cloned())
+    })
+}
+
+/// Labels by directory name, from `rules` or the built-in list
+fn junk_rules(rules: Option<Vec<JunkRule>>) -> HashMap<String, String> {
+    match rules {
+        Some(rules) => rules
+            .into_iter()
+            .map(|rule| (rule.name, rule.label))
+            .collect(),
+        None => default_junk_rules(),
+    }
+}
+
+fn default_junk_rules() -> HashMap<String, String> {
+    DEFAULT_JUNK_RULES
+        .iter()
+        .map(|&(name, label)| (name.to_string(), label.to_string()))
+        .collect()
+}
+
+/// Directories below `node` that `label` gives a label, largest first.
+/// Nothing inside a labelled directory is looked at.
+fn junk_dirs(node: &FileNode, label: impl Fn(&FileNode) -> Option<String>) -> Vec<JunkDir> {
+    let mut junk = Vec::new();
+    let mut stack: Vec<&FileNode> = node.children.iter().collect();
+    while let Some(node) = stack.pop() {
+        if !node.is_directory || node.in_archive {
+            continue;
+        }
+        match label(node) {
+            Some(label) => junk.push(JunkDir {
+                path: node.path.to_string(),
+                size: node.size,
+                total_files: node.total_files,
+                label,
+            }),
+            None => stack.extend(&node.children),
+        }
+    }
+    junk.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    junk
+}
+
+/// Whether `node` is a trash folder: `~/.Trash` on macOS, the XDG
+/// `~/.local/share/Trash` and `.Trash-$uid` on other drives, or the
+/// Windows recycle bin
+fn is_trash(node: &FileNode) -> bool {
+    match node.name.as_str() {
+        ".Trash" | "$RECYCLE.BIN" => true,
+        "Trash" => node
+            .path
+            .to_string()
+            .strip_suffix("Trash")
+            .is_some_and(|parent| {
+                parent
+                    .trim_end_matches(is_separator)
+                    .ends_with(".local/share")
+            }),
+        name => name
+            .strip_prefix(".Trash-")
+            .is_some_and(|uid| uid.parse::<u32>().is_ok()),
+    }
+}
+
+/// Junk of one kind, as listed by `reclaimable_report`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReclaimableCategory {
+    pub label: String,
+    pub size: u64,
+    pub dir_count: usize,
+}
+
+/// How much space emptying the trash and removing junk below a path would
+/// give back, next to the space already free
+#[derive(Debug, Clone, Serialize)]
+pub struct ReclaimableReport {
+    /// Largest first
+    pub categories: Vec<ReclaimableCategory>,
+    /// Sum of the `categories`
+    pub reclaimable: u64,
+    /// Space available on the drive, `None` when it cannot be queried
+    pub free_space: Option<u64>,
+}
+
+/// Totals the trash folders and the directories `find_junk` flags by
+/// default below the cached `path`, without scanning anything
+#[tauri::command]
+pub async fn reclaimable_report(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<ReclaimableReport, ScanError> {
+    scanner.with_node(&path, reclaimable)
+}
+
+/// The report `reclaimable_report` describes for `node`
+fn reclaimable(node: &FileNode) -> ReclaimableReport {
+    let rules = default_junk_rules();
+    let junk = junk_dirs(node, |node| {
+        if is_trash(node) {
+            Some("Trash".to_string())
+        } else {
+            rules.get(&node.name).cloned()
+        }
+    });
+
+    let mut categories: Vec<ReclaimableCategory> = Vec::new();
+    for dir in junk {
+        match categories
+            .iter_mut()
+            .find(|category| category.label == dir.label)
+        {
+            Some(category) => {
+                category.size = category.size.saturating_add(dir.size);
+                category.dir_count += 1;
+            }
+            None => categories.push(ReclaimableCategory {
+                label: dir.label,
+                size: dir.size,
+                dir_count: 1,
+            }),
+        }
+    }
+    categories.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.label.cmp(&b.label)));
+    ReclaimableReport {
+        reclaimable: categories.iter().fold(0, |total: u64, category| {
+            total.saturating_add(category.size)
+        }),
+        categories,
+        free_space: drives::available_space(&decode_path(&node.path.to_string())),
+    }
+}
+
+/// Directories with identical contents, as returned by `find_duplicate_dirs`
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateDirs {
+    pub paths: Vec<String>,
+    /// Size of each copy
+    pub size: u64,
+    /// Space freed by keeping only one of the copies
+    pub reclaimable: u64,
+}
+
+/// A directory that has the same layout as another one, see
+/// `structure_hashes`
+struct DirCandidate {
+    structure: blake3::Hash,
+    path: String,
+    size: u64,
+    /// Files in the directory, ordered by their path below it
+    files: Vec<PathBuf>,
+}
+
+/// Hashes the layout of the directory `node`: the names and kinds of its
+/// entries, the lengths of its files and the layout of its subdirectories.
+/// Every directory below `node` (and `node` itself) that holds files is
+/// added to `hashes`. Directories whose contents are not fully known, like
+/// unreadable or cut-off ones, get `None`, and so do all their ancestors.
+fn structure_hashes<'a>(
+    node: &'a FileNode,
+    hashes: &mut Vec<(blake3::Hash, &'a FileNode)>,
+) -> Option<blake3::Hash> {
+    let mut children: Vec<&FileNode> = node.children.iter().collect();
+    children.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut hasher = blake3::Hasher::new();
+    let mut complete = !(node.inaccessible || node.truncated || node.mount_point);
+    for child in children {
+        hasher
+            .update(&child.name.len().to_le_bytes())
+            .update(child.name.as_bytes());
+        if child.is_directory {
+            match structure_hashes(child, hashes) {
+                Some(hash) => hasher.update(b"/").update(hash.as_bytes()),
+                None => {
+                    complete = false;
+                    continue;
+                }
+            };
+        } else if let Some(target) = &child.symlink_target {
+            hasher
+                .update(b"@")
+                .update(&target.len().to_le_bytes())
+                .update(target.as_bytes());
+        } else {
+            hasher
+                .update(b":")
+                .update(&child.apparent_size.to_le_bytes());
+        }
+    }
+    complete &= node.skipped_children == 0;
+    if !complete {
+        return None;
+    }
+    let hash = hasher.finalize();
+    if node.total_files > 0 {
+        hashes.push((hash, node));
+    }
+    Some(hash)
+}
+
+/// Hashes the contents of a file, `None` if it cannot be read
+fn content_hash(path: &Path) -> Option<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update_reader(fs::File::open(extended_length(path)).ok()?)
+        .ok()?;
+    Some(hasher.finalize())
+}
+
+/// Finds directories below `path` that are copies of each other: the same
+/// names, file lengths and file contents throughout. Candidates are picked
+/// from the cached layout first, only their files are read. Copies inside a
+/// reported copy are not reported again. Largest savings first.
+#[tauri::command]
+pub async fn find_duplicate_dirs(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<Vec<DuplicateDirs>, ScanError> {
+    // The cache is only held while picking candidates, not while their
+    // files are read
+    let candidates = scanner.with_node(&path, dir_candidates)?;
+    Ok(duplicate_groups(&candidates))
+}
+
+/// Directories below `node` laid out like at least one other
+fn dir_candidates(node: &FileNode) -> Vec<DirCandidate> {
+    let mut hashes = Vec::new();
+    structure_hashes(node, &mut hashes);
+    let mut by_structure: HashMap<blake3::Hash, Vec<&FileNode>> = HashMap::new();
+    for (hash, dir) in hashes {
+        by_structure.entry(hash).or_default().push(dir);
+    }
+    by_structure
+        .into_iter()
+        .filter(|(_, dirs)| dirs.len() > 1)
+        .flat_map(|(structure, dirs)| {
+            dirs.into_iter().map(move |dir| {
+                let path = dir.path.to_string();
+                let mut files: Vec<(String, PathBuf)> = dir
+                    .iter()
+                    .filter(|node| !node.is_directory && !node.is_symlink && !node.in_archive)
+                    .map(|file| {
+                        let file_path = file.path.to_string();
+                        let relative = relative_to_root(&file_path, &path)
+                            .unwrap_or_default()
+                            .to_string();
+                        (relative, decode_path(&file_path))
+                    })
+                    .collect();
+                files.sort();
+                DirCandidate {
+                    structure,
+                    size: dir.size,
+                    files: files.into_iter().map(|(_, file)| file).collect(),
+                    path,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Groups the `candidates` whose files have the same contents too, see
+/// `find_duplicate_dirs`
+fn duplicate_groups(candidates: &[DirCandidate]) -> Vec<DuplicateDirs> {
+    // Files of nested candidates are shared, each one is read once
+    let mut files: Vec<&PathBuf> = candidates.iter().flat_map(|dir| &dir.files).collect();
+    files.sort();
+    files.dedup();
+    let contents: HashMap<&PathBuf, Option<blake3::Hash>> = files
+        .into_par_iter()
+        .map(|file| (file, content_hash(file)))
+        .collect();
+
+    let mut by_contents: HashMap<blake3::Hash, Vec<&DirCandidate>> = HashMap::new();
+    for dir in candidates {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(dir.structure.as_bytes());
+        let readable = dir.files.iter().all(|file| match contents[file] {
+            Some(hash) => {
+                hasher.update(hash.as_bytes());
+                true
+            }
+            None => false,
+        });
+        if readable {
+            by_contents.entry(hasher.finalize()).or_default().push(dir);
+        }
+    }
+
+    let mut groups: Vec<DuplicateDirs> = by_contents
+        .into_values()
+        .filter(|dirs| dirs.len() > 1 && dirs[0].size > 0)
+        .map(|dirs| {
+            let size = dirs.iter().map(|dir| dir.size).max().unwrap_or(0);
+            let mut paths: Vec<String> = dirs.into_iter().map(|dir| dir.path.clone()).collect();
+            paths.sort();
+            DuplicateDirs {
+                reclaimable: size.saturating_mul(paths.len() as u64 - 1),
+                size,
+                paths,
+            }
+        })
+        .collect();
+    // Enclosing copies are larger, so they come before the ones inside them
+    groups.sort_by(|a, b| {
+        b.reclaimable
+            .cmp(&a.reclaimable)
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    let mut reported: Vec<String> = Vec::new();
+    groups.retain(|group| {
+        let nested = group.paths.iter().all(|path| {
+            reported
+                .iter()
+                .any(|outer| relative_to_root(path, outer).is_some())
+        });
+        if !nested {
+            reported.extend(group.paths.iter().cloned());
+        }
+        !nested
+    });
+    groups
+}
+
+/// Adds the empty directories at and below `node` to `found`, parents
+/// before their children, and returns whether `node` is one. Directories
+/// whose contents are not fully known are never empty: ones with
+/// unreadable entries, ones the scan did not list (other filesystems,
+/// pseudo filesystems, the depth limit) and the directories holding them.
+fn collect_empty_dirs<'a>(node: &'a FileNode, found: &mut Vec<&'a FileNode>) -> bool {
+    if !node.is_directory || node.in_archive {
+        return false;
+    }
+    let index = found.len();
+    let mut empty = node.total_files == 0
+        && node.skipped == 0
+        && !node.mount_point
+        && !node.truncated
+        && !is_excluded(&decode_path(&node.path.to_string()));
+    for child in &node.children {
+        // Children are searched even below a directory that is not empty
+        empty &= collect_empty_dirs(child, found);
+    }
+    if empty {
+        found.insert(index, node);
+    }
+    empty
+}
+
+/// Empty directories below `node`, not counting `node` itself
+fn empty_dirs(node: &FileNode) -> Vec<FileNode> {
+    let mut found = Vec::new();
+    for child in &node.children {
+        collect_empty_dirs(child, &mut found);
+    }
+    found.into_iter().map(FileNode::detached).collect()
+}
+
+/// Finds every directory below `path` that holds no files, including ones
+/// that only contain other empty directories. Directories that may not be
+/// empty after all are not reported, see `collect_empty_dirs`.
+#[tauri::command]
+pub async fn find_empty_dirs(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<Vec<FileNode>, ScanError> {
+    scanner.with_node(&path, empty_dirs)
+}
+
+/// Totals the files below `path` by category, largest first. Categories
+/// without files are left out.
+#[tauri::command]
+pub async fn usage_by_category(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<Vec<CategoryStat>, ScanError> {
+    scanner.with_node(&path, category_usage)
+}
+
+/// Totals the files below `node` by category, see `usage_by_category`
+fn category_usage(node: &FileNode) -> Vec<CategoryStat> {
+    let mut stats: HashMap<Category, CategoryStat> = HashMap::new();
+    for file in node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive)
+    {
+        let category = Category::of(file.extension.as_deref());
+        let stat = stats.entry(category).or_insert(CategoryStat {
+            category,
+            total_bytes: 0,
+            file_count: 0,
+        });
+        stat.total_bytes = stat.total_bytes.saturating_add(file.size);
+        stat.file_count += 1;
+    }
+    let mut stats: Vec<CategoryStat> = stats.into_values().collect();
+    stats.sort_by_key(|stat| Reverse(stat.total_bytes));
+    stats
+}
+
+/// Totals the files below `path` by owner, largest first. Always empty on
+/// platforms without user ids.
+#[tauri::command]
+pub async fn usage_by_owner(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<Vec<OwnerStat>, ScanError> {
+    let names = user_names(&fs::read_to_string("/etc/passwd").unwrap_or_default());
+    scanner.with_node(&path, |node| owner_usage(node, &names))
+}
+
+/// Totals the files below `node` by owner, named after `names`
+fn owner_usage(node: &FileNode, names: &HashMap<u32, String>) -> Vec<OwnerStat> {
+    let mut stats: HashMap<u32, OwnerStat> = HashMap::new();
+    for file in node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive)
+    {
+        let Some(uid) = file.uid else {
+            continue;
+        };
+        let stat = stats.entry(uid).or_insert_with(|| OwnerStat {
+            uid,
+            owner_name: names.get(&uid).cloned(),
+            total_bytes: 0,
+            file_count: 0,
+        });
+        stat.total_bytes = stat.total_bytes.saturating_add(file.size);
+        stat.file_count += 1;
+    }
+    let mut stats: Vec<OwnerStat> = stats.into_values().collect();
+    stats.sort_by_key(|stat| Reverse(stat.total_bytes));
+    stats
+}
+
+/// Counts the files below `node` by size into the buckets `boundaries`
+/// split, labeled in `units`
+fn histogram(node: &FileNode, mut boundaries: Vec<u64>, units: SizeUnits) -> Vec<HistogramBucket> {
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut buckets: Vec<HistogramBucket> = (0..=boundaries.len())
+        .map(|i| {
+            let range_label = match (i.checked_sub(1).map(|i| boundaries[i]), boundaries.get(i)) {
+                (None, Some(&upper)) => format!("< {}", units.format(upper)),
+                (Some(lower), Some(&upper)) => {
+                    format!("{} – {}", units.format(lower), units.format(upper))
+                }
+                (Some(lower), None) => format!("≥ {}", units.format(lower)),
+                (None, None) => "All sizes".to_string(),
+            };
+            HistogramBucket {
+                range_label,
+                count: 0,
+                total_bytes: 0,
+            }
+        })
+        .collect();
+
+    for file in node
+        .iter()
+        .filter(|node| !node.is_directory && !node.in_archive)
+    {
+        let bucket = &mut buckets[boundaries.partition_point(|&bound| bound <= file.size)];
+        bucket.count += 1;
+        bucket.total_bytes = bucket.total_bytes.saturating_add(file.size);
+    }
+    buckets
+}
+
+/// Counts the files below `path` by size. `boundaries` split the buckets
+/// (each bucket includes its lower bound), defaulting to 1 KiB, 1 MiB,
+/// 100 MiB and 1 GiB. Labels use the units chosen in the settings.
+#[tauri::command]
+pub async fn size_histogram(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    boundaries: Option<Vec<u64>>,
+) -> Result<Vec<HistogramBucket>, ScanError> {
+    let boundaries = boundaries.unwrap_or_else(|| DEFAULT_HISTOGRAM_BOUNDARIES.to_vec());
+    let units = settings::load(&app).units;
+    scanner.with_node(&path, |node| histogram(node, boundaries, units))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use filetime::FileTime;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::disk_scanner::{ProgressTracker, ScanOptions};
+    use crate::paths::{encode_path, NodePath};
+
+    /// Scans `dir` with `options` and runs `f` on the cached root
+    fn with_scanned<R>(dir: &Path, options: ScanOptions, f: impl FnOnce(&FileNode) -> R) -> R {
+        let scanner = DiskScanner::new();
+        let path = encode_path(dir);
+        scanner
+            .build_cache_tracked(&path, options, Arc::new(ProgressTracker::silent()))
+            .unwrap();
+        scanner.with_node(&path, f).unwrap()
+    }
+
+    fn names(nodes: &[FileNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn empty_dirs_include_nested_chains() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::create_dir_all(dir.path().join("full/empty")).unwrap();
+        fs::write(dir.path().join("full/file"), b"data").unwrap();
+
+        let mut found = with_scanned(dir.path(), ScanOptions::default(), empty_dirs);
+        found.sort_by_key(|node| node.path.to_string());
+        assert_eq!(names(&found), ["a", "b", "c", "empty"]);
+    }
+
+    #[test]
+    fn empty_dirs_skip_directories_cut_off_by_the_depth_limit() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        let options = ScanOptions {
+            max_scan_depth: Some(1),
+            ..Default::default()
+        };
+
+        let found = with_scanned(dir.path(), options, empty_dirs);
+        assert!(found.is_empty(), "{:?}", names(&found));
+    }
+
+    fn file(name: &str, size: u64) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            size,
+            ..FileNode::default()
+        }
+    }
+
+    fn dir(name: &str, children: Vec<FileNode>) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            is_directory: true,
+            children,
+            ..FileNode::default()
+        }
+    }
+
+    /// Gives `node` and its descendants their paths below `parent`
+    fn with_paths(mut node: FileNode, parent: &str) -> FileNode {
+        let path = format!("{}/{}", parent, node.name);
+        node.path = NodePath::from(path.clone());
+        node.children = node
+            .children
+            .into_iter()
+            .map(|child| with_paths(child, &path))
+            .collect();
+        node
+    }
+
+    #[test]
+    fn histogram_buckets_files_by_size() {
+        let tree = dir(
+            "root",
+            vec![
+                file("tiny", 10),
+                file("kib", 1024),
+                dir("sub", vec![file("mib", 1 << 20), file("big", 200 << 20)]),
+                file("huge", 2 << 30),
+                FileNode {
+                    in_archive: true,
+                    ..file("archived", 10)
+                },
+            ],
+        );
+        let buckets = histogram(
+            &tree,
+            DEFAULT_HISTOGRAM_BOUNDARIES.to_vec(),
+            SizeUnits::Binary,
+        );
+        let counts: Vec<(&str, u64, u64)> = buckets
+            .iter()
+            .map(|b| (b.range_label.as_str(), b.count, b.total_bytes))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("< 1.0 KiB", 1, 10),
+                ("1.0 KiB – 1.0 MiB", 1, 1024),
+                ("1.0 MiB – 100.0 MiB", 1, 1 << 20),
+                ("100.0 MiB – 1.0 GiB", 1, 200 << 20),
+                ("≥ 1.0 GiB", 1, 2 << 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn histogram_labels_follow_the_units_and_sort_boundaries() {
+        let tree = dir(
+            "root",
+            vec![
+                file("a", 1500),
+                file("b", 500),
+                file("c", 1000),
+                file("d", 10),
+            ],
+        );
+        // Unsorted and repeated boundaries count once each, in order
+        let buckets = histogram(&tree, vec![1000, 10, 1000], SizeUnits::Decimal);
+        let counts: Vec<(&str, u64)> = buckets
+            .iter()
+            .map(|b| (b.range_label.as_str(), b.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [("< 10 B", 0), ("10 B – 1.0 KB", 2), ("≥ 1.0 KB", 2)]
+        );
+
+        let all = histogram(&tree, Vec::new(), SizeUnits::Decimal);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].range_label, "All sizes");
+        assert_eq!((all[0].count, all[0].total_bytes), (4, 3010));
+    }
+
+    #[test]
+    fn stale_files_are_the_large_ones_untouched_since_the_cutoff() {
+        let dir = TempDir::new().unwrap();
+        let old = FileTime::from_unix_time(days_ago(400) as i64, 0);
+        for (name, size, mtime) in [
+            ("old_big", 20_000, Some(old)),
+            ("old_bigger", 40_000, Some(old)),
+            ("old_small", 10, Some(old)),
+            ("new_big", 20_000, None),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, vec![0; size]).unwrap();
+            if let Some(mtime) = mtime {
+                filetime::set_file_mtime(&path, mtime).unwrap();
+            }
+        }
+
+        let stale = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            modified_before(node, days_ago(365), 10_000)
+        });
+        assert_eq!(names(&stale), ["old_bigger", "old_big"]);
+        let none = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            modified_before(node, days_ago(500), 10_000)
+        });
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn never_read_compares_access_and_write_times() {
+        let dir = TempDir::new().unwrap();
+        let written = FileTime::from_unix_time(days_ago(10) as i64, 0);
+        // After the file was created too, which cannot be set and is now
+        let read = FileTime::from_unix_time(days_ago(0) as i64 + 60, 0);
+        let unread = dir.path().join("unread");
+        let opened = dir.path().join("opened");
+        for path in [&unread, &opened] {
+            fs::write(path, b"data").unwrap();
+        }
+        filetime::set_file_times(&unread, written, written).unwrap();
+        filetime::set_file_times(&opened, read, written).unwrap();
+        assert!(never_read(&unread));
+        assert!(!never_read(&opened));
+    }
+
+    #[test]
+    fn never_opened_files_are_the_large_unread_ones() {
+        let dir = TempDir::new().unwrap();
+        let written = FileTime::from_unix_time(days_ago(10) as i64, 0);
+        let read = FileTime::from_unix_time(days_ago(0) as i64 + 60, 0);
+        for (name, size, accessed) in [
+            ("download.iso", 50_000, written),
+            ("installer.dmg", 20_000, written),
+            ("opened.mkv", 80_000, read),
+            ("small", 10, written),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, vec![0; size]).unwrap();
+            filetime::set_file_times(&path, accessed, written).unwrap();
+        }
+
+        let candidates = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            files_at_least(node, 10_000)
+        });
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(
+            names(&unread(candidates)),
+            ["download.iso", "installer.dmg"]
+        );
+    }
+
+    #[test]
+    fn categories_come_from_the_scanned_extension() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("photo1.JPG"), vec![0; 8000]).unwrap();
+        fs::write(dir.path().join("song.mp3"), b"data").unwrap();
+        fs::write(dir.path().join("README"), b"data").unwrap();
+        // Only files are categorized, whatever their directory is called
+        fs::create_dir(dir.path().join("videos.mp4")).unwrap();
+        fs::write(dir.path().join("videos.mp4/notes.txt"), b"data").unwrap();
+
+        let stats = with_scanned(dir.path(), ScanOptions::default(), category_usage);
+        let mut counts: Vec<(Category, u64)> = stats
+            .iter()
+            .map(|stat| (stat.category, stat.file_count))
+            .collect();
+        assert_eq!(counts[0], (Category::Images, 1));
+        counts.sort_by_key(|&(category, _)| category as u8);
+        assert_eq!(
+            counts,
+            [
+                (Category::Images, 1),
+                (Category::Documents, 1),
+                (Category::Audio, 1),
+                (Category::Other, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_files_are_the_top_n_largest_first() {
+        let tree = dir(
+            "root",
+            vec![
+                file("small", 10),
+                dir(
+                    "sub",
+                    vec![file("biggest", 9000), file("mid", 500), file("tied_b", 700)],
+                ),
+                file("big", 8000),
+                file("tied_a", 700),
+                FileNode {
+                    in_archive: true,
+                    ..file("archived", 100_000)
+                },
+            ],
+        );
+        let tree = with_paths(tree, "");
+
+        // Ties are broken by path
+        let top = top_files(&tree, 4);
+        assert_eq!(names(&top), ["biggest", "big", "tied_b", "tied_a"]);
+        assert_eq!(top[0].path.to_string(), "/root/sub/biggest");
+        assert_eq!(top_files(&tree, 100).len(), 6);
+        assert!(top_files(&tree, 0).is_empty());
+    }
+
+    #[test]
+    fn search_returns_all_and_only_matching_names() {
+        let tree = with_paths(
+            dir(
+                "report",
+                vec![
+                    file("Report.pdf", 10),
+                    dir(
+                        "old reports",
+                        vec![file("q1-report.txt", 10), file("notes", 10)],
+                    ),
+                    file("summary", 10),
+                ],
+            ),
+            "",
+        );
+
+        let mut found = search_names(&tree, "report", false);
+        found.sort_by_key(|node| node.path.to_string());
+        let paths: Vec<String> = found.iter().map(|node| node.path.to_string()).collect();
+        // The searched directory itself is not a result
+        assert_eq!(
+            paths,
+            [
+                "/report/Report.pdf",
+                "/report/old reports",
+                "/report/old reports/q1-report.txt",
+            ]
+        );
+        assert!(found.iter().all(|node| node.children.is_empty()));
+
+        let mut found = search_names(&tree, "report", true);
+        found.sort_by_key(|node| node.path.to_string());
+        assert_eq!(names(&found), ["old reports", "q1-report.txt"]);
+        assert!(search_names(&tree, "missing", false).is_empty());
+    }
+
+    #[test]
+    fn size_maps_hold_every_path_down_to_the_depth() {
+        let tree = with_paths(
+            FileNode {
+                size: 600,
+                ..dir(
+                    "root",
+                    vec![
+                        FileNode {
+                            size: 500,
+                            ..dir("sub", vec![file("deep", 500)])
+                        },
+                        file("top", 100),
+                    ],
+                )
+            },
+            "",
+        );
+
+        let sizes = sizes_by_path(&tree, 1);
+        let expected: HashMap<String, u64> =
+            [("/root", 600), ("/root/sub", 500), ("/root/top", 100)]
+                .into_iter()
+                .map(|(path, size)| (path.to_string(), size))
+                .collect();
+        assert_eq!(sizes, expected);
+        assert_eq!(sizes_by_path(&tree, 2)["/root/sub/deep"], 500);
+        assert_eq!(sizes_by_path(&tree, 0).len(), 1);
+    }
+
+    #[test]
+    fn videos_and_code_get_their_own_categories() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("clip.mp4"), vec![0; 40_000]).unwrap();
+        fs::write(dir.path().join("movie.MKV"), vec![0; 20_000]).unwrap();
+        fs::write(dir.path().join("src/main.rs"), b"fn main() {}").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), b"").unwrap();
+
+        let stats = with_scanned(dir.path(), ScanOptions::default(), category_usage);
+        let stat = |category| stats.iter().find(|stat| stat.category == category).unwrap();
+        assert_eq!(stats[0].category, Category::Videos);
+        assert_eq!(stat(Category::Videos).file_count, 2);
+        assert!(stat(Category::Videos).total_bytes >= 60_000);
+        assert_eq!(stat(Category::Code).file_count, 2);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(Category::of(Some("mp4")), Category::Videos);
+        assert_eq!(Category::of(Some("rs")), Category::Code);
+        assert_eq!(Category::of(None), Category::Other);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_current_users_files_are_summed() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for (name, size) in [("a", 10_000), ("sub/b", 30_000)] {
+            fs::write(dir.path().join(name), vec![0; size]).unwrap();
+        }
+        // SAFETY: geteuid has no preconditions and cannot fail
+        let uid = unsafe { libc::geteuid() };
+        let names = HashMap::from([(uid, "me".to_string())]);
+
+        let (stats, size) = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            (owner_usage(node, &names), node.size)
+        });
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].uid, uid);
+        assert_eq!(stats[0].owner_name.as_deref(), Some("me"));
+        assert_eq!((stats[0].total_bytes, stats[0].file_count), (size, 2));
+    }
+
+    #[test]
+    fn owners_are_sorted_by_usage_and_named_from_passwd() {
+        let names = user_names(
+            "# comment\nroot:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\nbroken\n",
+        );
+        assert_eq!(names.len(), 2);
+        let owned = |name: &str, size: u64, uid: u32| FileNode {
+            uid: Some(uid),
+            ..file(name, size)
+        };
+        let tree = dir(
+            "root",
+            vec![
+                owned("a", 100, 0),
+                owned("b", 500, 1000),
+                owned("c", 200, 1000),
+                owned("d", 50, 4242),
+                file("unknown owner", 10_000),
+            ],
+        );
+        let stats = owner_usage(&tree, &names);
+        let stats: Vec<(u32, Option<&str>, u64, u64)> = stats
+            .iter()
+            .map(|s| (s.uid, s.owner_name.as_deref(), s.total_bytes, s.file_count))
+            .collect();
+        assert_eq!(
+            stats,
+            [
+                (1000, Some("alice"), 700, 2),
+                (0, Some("root"), 100, 1),
+                (4242, None, 50, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_dirs_come_from_anywhere_below() {
+        let sized_dir = |name: &str, size: u64, children| FileNode {
+            size,
+            ..dir(name, children)
+        };
+        let tree = with_paths(
+            sized_dir(
+                "root",
+                1000,
+                vec![
+                    sized_dir(
+                        "a",
+                        700,
+                        vec![sized_dir("deep", 600, vec![file("f", 600)]), file("g", 100)],
+                    ),
+                    sized_dir("b", 200, vec![file("h", 200)]),
+                    file("huge file", 5000),
+                    FileNode {
+                        in_archive: true,
+                        ..sized_dir("archived", 900, Vec::new())
+                    },
+                ],
+            ),
+            "",
+        );
+
+        let top = top_dirs(&tree, 2);
+        let paths: Vec<String> = top.iter().map(|node| node.path.to_string()).collect();
+        assert_eq!(paths, ["/root/a", "/root/a/deep"]);
+        assert!(top[0].children.is_empty());
+        // The searched directory itself is not a result
+        assert_eq!(names(&top_dirs(&tree, 10)), ["a", "deep", "b"]);
+    }
+
+    #[test]
+    fn recent_files_are_the_ones_touched_in_the_window() {
+        let dir = TempDir::new().unwrap();
+        for (name, days) in [("old", 30), ("yesterday", 1), ("week", 6)] {
+            let path = dir.path().join(name);
+            fs::write(&path, b"data").unwrap();
+            let mtime = FileTime::from_unix_time(days_ago(days) as i64, 0);
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+        fs::write(dir.path().join("touched"), b"data").unwrap();
+
+        let recent = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            modified_since(node, days_ago(7), 10)
+        });
+        assert_eq!(names(&recent), ["touched", "yesterday", "week"]);
+        let limited = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            modified_since(node, days_ago(7), 1)
+        });
+        assert_eq!(names(&limited), ["touched"]);
+    }
+
+    #[test]
+    fn junk_directories_are_flagged_with_their_sizes() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("web/node_modules/pkg/target")).unwrap();
+        fs::write(
+            dir.path().join("web/node_modules/pkg/index.js"),
+            vec![0; 20_000],
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("crate/target/debug")).unwrap();
+        fs::write(dir.path().join("crate/target/debug/app"), vec![0; 50_000]).unwrap();
+        fs::write(dir.path().join("crate/main.rs"), b"fn main() {}").unwrap();
+
+        let (junk, sizes) = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            let rules = junk_rules(None);
+            let junk = junk_dirs(node, |node| rules.get(&node.name).cloned());
+            let sizes = sizes_by_path(node, u32::MAX);
+            (junk, sizes)
+        });
+        let flagged: Vec<(&str, &str)> = junk
+            .iter()
+            .map(|dir| (dir.path.as_str(), dir.label.as_str()))
+            .collect();
+        let target = encode_path(&dir.path().join("crate/target"));
+        let node_modules = encode_path(&dir.path().join("web/node_modules"));
+        // The `target` inside `node_modules` is not reported on its own
+        assert_eq!(
+            flagged,
+            [
+                (target.as_str(), "Build output"),
+                (node_modules.as_str(), "Node.js dependencies")
+            ]
+        );
+        for found in &junk {
+            assert_eq!(Some(&found.size), sizes.get(&found.path));
+            assert_eq!(found.total_files, 1);
+        }
+        assert!(junk[0].size >= 50_000 && junk[1].size >= 20_000);
+
+        let rules = junk_rules(Some(vec![JunkRule {
+            name: "pkg".to_string(),
+            label: "Package".to_string(),
+        }]));
+        let custom = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            junk_dirs(node, |node| rules.get(&node.name).cloned())
+        });
+        let labels: Vec<&str> = custom.iter().map(|dir| dir.label.as_str()).collect();
+        assert_eq!(labels, ["Package"]);
+    }
+
+    #[test]
+    fn advanced_search_matches_in_every_mode() {
+        let tree = with_paths(
+            dir(
+                "srv",
+                vec![
+                    file("App.LOG", 10),
+                    dir(
+                        "logs",
+                        vec![
+                            file("error.log", 10),
+                            dir("cache", vec![file("old.log.gz", 10)]),
+                        ],
+                    ),
+                    file("catalog", 10),
+                ],
+            ),
+            "",
+        );
+        let search = |pattern: &str, mode| {
+            let mut found = search_matching(&tree, &Matcher::new(pattern, mode).unwrap());
+            found.sort_by_key(|node| node.path.to_string());
+            found
+                .iter()
+                .map(|node| node.path.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search("LOG", MatchMode::Substring),
+            [
+                "/srv/App.LOG",
+                "/srv/catalog",
+                "/srv/logs",
+                "/srv/logs/cache/old.log.gz",
+                "/srv/logs/error.log",
+            ]
+        );
+        // Globs without a `/` look at names, at any depth and with case
+        assert_eq!(search("*.log", MatchMode::Glob), ["/srv/logs/error.log"]);
+        assert_eq!(
+            search("logs/**/*.gz", MatchMode::Glob),
+            ["/srv/logs/cache/old.log.gz"]
+        );
+        assert!(search("cache/*", MatchMode::Glob).is_empty());
+        // Regular expressions look at the path below the searched directory
+        assert_eq!(
+            search(r"^logs/.*\.log", MatchMode::Regex),
+            ["/srv/logs/cache/old.log.gz", "/srv/logs/error.log"]
+        );
+        assert_eq!(search(r"(?i)\.log$", MatchMode::Regex).len(), 2);
+    }
+
+    #[test]
+    fn invalid_search_patterns_are_reported() {
+        for (pattern, mode) in [("[a-", MatchMode::Glob), ("(unclosed", MatchMode::Regex)] {
+            match Matcher::new(pattern, mode) {
+                Err(ScanError::InvalidPattern(message)) => {
+                    assert!(message.starts_with(pattern), "{}", message)
+                }
+                _ => panic!("{} was accepted", pattern),
+            }
+        }
+        // Any text is a valid substring
+        assert!(Matcher::new("(unclosed", MatchMode::Substring).is_ok());
+    }
+
+    #[test]
+    fn reclaimable_space_totals_trash_and_junk() {
+        let sized_dir = |name: &str, size: u64, children| FileNode {
+            size,
+            ..dir(name, children)
+        };
+        let tree = with_paths(
+            sized_dir(
+                "home",
+                10_000,
+                vec![
+                    sized_dir(
+                        "web",
+                        4000,
+                        vec![
+                            sized_dir("node_modules", 3000, Vec::new()),
+                            file("index.js", 1000),
+                        ],
+                    ),
+                    sized_dir(
+                        "app",
+                        2500,
+                        vec![
+                            sized_dir("node_modules", 500, Vec::new()),
+                            sized_dir("target", 2000, Vec::new()),
+                        ],
+                    ),
+                    sized_dir(
+                        ".local",
+                        1500,
+                        vec![sized_dir(
+                            "share",
+                            1500,
+                            vec![sized_dir("Trash", 1500, Vec::new())],
+                        )],
+                    ),
+                    file("notes", 2000),
+                ],
+            ),
+            "",
+        );
+
+        let report = reclaimable(&tree);
+        let categories: Vec<(&str, u64, usize)> = report
+            .categories
+            .iter()
+            .map(|category| (category.label.as_str(), category.size, category.dir_count))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                ("Node.js dependencies", 3500, 2),
+                ("Build output", 2000, 1),
+                ("Trash", 1500, 1),
+            ]
+        );
+        assert_eq!(report.reclaimable, 7000);
+    }
+
+    #[test]
+    fn copied_directory_trees_are_reported_once() {
+        let dir = TempDir::new().unwrap();
+        let write = |relative: &str, contents: &[u8]| {
+            let path = dir.path().join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        for copy in ["project", "backup/project"] {
+            write(&format!("{}/src/main.rs", copy), &[b'a'; 6000]);
+            write(&format!("{}/README", copy), b"readme");
+        }
+        // Same layout and lengths, different contents
+        write("other/src/main.rs", &[b'b'; 6000]);
+        write("other/README", b"readme");
+        write("unrelated", b"data");
+
+        let groups = with_scanned(dir.path(), ScanOptions::default(), |node| {
+            let candidates = dir_candidates(node);
+            // `src` of all three copies and the three copies themselves
+            assert_eq!(candidates.len(), 6);
+            duplicate_groups(&candidates)
+        });
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        assert_eq!(
+            group.paths,
+            [
+                encode_path(&dir.path().join("backup/project")),
+                encode_path(&dir.path().join("project")),
+            ]
+        );
+        assert!(group.size >= 6006);
+        assert_eq!(group.reclaimable, group.size);
+    }
+
+    #[test]
+    fn size_ranges_are_inclusive_and_open_ended_at_zero() {
+        let tree = with_paths(
+            dir(
+                "root",
+                vec![
+                    file("tiny", 10),
+                    file("low", 100),
+                    dir("sub", vec![file("mid", 500), file("high", 1000)]),
+                    file("huge", 50_000),
+                    FileNode {
+                        in_archive: true,
+                        ..file("archived", 500)
+                    },
+                ],
+            ),
+            "",
+        );
+        let range = |min, max| names(&files_sized_between(&tree, min, max)).join(" ");
+
+        assert_eq!(range(100, 1000), "high mid low");
+        assert_eq!(range(101, 999), "mid");
+        assert_eq!(range(1000, 0), "huge high");
+        assert_eq!(range(0, 0), "huge high mid low tiny");
+        assert_eq!(range(2000, 10_000), "");
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::{Component, Path};
+
+use zip::ZipArchive;
+
+use crate::disk_scanner::FileNode;
+use crate::paths::{display_name, encode_os_str, file_extension, NodePath};
+
+/// Whether `path` names a ZIP archive, judging by its extension
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// A directory inside an archive while its entries are collected. Archives
+/// need not list directories before (or at all) the files inside them.
+#[derive(Default)]
+struct ArchiveDir {
+    /// Keyed by encoded name, as the files are
+    dirs: BTreeMap<String, ArchiveDir>,
+    files: Vec<(String, FileNode)>,
+    mode: Option<u32>,
+}
+
+impl ArchiveDir {
+    fn dir(&mut self, parts: &[String]) -> &mut ArchiveDir {
+        parts
+            .iter()
+            .fold(self, |dir, part| dir.dirs.entry(part.clone()).or_default())
+    }
+
+    fn into_children(self, path: &NodePath) -> Vec<FileNode> {
+        let mut children: Vec<FileNode> = self
+            .dirs
+            .into_iter()
+            .map(|(name, dir)| {
+                let dir_path = path.join(&name);
+                let mode = dir.mode;
+                let children = dir.into_children(&dir_path);
+                let mut node = FileNode {
+                    name: display_name(&name),
+                    is_directory: true,
+                    children_count: children.len(),
+                    children,
+                    path: dir_path,
+                    show: true,
+                    mode,
+                    in_archive: true,
+                    ..Default::default()
+                };
+                node.refresh_aggregates(0);
+                node.sort_by_size();
+                node
+            })
+            .collect();
+        children.extend(self.files.into_iter().map(|(name, file)| FileNode {
+            path: path.join(&name),
+            ..file
+        }));
+        children
+    }
+}
+
+/// Lists the entries of the ZIP archive at `path`, whose node has the path
+/// `node_path`, as nodes below it, flagged `in_archive` and sized by their
+/// uncompressed size. Entries whose names would escape the archive are left
+/// out.
+pub fn archive_children(path: &Path, node_path: &NodePath) -> io::Result<Vec<FileNode>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut root = ArchiveDir::default();
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let Some(relative_path) = entry.enclosed_name() else {
+            continue;
+        };
+        let parts: Vec<String> = relative_path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(encode_os_str(part)),
+                _ => None,
+            })
+            .collect();
+        let Some((name, parents)) = parts.split_last() else {
+            continue;
+        };
+        let mode = entry.unix_mode().map(|mode| mode & 0o7777);
+        if entry.is_dir() {
+            root.dir(&parts).mode = mode;
+            continue;
+        }
+        let display = display_name(name);
+        root.dir(parents).files.push((
+            name.clone(),
+            FileNode {
+                extension: file_extension(Path::new(&display)),
+                name: display,
+                size: entry.size(),
+                apparent_size: entry.size(),
+                total_files: 1,
+                show: true,
+                mode,
+                in_archive: true,
+                ..Default::default()
+            },
+        ));
+    }
+    Ok(root.into_children(node_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::*;
+    use crate::paths::decode_path;
+
+    /// Writes a ZIP archive at `path` with `entries`, directories ending in `/`
+    fn write_zip(path: &Path, entries: &[(&str, usize)]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for &(name, size) in entries {
+            if name.ends_with('/') {
+                writer.add_directory(name, options).unwrap();
+            } else {
+                writer.start_file(name, options).unwrap();
+                writer.write_all(&vec![b'x'; size]).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn entries_are_listed_as_nodes_inside_the_archive() {
+        let dir = TempDir::new().unwrap();
+        let zip = dir.path().join("bundle.ZIP");
+        write_zip(
+            &zip,
+            &[
+                ("docs/inner/b.bin", 50),
+                ("docs/a.txt", 100),
+                ("top.md", 10),
+                ("50%/off.txt", 5),
+                ("empty/", 0),
+                ("../escape", 10),
+            ],
+        );
+        assert!(is_archive(&zip));
+
+        let children = archive_children(&zip, &NodePath::from("/bundle.ZIP".to_string())).unwrap();
+        let names: Vec<&str> = children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["50%", "docs", "empty", "top.md"]);
+        assert!(children.iter().all(|child| child.in_archive));
+
+        let docs = &children[1];
+        assert!(docs.is_directory);
+        assert_eq!((docs.size, docs.total_files), (150, 2));
+        assert_eq!(docs.path.to_string(), "/bundle.ZIP/docs");
+        let a = docs.children.iter().find(|c| c.name == "a.txt").unwrap();
+        assert_eq!((a.size, a.apparent_size), (100, 100));
+        assert_eq!(a.extension.as_deref(), Some("txt"));
+        assert_eq!(a.path.to_string(), "/bundle.ZIP/docs/a.txt");
+        assert!(children[2].is_directory && children[2].children.is_empty());
+        let off = &children[0].children[0];
+        assert_eq!(off.name, "off.txt");
+        assert_eq!(
+            decode_path(&off.path.to_string()),
+            Path::new("/bundle.ZIP/50%/off.txt")
+        );
+    }
+
+    #[test]
+    fn other_files_are_not_archives() {
+        let dir = TempDir::new().unwrap();
+        let fake = dir.path().join("fake.zip");
+        std::fs::write(&fake, b"not a zip").unwrap();
+        assert!(archive_children(&fake, &NodePath::from("/fake.zip".to_string())).is_err());
+        assert!(!is_archive(Path::new("archive.tar")));
+    }
+}
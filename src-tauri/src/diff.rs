@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::disk_scanner::{CacheDrift, DiskScanner, FileNode};
+use crate::error::ScanError;
+
+/// How a path differs between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A path whose size differs between two snapshots. Sizes of paths missing
+/// from one side are reported as 0.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub delta: i64,
+    pub status: DiffStatus,
+}
+
+/// Reads a tree written by `export_json`
+fn read_snapshot(file: &str) -> Result<FileNode, ScanError> {
+    let reader = File::open(file)
+        .map(BufReader::new)
+        .map_err(|e| ScanError::Io(format!("Failed to open {}: {}", file, e)))?;
+    serde_json::from_reader(reader)
+        .map_err(|e| ScanError::Io(format!("Failed to read {}: {}", file, e)))
+}
+
+/// Compares two trees by path, biggest changes first
+fn diff_nodes(old: &FileNode, new: &FileNode) -> Vec<DiffEntry> {
+    let old_sizes: HashMap<String, u64> =
+        old.iter().map(|n| (n.path.to_string(), n.size)).collect();
+    let new_sizes: HashMap<String, u64> =
+        new.iter().map(|n| (n.path.to_string(), n.size)).collect();
+
+    let mut entries: Vec<DiffEntry> = new_sizes
+        .iter()
+        .filter_map(|(path, &new_size)| match old_sizes.get(path) {
+            None => Some((path, 0, new_size, DiffStatus::Added)),
+            Some(&old_size) if old_size != new_size => {
+                Some((path, old_size, new_size, DiffStatus::Changed))
+            }
+            Some(_) => None,
+        })
+        .chain(
+            old_sizes
+                .iter()
+                .filter(|(path, _)| !new_sizes.contains_key(*path))
+                .map(|(path, &old_size)| (path, old_size, 0, DiffStatus::Removed)),
+        )
+        .map(|(path, old_size, new_size, status)| DiffEntry {
+            path: path.clone(),
+            old_size,
+            new_size,
+            delta: (new_size as i128 - old_size as i128).clamp(i64::MIN as i128, i64::MAX as i128)
+                as i64,
+            status,
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.delta
+            .unsigned_abs()
+            .cmp(&a.delta.unsigned_abs())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    entries
+}
+
+/// Compares two snapshots written by `export_json` and lists every path that
+/// was added, removed or changed size between them
+#[tauri::command]
+pub async fn diff_trees(old_file: String, new_file: String) -> Result<Vec<DiffEntry>, ScanError> {
+    let old = read_snapshot(&old_file)?;
+    let new = read_snapshot(&new_file)?;
+    Ok(diff_nodes(&old, &new))
+}
+
+/// Counts how far the cached tree at `path` is off from the filesystem, to
+/// tell whether it is worth rescanning
+#[tauri::command]
+pub async fn cache_drift(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<CacheDrift, ScanError> {
+    scanner.drift(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::paths::NodePath;
+
+    fn node(path: &str, size: u64, children: Vec<FileNode>) -> FileNode {
+        FileNode {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: NodePath::from(path.to_string()),
+            size,
+            is_directory: !children.is_empty(),
+            children,
+            ..FileNode::default()
+        }
+    }
+
+    #[test]
+    fn added_and_grown_files_are_reported() {
+        let old = node(
+            "/root",
+            300,
+            vec![
+                node("/root/a", 100, Vec::new()),
+                node("/root/b", 200, Vec::new()),
+            ],
+        );
+        let new = node(
+            "/root",
+            550,
+            vec![
+                node("/root/a", 100, Vec::new()),
+                node("/root/b", 250, Vec::new()),
+                node("/root/c", 200, Vec::new()),
+            ],
+        );
+        let dir = TempDir::new().unwrap();
+        let write = |name: &str, tree: &FileNode| {
+            let file = dir.path().join(name);
+            serde_json::to_writer(File::create(&file).unwrap(), tree).unwrap();
+            file.to_str().unwrap().to_string()
+        };
+        let old = read_snapshot(&write("old.json", &old)).unwrap();
+        let new = read_snapshot(&write("new.json", &new)).unwrap();
+
+        let entries = diff_nodes(&old, &new);
+        let entries: Vec<(&str, u64, u64, i64, DiffStatus)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.old_size, e.new_size, e.delta, e.status))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("/root", 300, 550, 250, DiffStatus::Changed),
+                ("/root/c", 0, 200, 200, DiffStatus::Added),
+                ("/root/b", 200, 250, 50, DiffStatus::Changed),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_files_shrink_by_their_size() {
+        let old = node("/root", 100, vec![node("/root/a", 100, Vec::new())]);
+        let new = FileNode {
+            is_directory: true,
+            ..node("/root", 0, Vec::new())
+        };
+        let entries = diff_nodes(&old, &new);
+        let removed = entries.iter().find(|e| e.path == "/root/a").unwrap();
+        assert_eq!(removed.status, DiffStatus::Removed);
+        assert_eq!((removed.new_size, removed.delta), (0, -100));
+    }
+
+    #[test]
+    fn missing_snapshots_are_io_errors() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing.json");
+        assert!(matches!(
+            read_snapshot(missing.to_str().unwrap()),
+            Err(ScanError::Io(_))
+        ));
+    }
+}
@@ -1,19 +1,1193 @@
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
 
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use parking_lot::Mutex;
+use rand::RngCore;
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, State};
+
+use crate::disk_scanner::{allocated_size, DiskScanner};
+use crate::error::ScanError;
+use crate::paths::{decode_path, encode_path, extended_length};
+use crate::settings::{self, check_protected};
+use crate::trash;
+
+/// Progress information reported while deleting items
+#[derive(Clone, Serialize)]
+pub struct DeleteProgress {
+    pub items_deleted: u64,
+    pub bytes_freed: u64,
+    pub current_path: String,
+}
+
+/// Outcome of an operation on one of several paths
+#[derive(Clone, Serialize)]
+pub struct PathResult {
+    pub path: String,
+    /// Why the operation failed, `None` on success
+    pub error: Option<ScanError>,
+}
+
+/// Outcome of moving one of the paths passed to `move_items`
+#[derive(Clone, Serialize)]
+pub struct MoveResult {
+    pub path: String,
+    /// Where the item ended up, `None` on failure
+    pub destination: Option<String>,
+    pub bytes_moved: u64,
+    /// Why the move failed, `None` on success
+    pub error: Option<ScanError>,
+}
+
+/// How long a token from `prepare_deletion` can be used for
+const PLAN_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// What deleting a set of paths would remove, returned by `prepare_deletion`
+#[derive(Clone, Serialize)]
+pub struct DeletionPlan {
+    pub total_bytes: u64,
+    pub file_count: u64,
+    /// Directories removed, including the given ones themselves
+    pub dir_count: u64,
+    /// Passed to `delete_items` to delete exactly this set of paths
+    pub token: String,
+}
+
+struct PreparedDeletion {
+    /// Sorted and deduplicated paths the plan was computed for
+    paths: Vec<String>,
+    prepared_at: Instant,
+}
+
+/// Deletions previewed with `prepare_deletion`, keyed by token. A token
+/// can be used once, for the exact paths it was issued for, within
+/// `PLAN_LIFETIME`.
+#[derive(Default)]
+pub struct DeletionPlans {
+    plans: Mutex<HashMap<String, PreparedDeletion>>,
+}
+
+impl DeletionPlans {
+    fn path_set(paths: &[String]) -> Vec<String> {
+        let mut paths = paths.to_vec();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    fn issue(&self, paths: &[String]) -> String {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        let mut plans = self.plans.lock();
+        plans.retain(|_, plan| plan.prepared_at.elapsed() < PLAN_LIFETIME);
+        plans.insert(
+            token.clone(),
+            PreparedDeletion {
+                paths: Self::path_set(paths),
+                prepared_at: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// Uses up `token`, failing unless it was issued for `paths` and has
+    /// not expired
+    fn redeem(&self, token: &str, paths: &[String]) -> Result<(), ScanError> {
+        let plan = self.plans.lock().remove(token).ok_or_else(|| {
+            ScanError::Refused(
+                "The deletion was not prepared or has already been carried out".to_string(),
+            )
+        })?;
+        if plan.prepared_at.elapsed() >= PLAN_LIFETIME {
+            return Err(ScanError::Refused(
+                "The deletion preview has expired, please confirm again".to_string(),
+            ));
+        }
+        if plan.paths != Self::path_set(paths) {
+            return Err(ScanError::Refused(
+                "The items to delete changed since they were previewed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Deletion progress tracker with throttled channel updates
+struct DeleteTracker<'a> {
+    items_deleted: u64,
+    bytes_freed: u64,
+    channel: Option<&'a Channel<DeleteProgress>>,
+    last_emit: Instant,
+    /// Minimum time between two updates
+    interval: Duration,
+}
+
+impl<'a> DeleteTracker<'a> {
+    fn new(channel: Option<&'a Channel<DeleteProgress>>) -> Self {
+        Self {
+            items_deleted: 0,
+            bytes_freed: 0,
+            channel,
+            last_emit: Instant::now(),
+            interval: Duration::from_millis(100),
+        }
+    }
+
+    fn add_item(&mut self, path: &Path, size: u64) {
+        self.items_deleted += 1;
+        self.bytes_freed += size;
+        if self.last_emit.elapsed() >= self.interval {
+            self.last_emit = Instant::now();
+            self.emit(path);
+        }
+    }
+
+    fn emit(&self, path: &Path) {
+        if let Some(channel) = self.channel {
+            let _ = channel.send(DeleteProgress {
+                items_deleted: self.items_deleted,
+                bytes_freed: self.bytes_freed,
+                current_path: encode_path(path),
+            });
+        }
+    }
+}
+
+/// Gives the owner write access to `path` if it lacks it. Symlinks are left
+/// alone since their permissions cannot be changed portably.
+fn make_writable(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    {
+        if permissions.mode() & 0o200 != 0 {
+            return Ok(());
+        }
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    {
+        if !permissions.readonly() {
+            return Ok(());
+        }
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+    }
+    fs::set_permissions(path, permissions)
+}
+
+/// Removes a single file or empty directory. When that is denied, the
+/// read-only flag is cleared from the entry (what blocks it on Windows) and
+/// from its parent (what blocks it on Unix) and the removal is retried once.
+fn remove_entry(path: &Path, is_dir: bool) -> io::Result<()> {
+    let remove = || {
+        if is_dir {
+            fs::remove_dir(path)
+        } else {
+            fs::remove_file(path)
+        }
+    };
+    match remove() {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let cleared =
+                make_writable(path).and_then(|()| path.parent().map_or(Ok(()), make_writable));
+            if cleared.is_err() {
+                return Err(e);
+            }
+            remove()
+        }
+        result => result,
+    }
+}
+
+/// Removes a file or directory tree entry by entry, reporting each removal
+fn remove_path(path: &Path, tracker: &mut DeleteTracker) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            remove_path(&entry?.path(), tracker)?;
+        }
+    }
+    remove_entry(path, metadata.is_dir())?;
+    tracker.add_item(path, allocated_size(&metadata));
+    Ok(())
+}
+
+/// Overwrites the contents of a regular file with random bytes `passes`
+/// times, syncing each pass to disk
+fn overwrite(path: &Path, passes: u8) -> io::Result<()> {
+    let mut file = match OpenOptions::new().write(true).open(path) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            make_writable(path)?;
+            OpenOptions::new().write(true).open(path)?
+        }
+        result => result?,
+    };
+    let len = file.metadata()?.len();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut rng = rand::thread_rng();
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = &mut buffer[..remaining.min(64 * 1024) as usize];
+            rng.fill_bytes(chunk);
+            file.write_all(chunk)?;
+            remaining -= chunk.len() as u64;
+        }
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Overwrites and removes a file, or every file in a directory tree.
+/// Symlinks are removed without touching what they point to, and special
+/// files (devices, sockets, FIFOs) are refused.
+fn secure_remove(path: &Path, passes: u8) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(path)?.file_type();
+    if file_type.is_dir() {
+        for entry in fs::read_dir(path)? {
+            secure_remove(&entry?.path(), passes)?;
+        }
+        remove_entry(path, true)
+    } else if file_type.is_file() {
+        overwrite(path, passes)?;
+        remove_entry(path, false)
+    } else if file_type.is_symlink() {
+        remove_entry(path, false)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a regular file or directory",
+        ))
+    }
+}
+
+/// Copies a file, symlink or directory tree to `to`, which must not exist
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())
+    } else {
+        #[cfg(unix)]
+        if metadata.file_type().is_symlink() {
+            return std::os::unix::fs::symlink(fs::read_link(from)?, to);
+        }
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Moves `from` to `to`. Across filesystems the item is copied and the
+/// original removed once the copy is complete.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => move_across_devices(from, to),
+        result => result,
+    }
+}
+
+/// Moves `from` to `to` by copying it and removing the original
+fn move_across_devices(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(e) = copy_tree(from, to) {
+        // Leave nothing half-copied behind, the original is intact
+        let _ = fs::remove_dir_all(to).or_else(|_| fs::remove_file(to));
+        return Err(e);
+    }
+    remove_path(from, &mut DeleteTracker::new(None))
+}
+
+/// Moves one item into `dest_dir` under its own name, see `move_items`
+fn move_into(
+    scanner: &DiskScanner,
+    protected: &[PathBuf],
+    path: &Path,
+    dest_dir: &Path,
+    overwrite: bool,
+) -> Result<(PathBuf, u64), ScanError> {
+    check_deletable(scanner, protected, path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| ScanError::InvalidArgument(format!("Cannot move {}", path.display())))?;
+    if !dest_dir.is_dir() {
+        return Err(ScanError::NotADirectory);
+    }
+    // Resolve the parent only, a symlink is moved rather than its target
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let source = fs::canonicalize(parent)
+        .map_err(|e| ScanError::io_with(path.display(), e))?
+        .join(name);
+    let dest_dir =
+        fs::canonicalize(dest_dir).map_err(|e| ScanError::io_with(dest_dir.display(), e))?;
+    if dest_dir.starts_with(&source) {
+        return Err(ScanError::InvalidArgument(format!(
+            "Cannot move {} into itself",
+            path.display()
+        )));
+    }
+    let target = dest_dir.join(name);
+    if target == source {
+        return Err(ScanError::InvalidArgument(format!(
+            "{} is already in {}",
+            path.display(),
+            dest_dir.display()
+        )));
+    }
+    if source.starts_with(&target) {
+        return Err(ScanError::InvalidArgument(format!(
+            "Cannot replace {}, it contains {}",
+            target.display(),
+            path.display()
+        )));
+    }
+    // An item being replaced is set aside next to itself first, and only
+    // removed once the move has succeeded
+    let replaced = match fs::symlink_metadata(&target) {
+        Ok(_) if !overwrite => {
+            return Err(ScanError::Refused(format!(
+                "{} already exists",
+                target.display()
+            )));
+        }
+        Ok(_) => {
+            check_protected(protected, &target)?;
+            let mut aside = name.to_os_string();
+            aside.push(format!(".maka-replaced-{}", std::process::id()));
+            let aside = dest_dir.join(aside);
+            fs::rename(&target, &aside).map_err(|e| {
+                ScanError::io_with(format!("Failed to replace {}", target.display()), e)
+            })?;
+            Some(aside)
+        }
+        Err(_) => None,
+    };
+
+    let bytes = scanner
+        .directory_size(&encode_path(path))
+        .map_or(0, |totals| totals.size);
+    if let Err(e) = move_path(&extended_length(path), &extended_length(&target)) {
+        if let Some(aside) = &replaced {
+            let _ = fs::rename(aside, &target);
+        }
+        return Err(ScanError::io_with(
+            format!("Failed to move {}", path.display()),
+            e,
+        ));
+    }
+    if let Some(aside) = replaced {
+        // The move itself went through, a leftover copy is not worth failing it
+        let _ = remove_path(&extended_length(&aside), &mut DeleteTracker::new(None));
+    }
+    Ok((target, bytes))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Where the entry at `path` really is: its parent is resolved, but not the
+/// entry itself, since deleting a symlink removes the link and not what it
+/// points to
+fn resolve_entry(path: &Path) -> PathBuf {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    match (parent, path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent)
+            .unwrap_or_else(|_| parent.to_path_buf())
+            .join(name),
+        _ => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// Refuses paths whose deletion would almost certainly be a mistake:
+/// filesystem roots, the user's home directory and the directories above
+/// it, and anything outside the directories that have actually been
+/// scanned. Paths going up with `..` are refused outright. Entries inside
+/// archives cannot be deleted on their own either, nor can the user's
+/// `protected` paths and their contents.
+fn check_deletable(
+    scanner: &DiskScanner,
+    protected: &[PathBuf],
+    requested: &Path,
+) -> Result<(), ScanError> {
+    if requested
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(ScanError::Refused(format!(
+            "Refusing to delete {}: it goes up with `..`",
+            requested.display()
+        )));
+    }
+    check_protected(protected, requested)?;
+    if scanner.in_archive(requested) {
+        return Err(ScanError::Refused(format!(
+            "Refusing to delete {}: it is inside an archive",
+            requested.display()
+        )));
+    }
+    let path = resolve_entry(requested);
+    if path.parent().is_none() {
+        return Err(ScanError::Refused(format!(
+            "Refusing to delete {}: it is a filesystem root",
+            path.display()
+        )));
+    }
+    if let Some(home) = home_dir() {
+        let home = fs::canonicalize(&home).unwrap_or(home);
+        if home.starts_with(&path) {
+            return Err(ScanError::Refused(format!(
+                "Refusing to delete {}: it is or contains the home directory",
+                path.display()
+            )));
+        }
+    }
+    if !scanner.contains_path(&path) {
+        return Err(ScanError::Refused(format!(
+            "Refusing to delete {}: it is not inside a scanned directory",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Adds up what deleting `paths` would remove, without a token yet. Paths
+/// that are already gone, e.g. after a deletion that stopped partway,
+/// count as nothing so the rest can still be retried.
+fn deletion_totals(scanner: &DiskScanner, paths: &[String]) -> Result<DeletionPlan, ScanError> {
+    let mut plan = DeletionPlan {
+        total_bytes: 0,
+        file_count: 0,
+        dir_count: 0,
+        token: String::new(),
+    };
+    for path in paths {
+        let totals = match scanner.directory_size(path) {
+            Err(ScanError::NotFound) => continue,
+            totals => totals?,
+        };
+        plan.total_bytes = plan.total_bytes.saturating_add(totals.size);
+        plan.file_count += totals.file_count;
+        plan.dir_count += totals.dir_count;
+        if fs::symlink_metadata(extended_length(&decode_path(path))).is_ok_and(|m| m.is_dir()) {
+            plan.dir_count += 1;
+        }
+    }
+    Ok(plan)
+}
+
+/// Adds up what deleting `paths` would remove, for the user to confirm
+/// before passing the plan's token to `delete_items`
+#[tauri::command]
+pub async fn prepare_deletion(
+    scanner: State<'_, DiskScanner>,
+    plans: State<'_, DeletionPlans>,
+    paths: Vec<String>,
+) -> Result<DeletionPlan, ScanError> {
+    let mut plan = deletion_totals(&scanner, &paths)?;
+    plan.token = plans.issue(&paths);
+    Ok(plan)
+}
+
+/// Deletes the given paths, which must be the ones `token` was prepared
+/// for by `prepare_deletion`. Unless `allow_unsafe` is set, every path is
+/// checked by `check_deletable` before anything is removed.
 #[tauri::command]
-pub async fn delete_items(paths: Vec<String>) -> Result<(), String> {
+pub async fn delete_items(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    plans: State<'_, DeletionPlans>,
+    paths: Vec<String>,
+    token: String,
+    on_progress: Option<Channel<DeleteProgress>>,
+    allow_unsafe: Option<bool>,
+) -> Result<(), ScanError> {
+    plans.redeem(&token, &paths)?;
+    delete_paths(
+        &scanner,
+        &settings::protected_paths(&app)?,
+        &paths,
+        on_progress.as_ref(),
+        allow_unsafe.unwrap_or(false),
+    )
+}
+
+/// Checks and then deletes `paths`, see `delete_items`. Nothing is removed
+/// when any of them is refused.
+fn delete_paths(
+    scanner: &DiskScanner,
+    protected: &[PathBuf],
+    paths: &[String],
+    on_progress: Option<&Channel<DeleteProgress>>,
+    allow_unsafe: bool,
+) -> Result<(), ScanError> {
+    if !allow_unsafe {
+        for path_str in paths {
+            check_deletable(scanner, protected, &decode_path(path_str))?;
+        }
+    } else {
+        for path_str in paths {
+            check_protected(protected, &decode_path(path_str))?;
+        }
+    }
+
+    let mut tracker = DeleteTracker::new(on_progress);
     for path_str in paths {
-        let path = Path::new(&path_str);
+        let path = &extended_length(&decode_path(path_str));
         if path.exists() {
-            if path.is_dir() {
-                fs::remove_dir_all(path)
-                    .map_err(|e| format!("Failed to delete directory {}: {}", path_str, e))?;
-            } else {
-                fs::remove_file(path)
-                    .map_err(|e| format!("Failed to delete file {}: {}", path_str, e))?;
-            }
+            let kind = if path.is_dir() { "directory" } else { "file" };
+            remove_path(path, &mut tracker).map_err(|e| {
+                ScanError::io_with(format!("Failed to delete {} {}", kind, path_str), e)
+            })?;
+            tracker.emit(path);
         }
     }
     Ok(())
 }
+
+/// Moves the given paths to the platform's trash so they can be restored
+/// from Finder, Explorer or the desktop's trash UI. Each path is checked by
+/// `check_deletable` and reported on separately.
+#[tauri::command]
+pub async fn native_trash(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    paths: Vec<String>,
+) -> Result<Vec<PathResult>, ScanError> {
+    let protected = settings::protected_paths(&app)?;
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let decoded = decode_path(&path);
+            let error = check_deletable(&scanner, &protected, &decoded)
+                .and_then(|()| {
+                    trash::move_to_trash(&decoded).map_err(|e| {
+                        ScanError::io_with(format!("Failed to move {} to the trash", path), e)
+                    })
+                })
+                .err();
+            PathResult { path, error }
+        })
+        .collect())
+}
+
+/// Deletes the given paths, which must be the ones `token` was prepared
+/// for by `prepare_deletion`, after overwriting every regular file in them
+/// with random data. `passes` is how many times each file is overwritten
+/// and must be at least 1. Every path is checked by `check_deletable` and
+/// reported on separately. On SSDs and copy-on-write filesystems (APFS,
+/// Btrfs) the old blocks may survive the overwrite.
+#[tauri::command]
+pub async fn secure_delete(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    plans: State<'_, DeletionPlans>,
+    paths: Vec<String>,
+    token: String,
+    passes: u8,
+) -> Result<Vec<PathResult>, ScanError> {
+    if passes == 0 {
+        return Err(ScanError::InvalidArgument(
+            "A secure delete needs at least one pass".to_string(),
+        ));
+    }
+    plans.redeem(&token, &paths)?;
+    let protected = settings::protected_paths(&app)?;
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let decoded = decode_path(&path);
+            let error = check_deletable(&scanner, &protected, &decoded)
+                .and_then(|()| {
+                    secure_remove(&extended_length(&decoded), passes).map_err(|e| {
+                        ScanError::io_with(format!("Failed to securely delete {}", path), e)
+                    })
+                })
+                .err();
+            PathResult { path, error }
+        })
+        .collect())
+}
+
+/// Command removing `paths` as root through polkit's `pkexec`, which asks
+/// for an administrator password in a desktop dialog
+fn elevated_delete_command(paths: &[PathBuf]) -> Command {
+    let mut command = Command::new("pkexec");
+    command.args(["rm", "-rf", "--"]).args(paths);
+    command
+}
+
+/// Deletes the given paths as root, for files `delete_items` failed on
+/// with a permission error. Only ever run when the user asks for it, with
+/// a `token` prepared for exactly these paths by `prepare_deletion`. Each
+/// path is checked by `check_deletable`, the others are removed by a single
+/// `pkexec` prompt and reported on by whether they are gone afterwards.
+/// Only supported on Linux.
+#[tauri::command]
+pub async fn delete_items_elevated(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    plans: State<'_, DeletionPlans>,
+    paths: Vec<String>,
+    token: String,
+) -> Result<Vec<PathResult>, ScanError> {
+    if !cfg!(target_os = "linux") {
+        return Err(ScanError::Unsupported(
+            "Deleting as administrator is only supported on Linux".to_string(),
+        ));
+    }
+    plans.redeem(&token, &paths)?;
+
+    let protected = settings::protected_paths(&app)?;
+    let mut results: Vec<PathResult> = paths
+        .into_iter()
+        .map(|path| {
+            let error = check_deletable(&scanner, &protected, &decode_path(&path)).err();
+            PathResult { path, error }
+        })
+        .collect();
+    let allowed: Vec<PathBuf> = results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .map(|result| decode_path(&result.path))
+        .collect();
+    if allowed.is_empty() {
+        return Ok(results);
+    }
+
+    let status = elevated_delete_command(&allowed)
+        .status()
+        .map_err(|e| ScanError::io_with("Failed to run pkexec", e))?;
+    for result in results.iter_mut().filter(|result| result.error.is_none()) {
+        if fs::symlink_metadata(decode_path(&result.path)).is_ok() {
+            // pkexec exits with 126 when the prompt is dismissed
+            result.error = Some(match status.code() {
+                Some(126) | Some(127) => ScanError::PermissionDenied,
+                _ => ScanError::Io(format!("Failed to delete {} as administrator", result.path)),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Moves the given paths into `dest_dir`, e.g. onto an external drive.
+/// Existing items of the same name are only replaced with `overwrite`.
+/// Each path is checked by `check_deletable` and reported on separately.
+#[tauri::command]
+pub async fn move_items(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    paths: Vec<String>,
+    dest_dir: String,
+    overwrite: Option<bool>,
+) -> Result<Vec<MoveResult>, ScanError> {
+    let dest_dir = decode_path(&dest_dir);
+    let protected = settings::protected_paths(&app)?;
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            match move_into(
+                &scanner,
+                &protected,
+                &decode_path(&path),
+                &dest_dir,
+                overwrite.unwrap_or(false),
+            ) {
+                Ok((destination, bytes_moved)) => MoveResult {
+                    path,
+                    destination: Some(encode_path(&destination)),
+                    bytes_moved,
+                    error: None,
+                },
+                Err(error) => MoveResult {
+                    path,
+                    destination: None,
+                    bytes_moved: 0,
+                    error: Some(error),
+                },
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::disk_scanner::{ProgressTracker, ScanOptions};
+
+    /// A scanner with `dir` cached
+    fn scanned(dir: &Path) -> DiskScanner {
+        let scanner = DiskScanner::new();
+        scanner
+            .build_cache_tracked(
+                &encode_path(dir),
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        scanner
+    }
+
+    fn refused(result: Result<(), ScanError>) -> String {
+        match result {
+            Err(ScanError::Refused(message)) => message,
+            other => panic!("expected a refusal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refuses_filesystem_root() {
+        let dir = TempDir::new().unwrap();
+        let scanner = scanned(dir.path());
+        let root = if cfg!(windows) { "C:\\" } else { "/" };
+        let message = refused(check_deletable(&scanner, &[], Path::new(root)));
+        assert!(message.contains("filesystem root"), "{}", message);
+    }
+
+    #[test]
+    fn refuses_home_and_its_parents() {
+        let Some(home) = home_dir().filter(|home| home.is_dir()) else {
+            return;
+        };
+        let dir = TempDir::new().unwrap();
+        let scanner = scanned(dir.path());
+        let message = refused(check_deletable(&scanner, &[], &home));
+        assert!(message.contains("home directory"), "{}", message);
+        if let Some(parent) = home.parent().filter(|parent| parent.parent().is_some()) {
+            let message = refused(check_deletable(&scanner, &[], parent));
+            assert!(message.contains("home directory"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn allows_files_inside_a_scanned_root() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/file"), b"data").unwrap();
+        let scanner = scanned(dir.path());
+        check_deletable(&scanner, &[], &dir.path().join("sub/file")).unwrap();
+        check_deletable(&scanner, &[], &dir.path().join("sub")).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_outside_scanned_roots() {
+        let scanned_dir = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        fs::write(other.path().join("file"), b"data").unwrap();
+        let scanner = scanned(scanned_dir.path());
+        let message = refused(check_deletable(&scanner, &[], &other.path().join("file")));
+        assert!(
+            message.contains("not inside a scanned directory"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn refuses_escaping_a_root_with_parent_components() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("root")).unwrap();
+        fs::create_dir(dir.path().join("outside")).unwrap();
+        let scanner = scanned(&dir.path().join("root"));
+        let escaping = dir.path().join("root").join("..").join("outside");
+        refused(check_deletable(&scanner, &[], &escaping));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allows_symlinks_pointing_outside_the_root() {
+        let dir = TempDir::new().unwrap();
+        std::os::unix::fs::symlink("/", dir.path().join("link")).unwrap();
+        let scanner = scanned(dir.path());
+        check_deletable(&scanner, &[], &dir.path().join("link")).unwrap();
+    }
+
+    #[test]
+    fn refuses_protected_paths() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("keep")).unwrap();
+        fs::write(dir.path().join("keep/file"), b"data").unwrap();
+        let scanner = scanned(dir.path());
+        let protected = [fs::canonicalize(dir.path().join("keep")).unwrap()];
+        refused(check_deletable(
+            &scanner,
+            &protected,
+            &dir.path().join("keep/file"),
+        ));
+    }
+
+    #[test]
+    fn deletions_refuse_protected_paths_and_their_contents() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("sub/keep/inner")).unwrap();
+        fs::write(dir.path().join("sub/keep/inner/file"), b"data").unwrap();
+        fs::write(dir.path().join("other"), b"data").unwrap();
+        let scanner = scanned(dir.path());
+        let protected = [fs::canonicalize(dir.path().join("sub/keep")).unwrap()];
+        let encoded = |relative: &str| encode_path(&dir.path().join(relative));
+
+        let root = encode_path(dir.path());
+        for allow_unsafe in [false, true] {
+            // Inside `keep`, `keep` itself, its parent and the scanned root
+            for target in [
+                encoded("sub/keep/inner/file"),
+                encoded("sub/keep/inner"),
+                encoded("sub/keep"),
+                encoded("sub"),
+                root.clone(),
+            ] {
+                let message = refused(delete_paths(
+                    &scanner,
+                    &protected,
+                    &[encoded("other"), target],
+                    None,
+                    allow_unsafe,
+                ));
+                assert!(message.contains("is protected"), "{}", message);
+            }
+        }
+        // Refusing one path leaves the whole batch untouched
+        assert!(dir.path().join("other").exists());
+        assert!(dir.path().join("sub/keep/inner/file").exists());
+
+        delete_paths(&scanner, &protected, &[encoded("other")], None, false).unwrap();
+        assert!(!dir.path().join("other").exists());
+    }
+
+    #[test]
+    fn plans_after_a_partial_deletion_skip_what_is_gone() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("kept")).unwrap();
+        fs::write(dir.path().join("kept/file"), vec![0; 5000]).unwrap();
+        fs::write(dir.path().join("gone"), vec![0; 3000]).unwrap();
+        let scanner = scanned(dir.path());
+        let batch = [
+            encode_path(&dir.path().join("gone")),
+            encode_path(&dir.path().join("kept")),
+        ];
+        let before = deletion_totals(&scanner, &batch).unwrap();
+        assert_eq!((before.file_count, before.dir_count), (2, 1));
+
+        // The first pass only got as far as `gone`
+        delete_paths(&scanner, &[], &batch[..1], None, false).unwrap();
+        let retry = deletion_totals(&scanner, &batch).unwrap();
+        let kept = scanner.directory_size(&batch[1]).unwrap();
+        assert_eq!((retry.file_count, retry.dir_count), (1, 1));
+        assert_eq!(retry.total_bytes, kept.size);
+
+        delete_paths(&scanner, &[], &batch, None, false).unwrap();
+        assert!(!dir.path().join("kept").exists());
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn token_redeems_for_the_same_paths_in_any_order() {
+        let plans = DeletionPlans::default();
+        let token = plans.issue(&paths(&["/a", "/b"]));
+        plans.redeem(&token, &paths(&["/b", "/a", "/a"])).unwrap();
+    }
+
+    #[test]
+    fn token_is_single_use() {
+        let plans = DeletionPlans::default();
+        let token = plans.issue(&paths(&["/a"]));
+        plans.redeem(&token, &paths(&["/a"])).unwrap();
+        assert!(matches!(
+            plans.redeem(&token, &paths(&["/a"])),
+            Err(ScanError::Refused(_))
+        ));
+    }
+
+    #[test]
+    fn token_is_refused_for_other_paths() {
+        let plans = DeletionPlans::default();
+        let token = plans.issue(&paths(&["/a"]));
+        assert!(matches!(
+            plans.redeem(&token, &paths(&["/a", "/b"])),
+            Err(ScanError::Refused(_))
+        ));
+        // A mismatch uses the token up as well
+        assert!(plans.redeem(&token, &paths(&["/a"])).is_err());
+    }
+
+    #[test]
+    fn token_expires() {
+        let plans = DeletionPlans::default();
+        let token = plans.issue(&paths(&["/a"]));
+        let expired = Instant::now().checked_sub(PLAN_LIFETIME).unwrap();
+        plans.plans.lock().get_mut(&token).unwrap().prepared_at = expired;
+        let error = plans.redeem(&token, &paths(&["/a"])).unwrap_err();
+        assert!(error.to_string().contains("expired"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_token_is_refused() {
+        let plans = DeletionPlans::default();
+        assert!(plans.redeem("nope", &paths(&["/a"])).is_err());
+    }
+
+    #[test]
+    fn elevated_delete_runs_rm_through_pkexec() {
+        let paths = [PathBuf::from("/var/a b"), PathBuf::from("-rf")];
+        let command = elevated_delete_command(&paths);
+        assert_eq!(command.get_program(), "pkexec");
+        let args: Vec<&std::ffi::OsStr> = command.get_args().collect();
+        // Paths come after `--`, so one starting with `-` is not an option
+        assert_eq!(args, ["rm", "-rf", "--", "/var/a b", "-rf"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_can_be_scanned_and_deleted() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join(std::ffi::OsStr::from_bytes(b"bad\xFFname"));
+        fs::write(&file, b"data").unwrap();
+        let scanner = scanned(dir.path());
+
+        let encoded = encode_path(&file);
+        let cached = scanner.with_node(&encoded, |node| node.path.to_string());
+        assert_eq!(cached.as_deref(), Ok(encoded.as_str()));
+
+        let decoded = decode_path(&encoded);
+        check_deletable(&scanner, &[], &decoded).unwrap();
+        remove_path(&decoded, &mut DeleteTracker::new(None)).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn moves_need_overwrite_to_replace_an_item() {
+        let dir = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        fs::write(dir.path().join("file"), b"new").unwrap();
+        fs::write(dest.path().join("file"), b"old").unwrap();
+        let scanner = scanned(dir.path());
+        let source = dir.path().join("file");
+
+        let message = match move_into(&scanner, &[], &source, dest.path(), false) {
+            Err(ScanError::Refused(message)) => message,
+            other => panic!("expected a refusal, got {:?}", other),
+        };
+        assert!(message.contains("already exists"), "{}", message);
+
+        let size = scanner.directory_size(&encode_path(&source)).unwrap().size;
+        let (target, bytes) = move_into(&scanner, &[], &source, dest.path(), true).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(bytes, size);
+        assert!(!source.exists());
+        assert_eq!(fs::read_dir(dest.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn moves_do_not_replace_protected_items() {
+        let dir = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        fs::write(dir.path().join("file"), b"new").unwrap();
+        fs::write(dest.path().join("file"), b"old").unwrap();
+        let scanner = scanned(dir.path());
+        let protected = [fs::canonicalize(dest.path().join("file")).unwrap()];
+
+        let result = move_into(
+            &scanner,
+            &protected,
+            &dir.path().join("file"),
+            dest.path(),
+            true,
+        );
+        assert!(matches!(result, Err(ScanError::Refused(_))), "{:?}", result);
+        assert_eq!(fs::read(dest.path().join("file")).unwrap(), b"old");
+        assert!(dir.path().join("file").exists());
+    }
+
+    #[test]
+    fn failed_moves_keep_the_item_they_would_replace() {
+        let dir = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("item")).unwrap();
+        fs::write(dir.path().join("item/file"), b"data").unwrap();
+        // A directory cannot be renamed over a file
+        fs::write(dest.path().join("item"), b"old").unwrap();
+        let scanner = scanned(dir.path());
+        fs::remove_dir_all(dir.path().join("item")).unwrap();
+
+        let result = move_into(&scanner, &[], &dir.path().join("item"), dest.path(), true);
+        assert!(result.is_err());
+        assert_eq!(fs::read(dest.path().join("item")).unwrap(), b"old");
+        assert_eq!(fs::read_dir(dest.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn moves_across_devices_copy_then_remove() {
+        let dir = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        let source = dir.path().join("tree");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("sub/file"), b"data").unwrap();
+        fs::write(source.join("top"), b"top").unwrap();
+
+        let target = dest.path().join("tree");
+        move_across_devices(&source, &target).unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read(target.join("sub/file")).unwrap(), b"data");
+        assert_eq!(fs::read(target.join("top")).unwrap(), b"top");
+    }
+
+    #[test]
+    fn overwrites_replace_the_contents_in_place() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("secret");
+        let secret = vec![b'x'; 100_000];
+        fs::write(&file, &secret).unwrap();
+        overwrite(&file, 2).unwrap();
+        let rewritten = fs::read(&file).unwrap();
+        assert_eq!(rewritten.len(), secret.len());
+        assert_ne!(rewritten, secret);
+    }
+
+    #[test]
+    fn entries_inside_archives_are_not_deleted() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let dir = TempDir::new().unwrap();
+        let zip = dir.path().join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip).unwrap());
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("inner.txt", options).unwrap();
+        writer.write_all(b"data").unwrap();
+        writer.finish().unwrap();
+        let scanner = DiskScanner::new();
+        let options = ScanOptions {
+            scan_archives: true,
+            ..ScanOptions::default()
+        };
+        scanner
+            .build_cache_tracked(
+                &encode_path(dir.path()),
+                options,
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+
+        let inner = zip.join("inner.txt");
+        assert!(scanner
+            .with_node(&encode_path(&inner), |node| node.in_archive)
+            .unwrap());
+        let message = refused(check_deletable(&scanner, &[], &inner));
+        assert!(message.contains("inside an archive"), "{}", message);
+        check_deletable(&scanner, &[], &zip).unwrap();
+    }
+
+    #[test]
+    fn read_only_items_are_deleted_after_clearing_the_flag() {
+        let dir = TempDir::new().unwrap();
+        let sub = dir.path().join("sub");
+        let file = sub.join("locked");
+        fs::create_dir(&sub).unwrap();
+        fs::write(&file, b"data").unwrap();
+        let mut permissions = fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&file, permissions).unwrap();
+        // What stops a removal on Unix is a read-only parent
+        #[cfg(unix)]
+        fs::set_permissions(&sub, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let mut tracker = DeleteTracker::new(None);
+        remove_path(&sub, &mut tracker).unwrap();
+        assert!(!sub.exists());
+        assert_eq!(tracker.items_deleted, 2);
+    }
+
+    #[test]
+    fn deletions_report_every_item_removed() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("tree");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for name in ["a", "b", "sub/c", "sub/d"] {
+            fs::write(root.join(name), vec![0; 5000]).unwrap();
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        let channel = Channel::new(move |body| {
+            if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+                received
+                    .lock()
+                    .push(serde_json::from_str::<serde_json::Value>(&json).unwrap());
+            }
+            Ok(())
+        });
+        let mut tracker = DeleteTracker {
+            interval: Duration::ZERO,
+            ..DeleteTracker::new(Some(&channel))
+        };
+
+        remove_path(&root, &mut tracker).unwrap();
+
+        assert!(!root.exists());
+        let events = events.lock();
+        assert_eq!(events.len(), 6);
+        assert_eq!(tracker.items_deleted, 6);
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event["items_deleted"], i as u64 + 1);
+        }
+        let last = events.last().unwrap();
+        assert_eq!(last["bytes_freed"], tracker.bytes_freed);
+        assert!(tracker.bytes_freed >= 4 * 5000);
+        assert_eq!(last["current_path"], encode_path(&root));
+    }
+
+    #[test]
+    fn secure_removal_clears_whole_trees() {
+        let dir = TempDir::new().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("sub/file"), b"secret").unwrap();
+        fs::write(tree.join("file"), b"secret").unwrap();
+        secure_remove(&tree, 1).unwrap();
+        assert!(!tree.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secure_removal_refuses_special_files() {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join("socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let error = secure_remove(&socket, 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(fs::symlink_metadata(&socket).is_ok());
+    }
+}
@@ -1,42 +1,115 @@
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
 
-use dashmap::DashMap;
-use parking_lot::Mutex;
+use dashmap::{DashMap, DashSet};
+use parking_lot::{Mutex, RwLock};
+use rayon::iter::Either;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter};
+use tauri::ipc::{Channel, Response};
+use tauri::{AppHandle, Emitter, State};
+use tracing::{debug, info, instrument};
 
-static SCANNER: std::sync::LazyLock<DiskScanner> = std::sync::LazyLock::new(|| DiskScanner::new());
+use crate::archive;
+use crate::error::ScanError;
+use crate::paths::{
+    canonicalize, components, decode_path, encode_path, encoded_name, extended_length,
+    file_extension, node_name, relative_to_root, NodePath,
+};
+use crate::settings::{self, SizeUnits};
 
 /// Progress information emitted during scanning
 #[derive(Clone, Serialize)]
 pub struct ScanProgress {
+    /// Job the scan runs as (see `jobs::start_scan`), `None` for scans
+    /// started directly
+    pub job_id: Option<u64>,
     pub scanned_size: u64,
     pub file_count: u64,
 }
 
-/// Thread-safe progress tracker with throttled event emission
-struct ProgressTracker {
+/// Emitted once as `scan-summary` when `build_cache` finishes
+#[derive(Clone, Serialize)]
+pub struct ScanSummary {
+    pub root: String,
+    pub duration_ms: u64,
+    pub total_files: u64,
+    pub total_dirs: u64,
+    pub total_bytes: u64,
+    /// Entries that could not be read
+    pub skipped: u64,
+}
+
+impl ScanSummary {
+    /// Summary of a scan of `root` that produced `node` in `duration`
+    fn of(root: &str, node: &FileNode, duration: Duration) -> Self {
+        Self {
+            root: root.to_string(),
+            duration_ms: duration.as_millis() as u64,
+            total_files: node.total_files,
+            total_dirs: node
+                .iter()
+                .filter(|node| node.is_directory && !node.in_archive)
+                .count() as u64,
+            total_bytes: node.size,
+            skipped: node.skipped,
+        }
+    }
+}
+
+/// Thread-safe progress tracker with throttled event emission. It also
+/// carries the flag a running scan polls to find out it was cancelled.
+pub struct ProgressTracker {
     scanned_size: AtomicU64,
     file_count: AtomicU64,
-    app_handle: AppHandle,
+    /// Where events go, `None` for scans nobody watches
+    app_handle: Option<AppHandle>,
+    /// Receives progress instead of the `scan-progress` event when set
+    channel: Option<Channel<ScanProgress>>,
+    job_id: Option<u64>,
     last_emit: Mutex<Instant>,
+    cancelled: AtomicBool,
 }
 
 impl ProgressTracker {
-    fn new(app_handle: AppHandle) -> Self {
+    pub(crate) fn new(app_handle: AppHandle, channel: Option<Channel<ScanProgress>>) -> Self {
+        Self {
+            app_handle: Some(app_handle),
+            channel,
+            ..Self::silent()
+        }
+    }
+
+    /// Tracker for the scan job `job_id`, whose progress carries its id
+    pub(crate) fn for_job(
+        app_handle: AppHandle,
+        channel: Option<Channel<ScanProgress>>,
+        job_id: u64,
+    ) -> Self {
+        Self {
+            job_id: Some(job_id),
+            ..Self::new(app_handle, channel)
+        }
+    }
+
+    /// Tracker for a scan that emits no events, e.g. in benchmarks
+    pub fn silent() -> Self {
         Self {
             scanned_size: AtomicU64::new(0),
             file_count: AtomicU64::new(0),
-            app_handle,
+            app_handle: None,
+            channel: None,
+            job_id: None,
             last_emit: Mutex::new(Instant::now()),
+            cancelled: AtomicBool::new(false),
         }
     }
 
@@ -50,292 +123,4682 @@ impl ProgressTracker {
         let mut last = self.last_emit.lock();
         if last.elapsed() >= Duration::from_millis(100) {
             *last = Instant::now();
-            let scanned = self.scanned_size.load(Ordering::Relaxed);
-            let count = self.file_count.load(Ordering::Relaxed);
-            let _ = self.app_handle.emit(
-                "scan-progress",
-                ScanProgress {
-                    scanned_size: scanned,
-                    file_count: count,
-                },
-            );
+            self.emit_final();
         }
     }
 
     fn emit_final(&self) {
-        let scanned = self.scanned_size.load(Ordering::Relaxed);
-        let count = self.file_count.load(Ordering::Relaxed);
-        let _ = self.app_handle.emit(
-            "scan-progress",
-            ScanProgress {
-                scanned_size: scanned,
-                file_count: count,
-            },
-        );
+        let progress = self.progress();
+        match &self.channel {
+            Some(channel) => {
+                let _ = channel.send(progress);
+            }
+            None => {
+                if let Some(app_handle) = &self.app_handle {
+                    let _ = app_handle.emit("scan-progress", progress);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn progress(&self) -> ScanProgress {
+        ScanProgress {
+            job_id: self.job_id,
+            scanned_size: self.scanned_size.load(Ordering::Relaxed),
+            file_count: self.file_count.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Makes the scan stop with `ScanError::Cancelled` at its next check
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn check_cancelled(&self) -> Result<(), ScanError> {
+        if self.is_cancelled() {
+            Err(ScanError::Cancelled)
+        } else {
+            Ok(())
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Fields missing from older JSON exports are filled with defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FileNode {
     pub name: String,
-    pub path: String,
+    pub path: NodePath,
+    /// Aggregated size of the whole subtree on disk, whether or not its
+    /// children are included. This is allocated space: sparse files count
+    /// only their allocated blocks, and on APFS the blocks shared by clones
+    /// of a file are counted once per scan (see `clone_info`). Sizes are a
+    /// snapshot from when each entry was stat'ed, so a file still being
+    /// written to may already be larger on disk.
     pub size: u64,
+    /// Aggregated logical length of the files in the subtree, which is what
+    /// `size` would be if every byte were stored once
+    pub apparent_size: u64,
     pub is_directory: bool,
+    /// Lowercased extension of a file's name, `None` for directories and
+    /// names without one
+    pub extension: Option<String>,
     pub children: Vec<FileNode>,
+    /// Number of entries listed for this directory on disk, including ones
+    /// that could not be read or that the scan options left out. Unlike
+    /// `children.len()`, it stays the same when children are filtered,
+    /// emptied or folded for a response.
     pub children_count: usize,
+    /// Number of files in this subtree (1 for a file)
+    pub total_files: u64,
+    /// False for nodes at the depth limit, whose children were left out
     pub show: bool,
+    /// Set on directories whose children were left out of a response, as
+    /// opposed to directories that are empty
+    pub truncated: bool,
+    /// Number of children left out of this response by a size filter
+    pub hidden_count: usize,
+    /// Last modification time in seconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// Owner's user id, `None` where there is no such thing (Windows)
+    pub uid: Option<u32>,
+    /// Unix permission bits (e.g. `0o644`), `None` on Windows
+    pub mode: Option<u32>,
+    /// Set for directories that could not be read; their size is unknown
+    /// and reported as 0
+    pub inaccessible: bool,
+    /// Number of entries in this subtree that could not be read, including
+    /// inaccessible directories
+    pub skipped: u64,
+    /// Number of entries directly in this directory that could not be read,
+    /// so its listing is incomplete by that many
+    pub skipped_children: u32,
+    /// Set for symbolic links, which are never followed and count only
+    /// their own size
+    pub is_symlink: bool,
+    /// Where a symbolic link points to
+    pub symlink_target: Option<String>,
+    /// Set for entries listed from inside a ZIP archive, which is the node
+    /// they hang off. Their `size` is uncompressed and not part of the
+    /// archive's, and they cannot be deleted.
+    pub in_archive: bool,
+    /// Set for directories on another filesystem than the scanned root,
+    /// which scans with `one_filesystem` do not enter. They have no children
+    /// and a size of 0.
+    pub mount_point: bool,
+    /// Set for directories where another filesystem is mounted, whether or
+    /// not the scan entered them. Unlike `mount_point`, this is about the
+    /// directory itself rather than the scan's options.
+    pub is_mount_point: bool,
+    /// Share of the parent's size, from 0 to 100. Only filled in for
+    /// returned trees, where the root gets 100.
+    pub percent_of_parent: f32,
+    /// `size` in decimal units, e.g. "4.2 GB". Only filled in for returned
+    /// trees; the frontend picks this or `size_human_binary` by
+    /// `ScanSettings::units`.
+    pub size_human: String,
+    /// `size` in binary units, e.g. "4.0 GiB", see `size_human`
+    pub size_human_binary: String,
+    /// When the cached tree was scanned, in seconds since the Unix epoch.
+    /// Only set on the root of returned trees.
+    pub scanned_at: Option<u64>,
 }
 
 impl FileNode {
-    pub fn limit_depth(&self, max_depth: u32) -> Self {
-        let mut filtered_node = Self {
+    /// Copies this node without any of its children
+    pub fn detached(&self) -> Self {
+        Self {
             name: self.name.clone(),
             path: self.path.clone(),
             size: self.size,
+            apparent_size: self.apparent_size,
             is_directory: self.is_directory,
+            extension: self.extension.clone(),
             children: Vec::new(),
             children_count: self.children_count,
+            total_files: self.total_files,
             show: self.show,
-        };
+            truncated: self.truncated,
+            hidden_count: self.hidden_count,
+            modified: self.modified,
+            uid: self.uid,
+            mode: self.mode,
+            inaccessible: self.inaccessible,
+            skipped: self.skipped,
+            skipped_children: self.skipped_children,
+            is_symlink: self.is_symlink,
+            symlink_target: self.symlink_target.clone(),
+            in_archive: self.in_archive,
+            mount_point: self.mount_point,
+            is_mount_point: self.is_mount_point,
+            percent_of_parent: self.percent_of_parent,
+            size_human: self.size_human.clone(),
+            size_human_binary: self.size_human_binary.clone(),
+            scanned_at: self.scanned_at,
+        }
+    }
+
+    /// Copies this node with `max_depth` levels of nodes, the root counting
+    /// as the first. Nodes at the cutoff get `show: false` and no children,
+    /// and are marked `truncated` if they had any.
+    ///
+    /// Nothing is recomputed: `size`, `total_files` and `children_count` are
+    /// already aggregated over the full cached subtree, so they stay
+    /// accurate for pruned nodes.
+    pub fn limit_depth(&self, max_depth: u32) -> Self {
+        self.limit_depth_filtered(max_depth, 0)
+    }
+
+    /// Like `limit_depth`, but also drops children smaller than `min_size`.
+    /// Dropped children still count towards the parent's `size`, and how
+    /// many were dropped is recorded in `hidden_count`.
+    pub fn limit_depth_filtered(&self, max_depth: u32, min_size: u64) -> Self {
+        let mut filtered_node = self.detached();
 
         if max_depth == 0 {
             filtered_node.show = false;
+            filtered_node.truncated = self.truncated || !self.children.is_empty();
             return filtered_node;
         }
 
         for child in &self.children {
-            filtered_node
-                .children
-                .push(child.limit_depth(max_depth - 1));
+            if child.size < min_size {
+                filtered_node.hidden_count += 1;
+            } else {
+                filtered_node
+                    .children
+                    .push(child.limit_depth_filtered(max_depth - 1, min_size));
+            }
         }
         filtered_node
     }
-}
 
-pub struct DiskScanner {
-    // Cache root file nodes in memory
-    cache: DashMap<String, FileNode>,
-}
+    /// Re-sorts the children at every level of this (already depth-limited)
+    /// tree. The sort is stable.
+    pub fn sort_children(&mut self, sort: SortKey) {
+        self.children.sort_by(|a, b| sort.compare(a, b));
+        for child in &mut self.children {
+            child.sort_children(sort);
+        }
+    }
 
-impl DiskScanner {
-    pub fn new() -> Self {
-        Self {
-            cache: DashMap::new(),
+    /// Fills in `percent_of_parent` below this (already depth-limited) node.
+    /// Children of an empty directory get 0.
+    pub fn fill_percentages(&mut self) {
+        let parent_size = self.size;
+        for child in &mut self.children {
+            child.percent_of_parent = percent_of(child.size, parent_size);
+            child.fill_percentages();
         }
     }
 
-    pub fn build_cache(&self, path: &str, app_handle: AppHandle) -> Result<(), String> {
-        let tracker = Arc::new(ProgressTracker::new(app_handle));
-        let root_node = self.scan_file_or_directory(Path::new(path), &tracker)?;
-        tracker.emit_final();
-        self.cache.insert(path.to_string(), root_node);
-        Ok(())
+    /// Fills in `size_human` and `size_human_binary` for this (already
+    /// depth-limited) node and everything below it
+    pub fn fill_human_sizes(&mut self) {
+        self.size_human = SizeUnits::Decimal.format(self.size);
+        self.size_human_binary = SizeUnits::Binary.format(self.size);
+        for child in &mut self.children {
+            child.fill_human_sizes();
+        }
     }
 
-    fn scan_file_or_directory(
-        &self,
-        path: &Path,
-        tracker: &Arc<ProgressTracker>,
-    ) -> Result<FileNode, String> {
-        let path = Path::new(path);
-        if !path.exists() {
-            return Err("Path does not exist".to_string());
-        }
-
-        let metadata = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
-        if metadata.is_dir() {
-            let entries = match fs::read_dir(path) {
-                Ok(entries) => entries,
-                Err(e) => {
-                    return Err(e.to_string());
-                }
+    /// Folds the children `options` leaves out, at every level of this
+    /// (already depth-limited) tree, into one node named "(N smaller items)"
+    /// carrying their combined size. That node has an empty `path` and its
+    /// `children_count` is the number of folded items. Single small children
+    /// are left alone.
+    pub fn collapse_small(&mut self, options: CollapseOptions) {
+        let mut by_size: Vec<usize> = (0..self.children.len()).collect();
+        by_size.sort_by_key(|&i| std::cmp::Reverse(self.children[i].size));
+
+        let mut keep = vec![false; self.children.len()];
+        for (rank, &i) in by_size.iter().enumerate() {
+            let percent = if self.size == 0 {
+                0.0
+            } else {
+                self.children[i].size as f64 / self.size as f64 * 100.0
             };
+            let large = options
+                .min_percent
+                .is_none_or(|min_percent| percent >= f64::from(min_percent));
+            let top = options.max_children.is_none_or(|max| rank < max);
+            keep[i] = large && top;
+        }
 
-            // Skip /Volumes and /System/Volumes on macOS
-            #[cfg(target_os = "macos")]
-            if path.to_string_lossy() == "/Volumes" || path.to_string_lossy() == "/System/Volumes" {
-                return Ok(FileNode {
-                    name: path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string(),
-                    path: path.to_string_lossy().to_string(),
-                    size: 0,
-                    is_directory: true,
-                    children: vec![],
-                    children_count: 0,
-                    show: true,
-                });
-            }
-
-            // Skip /proc, /sys, /dev on Linux
-            #[cfg(target_os = "linux")]
-            {
-                let path_str = path.to_string_lossy();
-                if path_str == "/proc" || path_str == "/sys" || path_str == "/dev" {
-                    return Ok(FileNode {
-                        name: path
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string(),
-                        path: path_str.to_string(),
-                        size: 0,
-                        is_directory: true,
-                        children: vec![],
-                        children_count: 0,
-                        show: true,
-                    });
+        if keep.iter().filter(|&&kept| !kept).count() > 1 {
+            let mut remainder = FileNode {
+                is_directory: false,
+                show: true,
+                ..Default::default()
+            };
+            let mut kept = keep.iter();
+            self.children.retain(|child| {
+                if *kept.next().unwrap() {
+                    return true;
                 }
-            }
+                remainder.size = remainder.size.saturating_add(child.size);
+                remainder.apparent_size =
+                    remainder.apparent_size.saturating_add(child.apparent_size);
+                remainder.total_files = remainder.total_files.saturating_add(child.total_files);
+                remainder.children_count += 1;
+                false
+            });
+            remainder.name = format!("({} smaller items)", remainder.children_count);
+            self.children.push(remainder);
+        }
 
-            // Use rayon for parallel processing of directory entries
-            let tracker = Arc::clone(tracker);
-            let mut children: Vec<FileNode> = entries
-                .par_bridge() // Convert to parallel iterator
-                .filter_map(|entry| entry.ok())
-                .filter_map(|entry| {
-                    let entry_path = entry.path();
-                    match self.scan_file_or_directory(&entry_path, &tracker) {
-                        Ok(child_node) => Some(child_node),
-                        Err(_) => None,
-                    }
-                })
-                .collect();
+        for child in &mut self.children {
+            child.collapse_small(options);
+        }
+    }
 
-            // Sort by size (largest first)
-            children.sort_by(|a, b| b.size.cmp(&a.size));
-            let size = children.iter().map(|c| c.size).sum();
-            let children_count = children.len();
-
-            Ok(FileNode {
-                name: path
-                    .file_name()
-                    .map(|name| name.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "/".to_string()),
-                path: path.to_string_lossy().to_string(),
-                size: size,
-                is_directory: true,
-                children,
-                children_count: children_count,
-                show: true,
-            })
-        } else {
-            // Calculate actual disk space used (handles sparse files)
-            let actual_size = {
-                #[cfg(target_family = "unix")]
-                {
-                    // Unix: use st_blocks (each block is 512 bytes)
-                    metadata.blocks() * 512
-                }
-                #[cfg(not(target_family = "unix"))]
-                {
-                    // Non-Unix systems: fall back to logical size
-                    metadata.len()
-                }
-            };
+    /// Puts the children in order of size, largest first
+    pub(crate) fn sort_by_size(&mut self) {
+        self.children.sort_by_key(|c| std::cmp::Reverse(c.size));
+    }
 
-            // Update progress tracker (only for files, as per requirement)
-            tracker.add_file(actual_size);
-
-            Ok(FileNode {
-                name: path
-                    .file_name()
-                    .expect("Failed to get file name")
-                    .to_string_lossy()
-                    .to_string(),
-                path: path.to_string_lossy().to_string(),
-                size: actual_size,
-                is_directory: false,
-                children: vec![],
-                children_count: 0,
-                show: true,
+    /// Recomputes a directory's aggregates from its children.
+    /// `own_skipped` is the number of unreadable entries directly inside
+    /// this directory.
+    pub(crate) fn refresh_aggregates(&mut self, own_skipped: u64) {
+        self.size = saturating_sum(self.children.iter().map(|c| c.size));
+        self.apparent_size = saturating_sum(self.children.iter().map(|c| c.apparent_size));
+        self.total_files = saturating_sum(self.children.iter().map(|c| c.total_files));
+        self.skipped =
+            own_skipped.saturating_add(saturating_sum(self.children.iter().map(|c| c.skipped)));
+        // An inaccessible directory counts itself in `own_skipped`
+        let unlisted = own_skipped.saturating_sub(u64::from(self.inaccessible));
+        let unreadable = self.children.iter().filter(|c| c.inaccessible).count() as u64;
+        self.skipped_children = u32::try_from(unlisted + unreadable).unwrap_or(u32::MAX);
+    }
+
+    /// Unreadable entries directly inside this directory, i.e. the part of
+    /// `skipped` its children do not account for
+    fn own_skipped(&self) -> u64 {
+        let from_children = saturating_sum(self.children.iter().map(|c| c.skipped));
+        self.skipped.saturating_sub(from_children)
+    }
+
+    /// Rough memory footprint of this node alone, not counting children
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.name.len() + self.path.estimated_bytes()
+    }
+
+    /// Iterates over this node and all of its descendants depth-first,
+    /// parents before their children
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { stack: vec![self] }
+    }
+}
+
+/// Share of `parent_size` taken by `size`, as in `fill_percentages`
+fn percent_of(size: u64, parent_size: u64) -> f32 {
+    if parent_size == 0 {
+        0.0
+    } else {
+        (size as f64 / parent_size as f64 * 100.0) as f32
+    }
+}
+
+/// Borrowing view of a cached node that serializes the same as the tree
+/// `limit_depth_filtered`, `sort_children` and `fill_percentages` would
+/// make of it, without copying any of the nodes
+struct LimitedView<'a> {
+    node: &'a FileNode,
+    max_depth: u32,
+    min_size: u64,
+    /// Leaves out everything but directories, see `result_json`
+    dirs_only: bool,
+    sort: Option<SortKey>,
+    percent_of_parent: f32,
+    scanned_at: Option<u64>,
+}
+
+impl<'a> LimitedView<'a> {
+    /// Children that are listed at all, before the size filter
+    fn listed(&self) -> impl Iterator<Item = &'a FileNode> + '_ {
+        self.node
+            .children
+            .iter()
+            .filter(|child| !self.dirs_only || child.is_directory)
+    }
+
+    fn children(&self) -> Vec<LimitedView<'a>> {
+        if self.max_depth == 0 {
+            return Vec::new();
+        }
+        let mut children: Vec<LimitedView<'a>> = self
+            .listed()
+            .filter(|child| child.size >= self.min_size)
+            .map(|child| LimitedView {
+                node: child,
+                max_depth: self.max_depth - 1,
+                min_size: self.min_size,
+                dirs_only: self.dirs_only,
+                sort: self.sort,
+                percent_of_parent: percent_of(child.size, self.node.size),
+                scanned_at: child.scanned_at,
             })
+            .collect();
+        if let Some(sort) = self.sort {
+            children.sort_by(|a, b| sort.compare(a.node, b.node));
         }
+        children
     }
+}
 
-    pub fn get_result_with_depth(&self, path: &str, max_depth: u32) -> Result<FileNode, String> {
-        for root_node in self.cache.iter() {
-            if path.starts_with(&root_node.path) {
-                let relative_path = path.strip_prefix(&root_node.path).unwrap().to_string();
-                let mut current_node = root_node.value();
-                for part in relative_path.split('/').filter(|p| !p.is_empty()) {
-                    if let Some(child) = current_node.children.iter().find(|c| c.name == part) {
-                        current_node = child;
-                    } else {
-                        return Err("Path not found in cache".to_string());
-                    }
-                }
+impl Serialize for LimitedView<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
 
-                return Ok(current_node.limit_depth(max_depth + 1));
-            }
+        let node = self.node;
+        let cut_off = self.max_depth == 0;
+        let children = self.children();
+        let hidden_count = if cut_off {
+            node.hidden_count
+        } else {
+            node.hidden_count + self.listed().count() - children.len()
+        };
+
+        let mut state = serializer.serialize_struct("FileNode", 27)?;
+        state.serialize_field("name", &node.name)?;
+        state.serialize_field("path", &node.path)?;
+        state.serialize_field("size", &node.size)?;
+        state.serialize_field("apparent_size", &node.apparent_size)?;
+        state.serialize_field("is_directory", &node.is_directory)?;
+        state.serialize_field("extension", &node.extension)?;
+        state.serialize_field("children", &children)?;
+        state.serialize_field("children_count", &node.children_count)?;
+        state.serialize_field("total_files", &node.total_files)?;
+        state.serialize_field("show", &(node.show && !cut_off))?;
+        state.serialize_field(
+            "truncated",
+            &(node.truncated || cut_off && !node.children.is_empty()),
+        )?;
+        state.serialize_field("hidden_count", &hidden_count)?;
+        state.serialize_field("modified", &node.modified)?;
+        state.serialize_field("uid", &node.uid)?;
+        state.serialize_field("mode", &node.mode)?;
+        state.serialize_field("inaccessible", &node.inaccessible)?;
+        state.serialize_field("skipped", &node.skipped)?;
+        state.serialize_field("skipped_children", &node.skipped_children)?;
+        state.serialize_field("is_symlink", &node.is_symlink)?;
+        state.serialize_field("symlink_target", &node.symlink_target)?;
+        state.serialize_field("in_archive", &node.in_archive)?;
+        state.serialize_field("mount_point", &node.mount_point)?;
+        state.serialize_field("is_mount_point", &node.is_mount_point)?;
+        state.serialize_field("percent_of_parent", &self.percent_of_parent)?;
+        state.serialize_field("size_human", &SizeUnits::Decimal.format(node.size))?;
+        state.serialize_field("size_human_binary", &SizeUnits::Binary.format(node.size))?;
+        state.serialize_field("scanned_at", &self.scanned_at)?;
+        state.end()
+    }
+}
+
+/// A node of a `CompactTree`, with the fields of `FileNode` the tree views
+/// need
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactNode {
+    /// Index of the parent in `CompactTree::nodes`, `None` for the root
+    pub parent: Option<usize>,
+    pub name: String,
+    /// Name the node's path is joined with (see `encode_path`), only given
+    /// when it differs from `name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_name: Option<String>,
+    pub size: u64,
+    pub is_directory: bool,
+    pub extension: Option<String>,
+    pub children_count: usize,
+    pub show: bool,
+    pub truncated: bool,
+}
+
+/// A depth-limited tree as a flat list of nodes pointing at their parents,
+/// which keeps wide trees from repeating every path in full. A node's path
+/// is its parent's joined with its `path_name`, or else its name, by
+/// `separator`, which roots such as `/` already end with.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactTree {
+    pub root_path: String,
+    pub separator: char,
+    pub scanned_at: u64,
+    /// Nodes in depth-first order, so parents come before their children
+    pub nodes: Vec<CompactNode>,
+}
+
+impl CompactTree {
+    /// Appends `node` and its children down to `max_depth` levels, in
+    /// `sort` order if given, flagged like `LimitedView` flags them
+    fn push(
+        &mut self,
+        node: &FileNode,
+        parent: Option<usize>,
+        max_depth: u32,
+        sort: Option<SortKey>,
+    ) {
+        let cut_off = max_depth == 0;
+        let index = self.nodes.len();
+        self.nodes.push(CompactNode {
+            parent,
+            name: node.name.clone(),
+            path_name: Some(node.path.name())
+                .filter(|&path_name| path_name != node.name)
+                .map(str::to_string),
+            size: node.size,
+            is_directory: node.is_directory,
+            extension: node.extension.clone(),
+            children_count: node.children_count,
+            show: node.show && !cut_off,
+            truncated: node.truncated || cut_off && !node.children.is_empty(),
+        });
+        if cut_off {
+            return;
+        }
+        let mut children: Vec<&FileNode> = node.children.iter().collect();
+        if let Some(sort) = sort {
+            children.sort_by(|a, b| sort.compare(a, b));
+        }
+        for child in children {
+            self.push(child, Some(index), max_depth - 1, sort);
         }
-        Err("Path not found in cache".to_string())
     }
 }
 
-// New Tauri commands for on-demand loading using rayon for parallel processing
-#[tauri::command]
-pub async fn build_cache(app: AppHandle, path: String) -> Result<(), String> {
-    // Use rayon parallel processing to build cache
-    rayon::scope(|_s| SCANNER.build_cache(&path, app.clone()))
+/// Which children `get_result_with_depth` folds into a single remainder
+/// node. A child is folded if it falls outside either limit.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct CollapseOptions {
+    /// Fold children smaller than this share of their parent, in percent
+    pub min_percent: Option<f32>,
+    /// Keep at most this many of the largest children
+    pub max_children: Option<usize>,
 }
 
-#[tauri::command]
-pub async fn get_result_with_depth(
-    app: AppHandle,
-    path: String,
-    max_depth: u32,
-) -> Result<FileNode, String> {
-    if let Ok(node) = SCANNER.get_result_with_depth(&path, max_depth) {
-        Ok(node)
+/// Order of children in returned trees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortKey {
+    #[default]
+    SizeDesc,
+    SizeAsc,
+    NameAsc,
+    CountDesc,
+}
+
+impl SortKey {
+    /// Compares two siblings, breaking ties by name
+    pub fn compare(self, a: &FileNode, b: &FileNode) -> std::cmp::Ordering {
+        let by_key = match self {
+            SortKey::SizeDesc => b.size.cmp(&a.size),
+            SortKey::SizeAsc => a.size.cmp(&b.size),
+            SortKey::NameAsc => std::cmp::Ordering::Equal,
+            SortKey::CountDesc => b.children_count.cmp(&a.children_count),
+        };
+        by_key.then_with(|| a.name.cmp(&b.name))
+    }
+}
+
+/// Puts `node` at `parts` below `parent` (or removes what is there when
+/// `node` is `None`), refreshing the aggregates of every directory on the
+/// way and, with `by_size`, their order. Returns the node that was replaced.
+fn replace_descendant(
+    parent: &mut FileNode,
+    parts: &[&str],
+    node: Option<FileNode>,
+    case_insensitive: bool,
+    by_size: bool,
+) -> Result<Option<FileNode>, ScanError> {
+    let (name, rest) = parts.split_first().ok_or(ScanError::NotCached)?;
+    let own_skipped = parent.own_skipped();
+    let index = find_child(&parent.children, name, case_insensitive);
+    let replaced = if rest.is_empty() {
+        match (index, node) {
+            (Some(index), Some(node)) => Some(std::mem::replace(&mut parent.children[index], node)),
+            (Some(index), None) => {
+                parent.children_count = parent.children_count.saturating_sub(1);
+                Some(parent.children.remove(index))
+            }
+            (None, Some(node)) => {
+                parent.children_count += 1;
+                parent.children.push(node);
+                None
+            }
+            (None, None) => None,
+        }
     } else {
-        // Let us try again
-        build_cache(app, path.clone()).await?;
-        SCANNER.get_result_with_depth(&path, max_depth)
+        let index = index.ok_or(ScanError::NotCached)?;
+        replace_descendant(
+            &mut parent.children[index],
+            rest,
+            node,
+            case_insensitive,
+            by_size,
+        )?
+    };
+    parent.refresh_aggregates(own_skipped);
+    if by_size {
+        parent.sort_by_size();
     }
+    Ok(replaced)
 }
 
-#[tauri::command]
-pub async fn get_system_drives() -> Result<Vec<String>, String> {
-    println!("=== [Backend] Tauri command get_system_drives called");
+/// Runs `update` on the node at `parts` below `node` (or on `node` itself
+/// when `parts` is empty), then refreshes the aggregates of every directory
+/// above it and, with `by_size`, their order
+fn update_descendant(
+    node: &mut FileNode,
+    parts: &[&str],
+    case_insensitive: bool,
+    by_size: bool,
+    update: impl FnOnce(&mut FileNode),
+) -> Result<(), ScanError> {
+    let Some((name, rest)) = parts.split_first() else {
+        update(node);
+        return Ok(());
+    };
+    let own_skipped = node.own_skipped();
+    let index = find_child(&node.children, name, case_insensitive).ok_or(ScanError::NotCached)?;
+    update_descendant(
+        &mut node.children[index],
+        rest,
+        case_insensitive,
+        by_size,
+        update,
+    )?;
+    node.refresh_aggregates(own_skipped);
+    if by_size {
+        node.sort_by_size();
+    }
+    Ok(())
+}
 
-    // Use rayon for parallel processing
-    let result = rayon::scope(|_s| {
-        #[cfg(target_os = "macos")]
-        {
-            let drives = vec!["/".to_string()];
-            println!("=== [Backend] macOS system drives: {:?}", drives);
-            Ok(drives)
-        }
-        #[cfg(target_os = "linux")]
-        {
-            use std::process::Command;
-
-            println!("=== [Backend] Getting Linux system drives using df command");
-            let output = Command::new("df")
-                .args(&["-P", "-x", "tmpfs", "-x", "devtmpfs"])
-                .output()
-                .map_err(|e| e.to_string())?;
-
-            let mut drives = Vec::new();
-            for line in String::from_utf8_lossy(&output.stdout).lines().skip(1) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 6 {
-                    drives.push(parts[5].to_string());
+/// Index of the child called `name` in its path. With `case_insensitive`, a
+/// child whose name only differs in case is found too, an exact match still
+/// wins.
+fn find_child(children: &[FileNode], name: &str, case_insensitive: bool) -> Option<usize> {
+    children
+        .iter()
+        .position(|c| c.path.name() == name)
+        .or_else(|| {
+            if !case_insensitive {
+                return None;
+            }
+            let name = name.to_lowercase();
+            children
+                .iter()
+                .position(|c| c.path.name().to_lowercase() == name)
+        })
+}
+
+/// Whether the filesystem `root` is on ignores case in names, as default
+/// APFS and NTFS do. Checked by looking up `root` or one of its entries
+/// under a name with the case of its letters swapped.
+fn is_case_insensitive(root: &Path) -> bool {
+    let swapped = |path: &Path| {
+        let name = path.file_name()?.to_str()?;
+        let swapped: String = name
+            .chars()
+            .map(|c| {
+                if c.is_lowercase() {
+                    c.to_uppercase().next().unwrap_or(c)
+                } else {
+                    c.to_lowercase().next().unwrap_or(c)
                 }
+            })
+            .collect();
+        (swapped != name).then(|| path.with_file_name(swapped))
+    };
+    let candidate = swapped(root)
+        .map(|swapped| (root.to_path_buf(), swapped))
+        .or_else(|| {
+            fs::read_dir(root)
+                .ok()?
+                .flatten()
+                .take(64)
+                .find_map(|entry| Some((entry.path(), swapped(&entry.path())?)))
+        });
+    match candidate {
+        Some((original, swapped)) => {
+            match (
+                fs::symlink_metadata(&original),
+                fs::symlink_metadata(&swapped),
+            ) {
+                (Ok(original), Ok(swapped)) => same_file(&original, &swapped),
+                _ => false,
             }
-            println!("=== [Backend] Linux system drives: {:?}", drives);
-            Ok(drives)
         }
-        #[cfg(target_os = "windows")]
-        {
-            let drives = vec!["C:\\".to_string()];
-            println!("=== [Backend] Windows system drives: {:?}", drives);
-            Ok(drives)
+        // Nothing to check against, go by the platform's default
+        None => cfg!(any(windows, target_os = "macos")),
+    }
+}
+
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        a.dev() == b.dev() && a.ino() == b.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        // Both names resolved, and Windows names differing only in case
+        // refer to the same entry unless case sensitivity was turned on
+        a.len() == b.len() && a.modified().ok() == b.modified().ok()
+    }
+}
+
+/// Depth-first iterator over a `FileNode` tree, see `FileNode::iter`
+pub struct Iter<'a> {
+    stack: Vec<&'a FileNode>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a FileNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+fn owner_uid(metadata: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        Some(metadata.uid())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn device_id(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        Some(metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Whether another filesystem is mounted on the directory at `path`: on Unix
+/// when its device differs from its parent's, read from `parent` if given,
+/// and on Windows when it is the root of a volume
+fn is_mount_point(path: &Path, metadata: &fs::Metadata, parent: Option<&fs::Metadata>) -> bool {
+    #[cfg(unix)]
+    {
+        let parent_device = match parent {
+            Some(parent) => device_id(parent),
+            None => match path.parent() {
+                Some(parent) => fs::metadata(parent).ok().and_then(|m| device_id(&m)),
+                None => return true,
+            },
+        };
+        parent_device.is_some_and(|device| Some(device) != device_id(metadata))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+        // Volumes are only ever mounted on drive roots or reparse points, so
+        // the volume lookup is skipped for every other directory
+        let candidate = path.parent().is_none()
+            || metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0;
+        let _ = parent;
+        candidate && crate::drives::is_volume_root(path)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (path, metadata, parent);
+        false
+    }
+}
+
+fn permission_bits(metadata: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        Some(metadata.mode() & 0o7777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Sums sizes and counts, saturating at `u64::MAX` instead of overflowing.
+/// Hard links are counted once per link, so totals can exceed what is
+/// actually on disk.
+pub(crate) fn saturating_sum(values: impl Iterator<Item = u64>) -> u64 {
+    values.fold(0, u64::saturating_add)
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
+
+/// Actual disk space used by a single entry (handles sparse files)
+pub(crate) fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    #[cfg(target_family = "unix")]
+    {
+        // Unix: use st_blocks (each block is 512 bytes)
+        metadata.blocks() * 512
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        // Non-Unix systems: fall back to logical size
+        metadata.len()
+    }
+}
+
+/// The clone group of an APFS file whose blocks may be shared with clones of
+/// it, and the bytes only this file uses. Scans count a group's shared
+/// blocks for the first member they find and only the private bytes for the
+/// rest. This is a best effort: clones that have since been partly rewritten
+/// share less than the group suggests, and `directory_size` does not dedupe.
+#[cfg(target_os = "macos")]
+fn clone_info(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::{c_void, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    const ATTR_CMN_RETURNED_ATTRS: u32 = 0x8000_0000;
+    const ATTR_CMNEXT_PRIVATESIZE: u32 = 0x0000_0008;
+    const ATTR_CMNEXT_CLONEID: u32 = 0x0000_0100;
+    const ATTR_CMNEXT_EXT_FLAGS: u32 = 0x0000_0200;
+    const FSOPT_NOFOLLOW: u32 = 0x0000_0001;
+    const FSOPT_PACK_INVAL_ATTRS: u32 = 0x0000_0008;
+    const FSOPT_ATTR_CMN_EXTENDED: u32 = 0x0000_0020;
+    const EF_MAY_SHARE_BLOCKS: u64 = 0x0000_0001;
+
+    // Layout of the reply, attributes come in the order of their bits
+    #[repr(C)]
+    #[derive(Default)]
+    struct Reply {
+        _length: u32,
+        returned: [u32; 5],
+        private_size: i64,
+        clone_id: u64,
+        ext_flags: u64,
+    }
+
+    let wanted = ATTR_CMNEXT_PRIVATESIZE | ATTR_CMNEXT_CLONEID | ATTR_CMNEXT_EXT_FLAGS;
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut request = libc::attrlist {
+        bitmapcount: libc::ATTR_BIT_MAP_COUNT,
+        reserved: 0,
+        commonattr: ATTR_CMN_RETURNED_ATTRS,
+        volattr: 0,
+        dirattr: 0,
+        fileattr: 0,
+        forkattr: wanted,
+    };
+    let mut reply = Reply::default();
+    // SAFETY: both buffers are valid for the sizes passed and outlive the call
+    let result = unsafe {
+        libc::getattrlist(
+            path.as_ptr(),
+            &mut request as *mut libc::attrlist as *mut c_void,
+            &mut reply as *mut Reply as *mut c_void,
+            std::mem::size_of::<Reply>(),
+            FSOPT_NOFOLLOW | FSOPT_PACK_INVAL_ATTRS | FSOPT_ATTR_CMN_EXTENDED,
+        )
+    };
+    // The fork attributes come back in the last word of `returned`
+    if result != 0 || reply.returned[4] & wanted != wanted {
+        return None;
+    }
+    if reply.ext_flags & EF_MAY_SHARE_BLOCKS == 0 {
+        return None;
+    }
+    Some((reply.clone_id, reply.private_size.max(0) as u64))
+}
+
+/// Pseudo and mount-point directories that are never descended into
+pub(crate) fn is_excluded(path: &Path) -> bool {
+    let path_str = path.to_string_lossy();
+
+    // Skip /Volumes and /System/Volumes on macOS
+    #[cfg(target_os = "macos")]
+    if path_str == "/Volumes" || path_str == "/System/Volumes" {
+        return true;
+    }
+
+    // Skip /proc, /sys, /dev on Linux
+    #[cfg(target_os = "linux")]
+    if path_str == "/proc" || path_str == "/sys" || path_str == "/dev" {
+        return true;
+    }
+
+    let _ = path_str;
+    false
+}
+
+/// How a root is scanned, as passed to `build_cache`. Fields left out take
+/// their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Whether entries whose names start with a dot are scanned
+    pub include_hidden: bool,
+    /// Glob patterns matched against entry names and full paths, matching
+    /// entries are left out
+    pub exclude_globs: Vec<String>,
+    /// Whether symlinks to directories are descended into
+    pub follow_symlinks: bool,
+    /// Cap on threads for this scan, `None` to use the scanner's pool
+    pub max_threads: Option<usize>,
+    /// Whether the entries of ZIP archives are listed below them
+    pub scan_archives: bool,
+    /// Whether to stay on the root's filesystem, like `du -x`. Only
+    /// supported on Unix.
+    pub one_filesystem: bool,
+    /// Number of directory levels below the root that are scanned into
+    /// nodes, `None` for all. Directories at the limit are only measured:
+    /// they get their full size but no children, and are marked `truncated`
+    /// until `expand` scans them.
+    pub max_scan_depth: Option<u32>,
+    /// Whether children are kept in the order the directory was read in
+    /// rather than largest first. Saves sorting huge directories; results
+    /// are still sorted on request.
+    pub preserve_order: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            include_hidden: true,
+            exclude_globs: Vec::new(),
+            follow_symlinks: false,
+            max_threads: None,
+            scan_archives: false,
+            one_filesystem: false,
+            max_scan_depth: None,
+            preserve_order: false,
         }
-    });
+    }
+}
 
-    result
+/// `ScanOptions` prepared for one scan
+struct ScanFilter {
+    include_hidden: bool,
+    exclude: Vec<glob::Pattern>,
+    follow_symlinks: bool,
+    scan_archives: bool,
+    /// Device of the root when the scan stays on its filesystem
+    device: Option<u64>,
+    /// Canonical path of the scanned root, whose contents are never entered
+    /// a second time through a symlink
+    root: PathBuf,
+    /// Canonical targets of the symlinks followed so far, so cycles end
+    visited: DashSet<PathBuf>,
+    /// APFS clone groups whose shared blocks have already been counted
+    #[cfg(target_os = "macos")]
+    clone_groups: DashSet<u64>,
+}
+
+impl ScanFilter {
+    fn new(options: &ScanOptions, root: &Path) -> Result<Self, ScanError> {
+        let exclude = options
+            .exclude_globs
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .map_err(|e| ScanError::InvalidPattern(format!("{}: {}", pattern, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            include_hidden: options.include_hidden,
+            exclude,
+            follow_symlinks: options.follow_symlinks,
+            scan_archives: options.scan_archives,
+            device: if options.one_filesystem {
+                fs::metadata(root)
+                    .ok()
+                    .and_then(|metadata| device_id(&metadata))
+            } else {
+                None
+            },
+            root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+            visited: DashSet::new(),
+            #[cfg(target_os = "macos")]
+            clone_groups: DashSet::new(),
+        })
+    }
+
+    /// Whether the entry at `path` is left out of the scan
+    fn skips(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if !self.include_hidden && name.starts_with('.') {
+            return true;
+        }
+        if self.exclude.is_empty() {
+            return false;
+        }
+        // Match the path as shown to users, without the long path prefix
+        let full_path = encode_path(path);
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(&name) || pattern.matches(&full_path))
+    }
+
+    /// Whether a directory is on another device than the root, for scans
+    /// that stay on one filesystem
+    fn crosses_device(&self, metadata: &fs::Metadata) -> bool {
+        self.device
+            .is_some_and(|device| device_id(metadata) != Some(device))
+    }
+
+    /// Metadata of the directory a symlink points to, if it should be
+    /// scanned as a directory of its own
+    fn followed_dir(&self, path: &Path, metadata: &fs::Metadata) -> Option<fs::Metadata> {
+        if !self.follow_symlinks || !metadata.file_type().is_symlink() {
+            return None;
+        }
+        let target = fs::metadata(path).ok().filter(|target| target.is_dir())?;
+        let canonical = fs::canonicalize(path).ok()?;
+        if canonical.starts_with(&self.root) || !self.visited.insert(canonical) {
+            return None;
+        }
+        Some(target)
+    }
+}
+
+/// A directory found during traversal whose node is built once all of its
+/// subdirectories have been scanned
+struct PendingDir {
+    path: PathBuf,
+    node_path: NodePath,
+    metadata: fs::Metadata,
+    parent: Option<usize>,
+    /// Index of this directory among the entries its parent read
+    position: usize,
+    /// See `FileNode::is_mount_point`
+    is_mount_point: bool,
+    /// Nodes with their index among the entries read, see `position`
+    children: Vec<(usize, FileNode)>,
+    /// Entries of this directory that could not be read
+    skipped: u64,
+    /// Entries of this directory on disk, see `FileNode::children_count`
+    entry_count: usize,
+    error: Option<ScanError>,
+    /// Totals of a directory at the scan's depth limit, which is measured
+    /// instead of listed
+    measured: Option<DirectorySize>,
+}
+
+impl PendingDir {
+    fn new(
+        path: PathBuf,
+        node_path: NodePath,
+        metadata: fs::Metadata,
+        parent: Option<usize>,
+    ) -> Self {
+        Self {
+            path,
+            node_path,
+            metadata,
+            parent,
+            position: 0,
+            is_mount_point: false,
+            children: Vec::new(),
+            skipped: 0,
+            entry_count: 0,
+            error: None,
+            measured: None,
+        }
+    }
+
+    /// Builds the directory's node, with its children in the order they
+    /// were read in if `preserve_order` is set and largest first otherwise.
+    /// A directory that could not be listed becomes an empty node flagged
+    /// as inaccessible.
+    fn into_node(mut self, preserve_order: bool) -> FileNode {
+        let inaccessible = self.error.is_some();
+        if preserve_order {
+            self.children
+                .sort_unstable_by_key(|(position, _)| *position);
+        }
+        let mut node = FileNode {
+            name: node_name(&self.path),
+            path: self.node_path,
+            size: 0,
+            apparent_size: 0,
+            is_directory: true,
+            extension: None,
+            children: self.children.into_iter().map(|(_, child)| child).collect(),
+            children_count: self.entry_count,
+            total_files: 0,
+            show: true,
+            truncated: false,
+            hidden_count: 0,
+            modified: modified_secs(&self.metadata),
+            uid: owner_uid(&self.metadata),
+            mode: permission_bits(&self.metadata),
+            inaccessible,
+            skipped: 0,
+            skipped_children: 0,
+            is_symlink: false,
+            symlink_target: None,
+            in_archive: false,
+            mount_point: false,
+            is_mount_point: self.is_mount_point,
+            percent_of_parent: 0.0,
+            size_human: String::new(),
+            size_human_binary: String::new(),
+            scanned_at: None,
+        };
+        node.refresh_aggregates(self.skipped.saturating_add(u64::from(inaccessible)));
+        if !preserve_order {
+            node.sort_by_size();
+        }
+        if let Some(measured) = self.measured {
+            node.size = measured.size;
+            node.apparent_size = measured.apparent_size;
+            node.total_files = measured.file_count;
+            node.truncated = measured.file_count + measured.dir_count > 0;
+        }
+        node
+    }
+}
+
+/// Immediate entries of a single directory
+#[derive(Default)]
+struct DirListing {
+    /// Nodes with their index among the entries read
+    files: Vec<(usize, FileNode)>,
+    subdirs: Vec<(usize, PathBuf, fs::Metadata)>,
+    /// Entries that could not be read
+    skipped: u64,
+    /// Entries listed, including unreadable and left out ones
+    entry_count: usize,
+}
+
+/// A scanned root kept in memory
+struct CacheEntry {
+    root: FileNode,
+    /// Options the root was scanned with, reused when parts are rescanned
+    options: ScanOptions,
+    /// Seconds since the Unix epoch when the scan finished
+    scanned_at: u64,
+    node_count: usize,
+    /// Whether names are looked up regardless of case, see
+    /// `is_case_insensitive`
+    case_insensitive: bool,
+    /// Logical time of the last query, used for LRU eviction
+    last_access: AtomicU64,
+}
+
+/// Summary of a cached root, as listed by `list_cached_roots`
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedRoot {
+    pub path: String,
+    pub size: u64,
+    pub scanned_at: u64,
+    pub node_count: usize,
+}
+
+/// Memory used by the cache, as reported by `cache_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub root_count: usize,
+    pub total_nodes: usize,
+    pub estimated_bytes: usize,
+}
+
+/// A slice of a directory's children, as returned by `get_children_page`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChildrenPage {
+    /// The requested children, without their own children
+    pub children: Vec<FileNode>,
+    /// Number of children the directory has in total
+    pub total: usize,
+}
+
+/// Number of children per batch sent by `stream_children`
+const STREAM_BATCH_SIZE: usize = 500;
+
+/// A batch of a directory's children, as sent by `stream_children`. The
+/// last batch has `done` set and may be empty.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChildrenBatch {
+    /// The next children in order, without their own children
+    pub children: Vec<FileNode>,
+    /// Number of children the directory has in total
+    pub total: usize,
+    pub done: bool,
+}
+
+/// Totals for a cached directory, as returned by `get_directory_info`
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryInfo {
+    pub path: String,
+    pub size: u64,
+    pub file_count: u64,
+    /// Number of directories below this one
+    pub dir_count: u64,
+    /// `size` divided by `file_count`, 0 without files
+    pub avg_file_size: u64,
+}
+
+/// One step of the way from a cached root to a node, see `ancestors`
+#[derive(Debug, Clone, Serialize)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+}
+
+/// How far a cached tree is off from the filesystem, see `DiskScanner::drift`
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheDrift {
+    /// Entries on disk missing from the cache. A new directory counts once,
+    /// however much is in it.
+    pub added: u64,
+    /// Cached entries gone from disk, counted like `added`
+    pub removed: u64,
+    /// Files whose size on disk differs from the cached one
+    pub size_changed: u64,
+}
+
+impl CacheDrift {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            added: self.added + other.added,
+            removed: self.removed + other.removed,
+            size_changed: self.size_changed + other.size_changed,
+        }
+    }
+}
+
+/// Outcome of scanning one of the roots passed to `build_cache_many`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanResult {
+    pub path: String,
+    /// Why the scan failed, `None` on success
+    pub error: Option<ScanError>,
+}
+
+/// Totals for a directory, as returned by `directory_size`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectorySize {
+    pub size: u64,
+    /// Logical length of the files, see `FileNode::apparent_size`
+    pub apparent_size: u64,
+    pub file_count: u64,
+    /// Number of directories below the measured one
+    pub dir_count: u64,
+}
+
+impl DirectorySize {
+    fn add(&mut self, other: &DirectorySize) {
+        self.size = self.size.saturating_add(other.size);
+        self.apparent_size = self.apparent_size.saturating_add(other.apparent_size);
+        self.file_count += other.file_count;
+        self.dir_count += other.dir_count;
+    }
+}
+
+/// Environment variable capping the number of scanning threads
+const MAX_THREADS_ENV: &str = "MAKA_MAX_THREADS";
+
+/// Number of roots kept in memory unless configured otherwise
+const DEFAULT_MAX_CACHED_ROOTS: usize = 8;
+
+/// Builds the pool scans run on. `None` or `0` uses one thread per logical CPU.
+fn build_thread_pool(max_threads: Option<usize>) -> Result<ThreadPool, ScanError> {
+    ThreadPoolBuilder::new()
+        .num_threads(max_threads.unwrap_or(0))
+        .thread_name(|index| format!("maka-scan-{}", index))
+        .build()
+        .map_err(|e| ScanError::Io(e.to_string()))
+}
+
+pub struct DiskScanner {
+    // Cache root file nodes in memory
+    cache: DashMap<String, CacheEntry>,
+    // Dedicated pool so scans don't compete with the global rayon pool
+    thread_pool: RwLock<Arc<ThreadPool>>,
+    // Pools for scans asking for their own thread count, built once per count
+    sized_pools: DashMap<usize, Arc<ThreadPool>>,
+    // Least recently queried roots are evicted beyond this many
+    max_cached_roots: AtomicUsize,
+    access_clock: AtomicU64,
+}
+
+impl Default for DiskScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskScanner {
+    pub fn new() -> Self {
+        let max_threads = std::env::var(MAX_THREADS_ENV)
+            .ok()
+            .and_then(|value| value.parse().ok());
+        let thread_pool = build_thread_pool(max_threads)
+            .or_else(|_| build_thread_pool(None))
+            .expect("Failed to build scanner thread pool");
+
+        Self {
+            cache: DashMap::new(),
+            thread_pool: RwLock::new(Arc::new(thread_pool)),
+            sized_pools: DashMap::new(),
+            max_cached_roots: AtomicUsize::new(DEFAULT_MAX_CACHED_ROOTS),
+            access_clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Replaces the scanning thread pool. Scans already running keep the
+    /// pool they started with.
+    pub fn set_max_threads(&self, max_threads: Option<usize>) -> Result<(), ScanError> {
+        let thread_pool = build_thread_pool(max_threads)?;
+        *self.thread_pool.write() = Arc::new(thread_pool);
+        Ok(())
+    }
+
+    /// Limits how many roots stay cached, evicting the least recently used
+    /// ones right away if there are already more. `None` restores the default.
+    pub fn set_max_cached_roots(&self, max_cached_roots: Option<usize>) {
+        let max_cached_roots = max_cached_roots.unwrap_or(DEFAULT_MAX_CACHED_ROOTS).max(1);
+        self.max_cached_roots
+            .store(max_cached_roots, Ordering::Relaxed);
+        self.evict_least_recently_used();
+    }
+
+    fn tick(&self) -> u64 {
+        self.access_clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn evict_least_recently_used(&self) {
+        while self.cache.len() > self.max_cached_roots.load(Ordering::Relaxed) {
+            // Find the key first, removing while iterating would deadlock
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|entry| entry.last_access.load(Ordering::Relaxed))
+                .map(|entry| entry.key().clone());
+            match oldest {
+                Some(key) => {
+                    self.cache.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// The pool a scan with `options` runs on
+    fn pool_for(&self, options: &ScanOptions) -> Result<Arc<ThreadPool>, ScanError> {
+        match options.max_threads {
+            Some(max_threads) => {
+                let pool = self
+                    .sized_pools
+                    .entry(max_threads)
+                    .or_try_insert_with(|| build_thread_pool(Some(max_threads)).map(Arc::new))?;
+                Ok(Arc::clone(pool.value()))
+            }
+            None => Ok(Arc::clone(&self.thread_pool.read())),
+        }
+    }
+
+    /// Scans `path` and caches the tree. Cached roots inside `path` are
+    /// replaced by it, and a `path` inside a cached root is spliced into
+    /// that tree instead of being cached on its own, so no part of the disk
+    /// is held (and counted) twice.
+    pub fn build_cache(
+        &self,
+        path: &str,
+        options: ScanOptions,
+        app_handle: AppHandle,
+    ) -> Result<(), ScanError> {
+        let tracker = Arc::new(ProgressTracker::new(app_handle, None));
+        self.build_cache_tracked(path, options, tracker)
+    }
+
+    /// Like `build_cache`, reporting to and cancelled through `tracker`
+    #[instrument(name = "build_cache", skip(self, options, tracker))]
+    pub fn build_cache_tracked(
+        &self,
+        path: &str,
+        options: ScanOptions,
+        tracker: Arc<ProgressTracker>,
+    ) -> Result<(), ScanError> {
+        let path = &canonicalize(path).unwrap_or_else(|| path.to_string());
+        let started = Instant::now();
+        let thread_pool = self.pool_for(&options)?;
+        let root_node = thread_pool
+            .install(|| self.scan_file_or_directory(&decode_path(path), &options, &tracker))?;
+        tracker.emit_final();
+        let scanned_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let node_count = root_node.iter().count();
+        let summary = ScanSummary::of(path, &root_node, started.elapsed());
+        info!(
+            nodes = node_count,
+            bytes = summary.total_bytes,
+            skipped = summary.skipped,
+            elapsed_ms = summary.duration_ms,
+            "Scan finished"
+        );
+        if let Some(app_handle) = &tracker.app_handle {
+            let _ = app_handle.emit("scan-summary", summary);
+        }
+
+        self.cache
+            .retain(|key, _| relative_to_root(key, path).is_none());
+        let parent_listed = decode_path(path).parent().is_some_and(|parent| {
+            self.with_node(&encode_path(parent), |node| {
+                !(node.truncated && node.children.is_empty())
+            })
+            .unwrap_or(false)
+        });
+        if parent_listed {
+            return self.merge_subtree(path, Some(root_node));
+        }
+        self.cache.insert(
+            path.to_string(),
+            CacheEntry {
+                root: root_node,
+                options,
+                scanned_at,
+                node_count,
+                case_insensitive: is_case_insensitive(&decode_path(path)),
+                last_access: AtomicU64::new(self.tick()),
+            },
+        );
+        self.evict_least_recently_used();
+        Ok(())
+    }
+
+    /// Scans and caches several roots in parallel, reporting on each one
+    pub fn build_cache_many(
+        &self,
+        paths: Vec<String>,
+        options: ScanOptions,
+        app_handle: AppHandle,
+    ) -> Vec<ScanResult> {
+        self.build_cache_many_tracked(paths, options, || {
+            Arc::new(ProgressTracker::new(app_handle.clone(), None))
+        })
+    }
+
+    /// Like `build_cache_many`, scanning each root with a tracker from
+    /// `new_tracker`
+    pub fn build_cache_many_tracked(
+        &self,
+        paths: Vec<String>,
+        options: ScanOptions,
+        new_tracker: impl Fn() -> Arc<ProgressTracker> + Sync,
+    ) -> Vec<ScanResult> {
+        let thread_pool = Arc::clone(&self.thread_pool.read());
+        thread_pool.install(|| {
+            paths
+                .into_par_iter()
+                .map(|path| {
+                    let error = self
+                        .build_cache_tracked(&path, options.clone(), new_tracker())
+                        .err();
+                    ScanResult { path, error }
+                })
+                .collect()
+        })
+    }
+
+    /// Rescans `path` with the options of the cached tree containing it and
+    /// splices the result in. A path that no longer exists is dropped from
+    /// the cache.
+    pub fn rescan_subtree(&self, path: &str, app_handle: AppHandle) -> Result<(), ScanError> {
+        let tracker = Arc::new(ProgressTracker::new(app_handle, None));
+        self.rescan_subtree_tracked(path, &tracker)
+    }
+
+    /// Like `rescan_subtree`, reporting to `tracker`
+    pub fn rescan_subtree_tracked(
+        &self,
+        path: &str,
+        tracker: &Arc<ProgressTracker>,
+    ) -> Result<(), ScanError> {
+        let options = self
+            .cache
+            .iter()
+            .filter(|entry| relative_to_root(path, entry.key()).is_some())
+            .max_by_key(|entry| entry.key().len())
+            .map(|entry| entry.options.clone())
+            .ok_or(ScanError::NotCached)?;
+        let thread_pool = self.pool_for(&options)?;
+        let node = match thread_pool
+            .install(|| self.scan_file_or_directory(&decode_path(path), &options, tracker))
+        {
+            Ok(node) => Some(node),
+            Err(ScanError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        self.merge_subtree(path, node)
+    }
+
+    /// Scans the cached directory at `path` if it was left at the depth limit
+    /// of its scan (see `ScanOptions::max_scan_depth`), so its children can
+    /// be shown. The scan goes as deep again as the original one. A path
+    /// further down, inside a directory at the limit, is reached by
+    /// expanding the directories on the way one scan at a time; their
+    /// ancestors are updated as each scan is merged in. Fails with
+    /// `NotCached` if `path` is not inside a cached root, or not found
+    /// once nothing is left to expand.
+    pub fn expand(&self, path: &str, app_handle: AppHandle) -> Result<(), ScanError> {
+        let tracker = Arc::new(ProgressTracker::new(app_handle, None));
+        self.expand_tracked(path, &tracker)
+    }
+
+    /// Like `expand`, reporting to `tracker`
+    pub fn expand_tracked(
+        &self,
+        path: &str,
+        tracker: &Arc<ProgressTracker>,
+    ) -> Result<(), ScanError> {
+        let mut expanded = None;
+        loop {
+            let (cut_off, complete) = self.with_partial_chain(path, |_, chain, complete| {
+                let node = chain[chain.len() - 1];
+                let cut_off = node.truncated && node.children.is_empty();
+                (cut_off.then(|| node.path.to_string()), complete)
+            })?;
+            match cut_off {
+                // Stop if a scan left the directory cut off, e.g. with a
+                // `max_scan_depth` of 0
+                Some(dir) if expanded.as_ref() != Some(&dir) => {
+                    self.rescan_subtree_tracked(&dir, tracker)?;
+                    expanded = Some(dir);
+                }
+                _ if complete => return Ok(()),
+                _ => return Err(ScanError::NotCached),
+            }
+        }
+    }
+
+    /// Makes `path` available in the cache: expands it (see `expand`) inside
+    /// a cached root, and scans it with `options` when it is not inside one.
+    /// Expanding can fail too, e.g. on a directory that can no longer be
+    /// read, which is returned rather than leaving it cut off unnoticed.
+    pub fn ensure_cached_tracked(
+        &self,
+        path: &str,
+        options: impl FnOnce() -> ScanOptions,
+        tracker: &Arc<ProgressTracker>,
+    ) -> Result<(), ScanError> {
+        match self.expand_tracked(path, tracker) {
+            Err(ScanError::NotCached) => {
+                self.build_cache_tracked(path, options(), Arc::clone(tracker))
+            }
+            result => result,
+        }
+    }
+
+    /// Compares the cached tree at `path` with the filesystem, walking both
+    /// side by side without touching the cache. Directories the scan did not
+    /// list, like those at its depth limit or on another filesystem, are not
+    /// compared.
+    pub fn drift(&self, path: &str) -> Result<CacheDrift, ScanError> {
+        self.with_chain(path, |entry, chain| {
+            let filter =
+                ScanFilter::new(&entry.options, &decode_path(&entry.root.path.to_string()))?;
+            let thread_pool = self.pool_for(&entry.options)?;
+            let node = chain[chain.len() - 1];
+            Ok(thread_pool.install(|| Self::drift_of(node, &decode_path(path), &filter)))
+        })?
+    }
+
+    /// Drift of the cached `node` from the entry at `path` on disk
+    fn drift_of(node: &FileNode, path: &Path, filter: &ScanFilter) -> CacheDrift {
+        let replaced = CacheDrift {
+            added: 1,
+            removed: 1,
+            size_changed: 0,
+        };
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return CacheDrift {
+                    removed: 1,
+                    ..Default::default()
+                }
+            }
+            Err(_) => return CacheDrift::default(),
+        };
+        if !node.is_directory {
+            return if metadata.is_dir() {
+                replaced
+            } else if metadata.len() != node.apparent_size {
+                CacheDrift {
+                    size_changed: 1,
+                    ..Default::default()
+                }
+            } else {
+                CacheDrift::default()
+            };
+        }
+        // Followed symlinks are cached as the directories they point to
+        if !metadata.is_dir() && !fs::metadata(path).is_ok_and(|target| target.is_dir()) {
+            return replaced;
+        }
+        let cut_off = node.truncated && node.children.is_empty();
+        if node.mount_point || node.inaccessible || cut_off || is_excluded(path) {
+            return CacheDrift::default();
+        }
+        let Ok(entries) = fs::read_dir(path) else {
+            return CacheDrift::default();
+        };
+
+        let cached: HashMap<&str, &FileNode> = node
+            .children
+            .iter()
+            .map(|child| (child.path.name(), child))
+            .collect();
+        let (drift, matched) = entries
+            .par_bridge()
+            .filter_map(|entry| {
+                let entry_path = entry.ok()?.path();
+                if filter.skips(&entry_path) {
+                    return None;
+                }
+                Some(match cached.get(encoded_name(&entry_path).as_str()) {
+                    Some(child) => (Self::drift_of(child, &entry_path, filter), 1),
+                    None => (
+                        CacheDrift {
+                            added: 1,
+                            ..Default::default()
+                        },
+                        0,
+                    ),
+                })
+            })
+            .reduce(
+                || (CacheDrift::default(), 0),
+                |(a, a_matched), (b, b_matched)| (a.merge(b), a_matched + b_matched),
+            );
+        CacheDrift {
+            removed: drift.removed + (cached.len() - matched) as u64,
+            ..drift
+        }
+    }
+
+    /// Drops the cached node at `path` and takes its size off its ancestors
+    pub fn remove_node(&self, path: &str) -> Result<(), ScanError> {
+        self.merge_subtree(path, None)
+    }
+
+    /// Replaces (or with `None`, removes) the cached node at `path` and
+    /// updates the sizes and counts of its ancestors
+    fn merge_subtree(&self, path: &str, node: Option<FileNode>) -> Result<(), ScanError> {
+        let key = self.root_key(path)?;
+        let mut entry = self.cache.get_mut(&key).ok_or(ScanError::NotCached)?;
+        let relative_path = relative_to_root(path, entry.key()).ok_or(ScanError::NotCached)?;
+        let parts: Vec<&str> = components(relative_path).collect();
+
+        let added = node.as_ref().map_or(0, |node| node.iter().count());
+        let replaced = if parts.is_empty() {
+            match node {
+                Some(node) => Some(std::mem::replace(&mut entry.root, node)),
+                None => {
+                    drop(entry);
+                    self.cache.remove(&key);
+                    return Ok(());
+                }
+            }
+        } else {
+            let case_insensitive = entry.case_insensitive;
+            let by_size = !entry.options.preserve_order;
+            replace_descendant(&mut entry.root, &parts, node, case_insensitive, by_size)?
+        };
+        let removed = replaced.map_or(0, |node| node.iter().count());
+        entry.node_count = entry
+            .node_count
+            .saturating_add(added)
+            .saturating_sub(removed);
+        Ok(())
+    }
+
+    /// Key of the most specific cached root containing `path`
+    fn root_key(&self, path: &str) -> Result<String, ScanError> {
+        self.cache
+            .iter()
+            .filter(|entry| relative_to_root(path, entry.key()).is_some())
+            .max_by_key(|entry| entry.key().len())
+            .map(|entry| entry.key().clone())
+            .ok_or(ScanError::NotCached)
+    }
+
+    /// Re-measures the cached node at `path` and carries the change up to
+    /// its ancestors, returning the new size. Unlike `rescan_subtree` the
+    /// node's children are left as they were, only its totals change.
+    pub fn recompute_size(&self, path: &str) -> Result<u64, ScanError> {
+        let key = self.root_key(path)?;
+        // Measured the way the tree was scanned, so the totals agree with
+        // those of the nodes around it
+        let options = self
+            .cache
+            .get(&key)
+            .map(|entry| entry.options.clone())
+            .ok_or(ScanError::NotCached)?;
+        let thread_pool = self.pool_for(&options)?;
+        let totals = thread_pool.install(|| Self::measure(&decode_path(path), &options))?;
+        let mut entry = self.cache.get_mut(&key).ok_or(ScanError::NotCached)?;
+        let relative_path = relative_to_root(path, entry.key()).ok_or(ScanError::NotCached)?;
+        let parts: Vec<&str> = components(relative_path).collect();
+        let case_insensitive = entry.case_insensitive;
+        let by_size = !entry.options.preserve_order;
+        update_descendant(&mut entry.root, &parts, case_insensitive, by_size, |node| {
+            node.size = totals.size;
+            node.apparent_size = totals.apparent_size;
+            node.total_files = totals.file_count;
+        })?;
+        Ok(totals.size)
+    }
+
+    fn scan_file_or_directory(
+        &self,
+        path: &Path,
+        options: &ScanOptions,
+        tracker: &Arc<ProgressTracker>,
+    ) -> Result<FileNode, ScanError> {
+        // Every path below is built from this one, so all of them get the
+        // prefix Windows needs for long paths
+        let path = &extended_length(path);
+        // Every entry is stat'ed exactly once and its node built from that
+        // one result, see `FileNode::size`
+        let metadata = fs::symlink_metadata(path)?;
+        let filter = ScanFilter::new(options, path)?;
+        if !metadata.is_dir() {
+            return Ok(Self::file_node(path, None, &metadata, &filter, tracker));
+        }
+
+        // Walk the tree breadth-first with an explicit worklist rather than
+        // recursing per level, so arbitrarily deep trees cannot overflow the
+        // stack. Each level is listed in parallel, and a directory always
+        // comes before its subdirectories in `dirs`.
+        let mut dirs = vec![PendingDir {
+            is_mount_point: is_mount_point(path, &metadata, None),
+            ..PendingDir::new(path.to_path_buf(), NodePath::of(path, None), metadata, None)
+        }];
+        let mut level_start = 0;
+        let mut depth = 0;
+        while level_start < dirs.len() {
+            if options
+                .max_scan_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+            {
+                tracker.check_cancelled()?;
+                dirs[level_start..].par_iter_mut().try_for_each(|dir| {
+                    match fs::read_dir(&dir.path) {
+                        Ok(entries) => {
+                            dir.entry_count = entries.count();
+                            dir.measured = Some(Self::measure_below(&dir.path, &filter, tracker)?);
+                        }
+                        Err(e) => dir.error = Some(e.into()),
+                    }
+                    Ok::<_, ScanError>(())
+                })?;
+                break;
+            }
+
+            let level_end = dirs.len();
+            let listings: Vec<Result<DirListing, ScanError>> = dirs[level_start..level_end]
+                .par_iter()
+                .map(|dir| Self::list_directory(&dir.path, &dir.node_path, &filter, tracker))
+                .collect();
+
+            for (index, listing) in (level_start..level_end).zip(listings) {
+                match listing {
+                    Ok(listing) => {
+                        dirs[index].children = listing.files;
+                        dirs[index].skipped = listing.skipped;
+                        dirs[index].entry_count = listing.entry_count;
+                        let parent_path = dirs[index].node_path.clone();
+                        let parent_metadata = dirs[index].metadata.clone();
+                        dirs.extend(listing.subdirs.into_iter().map(
+                            |(position, subdir, metadata)| {
+                                let node_path = NodePath::of(&subdir, Some(&parent_path));
+                                PendingDir {
+                                    position,
+                                    is_mount_point: is_mount_point(
+                                        &subdir,
+                                        &metadata,
+                                        Some(&parent_metadata),
+                                    ),
+                                    ..PendingDir::new(subdir, node_path, metadata, Some(index))
+                                }
+                            },
+                        ));
+                    }
+                    Err(ScanError::Cancelled) => return Err(ScanError::Cancelled),
+                    Err(e) => dirs[index].error = Some(e),
+                }
+            }
+            level_start = level_end;
+            depth += 1;
+        }
+
+        // Build nodes bottom-up: popping from the back finishes every
+        // subdirectory before its parent. Unreadable subdirectories are kept
+        // as inaccessible nodes so the missing data stays visible.
+        while dirs.len() > 1 {
+            let dir = dirs.pop().unwrap();
+            let parent = dir.parent.expect("Only the root has no parent");
+            let position = dir.position;
+            dirs[parent]
+                .children
+                .push((position, dir.into_node(options.preserve_order)));
+        }
+
+        let mut root = dirs.pop().unwrap();
+        if let Some(e) = root.error.take() {
+            return Err(e);
+        }
+        Ok(root.into_node(options.preserve_order))
+    }
+
+    /// Reads the immediate entries of a directory, turning files into leaf
+    /// nodes and returning subdirectories to be visited later
+    fn list_directory(
+        path: &Path,
+        node_path: &NodePath,
+        filter: &ScanFilter,
+        tracker: &ProgressTracker,
+    ) -> Result<DirListing, ScanError> {
+        tracker.check_cancelled()?;
+        if is_excluded(path) {
+            return Ok(DirListing::default());
+        }
+
+        let entries = fs::read_dir(path)?;
+        let skipped = AtomicU64::new(0);
+        let skip = || {
+            skipped.fetch_add(1, Ordering::Relaxed);
+        };
+        let entry_count = AtomicUsize::new(0);
+
+        // Use rayon for parallel processing of directory entries, numbering
+        // them first so the order they were read in can be restored
+        let (subdirs, files) = entries
+            .enumerate()
+            .par_bridge() // Convert to parallel iterator
+            .inspect(|_| {
+                entry_count.fetch_add(1, Ordering::Relaxed);
+            })
+            .filter_map(|(position, entry)| {
+                let entry = entry.inspect_err(|_| skip()).ok()?;
+                let entry_path = entry.path();
+                if filter.skips(&entry_path) {
+                    return None;
+                }
+                let Ok(metadata) = fs::symlink_metadata(&entry_path) else {
+                    skip();
+                    return None;
+                };
+                let target = filter.followed_dir(&entry_path, &metadata);
+                if metadata.is_dir() || target.is_some() {
+                    let metadata = target.unwrap_or(metadata);
+                    if filter.crosses_device(&metadata) {
+                        Some(Either::Right((
+                            position,
+                            Self::mount_point_node(&entry_path, Some(node_path), &metadata),
+                        )))
+                    } else {
+                        Some(Either::Left((position, entry_path, metadata)))
+                    }
+                } else {
+                    Some(Either::Right((
+                        position,
+                        Self::file_node(&entry_path, Some(node_path), &metadata, filter, tracker),
+                    )))
+                }
+            })
+            .partition_map(|entry| entry);
+
+        Ok(DirListing {
+            files,
+            subdirs,
+            skipped: skipped.into_inner(),
+            entry_count: entry_count.into_inner(),
+        })
+    }
+
+    /// Adds up the size of everything below `path` that `options` let
+    /// through without building nodes, so memory use only depends on the
+    /// width of the tree
+    fn measure(path: &Path, options: &ScanOptions) -> Result<DirectorySize, ScanError> {
+        let path = &extended_length(path);
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.is_dir() {
+            return Ok(DirectorySize {
+                size: allocated_size(&metadata),
+                apparent_size: metadata.len(),
+                file_count: 1,
+                dir_count: 0,
+            });
+        }
+
+        // Fail on an unreadable root, unreadable subdirectories are skipped
+        fs::read_dir(path)?;
+        let filter = ScanFilter::new(options, path)?;
+        Self::measure_below(path, &filter, &ProgressTracker::silent())
+    }
+
+    /// Adds up the size of everything below the directory `path` that
+    /// `filter` lets through, counting each file in `tracker` and stopping
+    /// once it is cancelled
+    fn measure_below(
+        path: &Path,
+        filter: &ScanFilter,
+        tracker: &ProgressTracker,
+    ) -> Result<DirectorySize, ScanError> {
+        let mut totals = DirectorySize::default();
+        let mut level = vec![path.to_path_buf()];
+        while !level.is_empty() {
+            let listings: Vec<(DirectorySize, Vec<PathBuf>)> = level
+                .par_iter()
+                .map(|dir| Self::measure_entries(dir, filter, tracker))
+                .collect::<Result<_, _>>()?;
+            level = Vec::new();
+            for (sizes, subdirs) in listings {
+                totals.add(&sizes);
+                level.extend(subdirs);
+            }
+        }
+        Ok(totals)
+    }
+
+    /// Sizes of the files directly inside `dir`, and its subdirectories
+    fn measure_entries(
+        dir: &Path,
+        filter: &ScanFilter,
+        tracker: &ProgressTracker,
+    ) -> Result<(DirectorySize, Vec<PathBuf>), ScanError> {
+        tracker.check_cancelled()?;
+        let mut sizes = DirectorySize::default();
+        let mut subdirs = Vec::new();
+        if is_excluded(dir) {
+            return Ok((sizes, subdirs));
+        }
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok((sizes, subdirs));
+        };
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            if filter.skips(&entry_path) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let target = filter.followed_dir(&entry_path, &metadata);
+            if metadata.is_dir() || target.is_some() {
+                if !filter.crosses_device(target.as_ref().unwrap_or(&metadata)) {
+                    sizes.dir_count += 1;
+                    subdirs.push(entry_path);
+                }
+            } else {
+                let size = allocated_size(&metadata);
+                tracker.add_file(size);
+                sizes.size = sizes.size.saturating_add(size);
+                sizes.apparent_size = sizes.apparent_size.saturating_add(metadata.len());
+                sizes.file_count += 1;
+            }
+        }
+        Ok((sizes, subdirs))
+    }
+
+    fn file_node(
+        path: &Path,
+        parent: Option<&NodePath>,
+        metadata: &fs::Metadata,
+        filter: &ScanFilter,
+        tracker: &ProgressTracker,
+    ) -> FileNode {
+        let mut node = Self::leaf_node(path, parent, metadata);
+        #[cfg(target_os = "macos")]
+        if metadata.is_file() {
+            if let Some((clone_group, private_size)) = clone_info(path) {
+                if !filter.clone_groups.insert(clone_group) {
+                    node.size = private_size.min(node.size);
+                }
+            }
+        }
+        if filter.scan_archives && metadata.is_file() && archive::is_archive(path) {
+            match archive::archive_children(path, &node.path) {
+                Ok(children) => {
+                    node.children_count = children.len();
+                    node.children = children;
+                }
+                Err(e) => debug!(path = %path.display(), error = %e, "Unreadable archive"),
+            }
+        }
+
+        // Update progress tracker (only for files, as per requirement)
+        tracker.add_file(node.size);
+        node
+    }
+
+    /// Stand-in for a directory on another filesystem, which is not entered
+    fn mount_point_node(
+        path: &Path,
+        parent: Option<&NodePath>,
+        metadata: &fs::Metadata,
+    ) -> FileNode {
+        FileNode {
+            size: 0,
+            apparent_size: 0,
+            is_directory: true,
+            extension: None,
+            total_files: 0,
+            mount_point: true,
+            is_mount_point: true,
+            ..Self::leaf_node(path, parent, metadata)
+        }
+    }
+
+    /// Node for the entry at `path`, below `parent` if it has one
+    fn leaf_node(path: &Path, parent: Option<&NodePath>, metadata: &fs::Metadata) -> FileNode {
+        let actual_size = allocated_size(metadata);
+        let is_symlink = metadata.file_type().is_symlink();
+        let symlink_target = if is_symlink {
+            fs::read_link(path).ok().map(|target| encode_path(&target))
+        } else {
+            None
+        };
+
+        FileNode {
+            name: node_name(path),
+            path: NodePath::of(path, parent),
+            size: actual_size,
+            apparent_size: metadata.len(),
+            is_directory: false,
+            extension: file_extension(path),
+            children: vec![],
+            children_count: 0,
+            total_files: 1,
+            show: true,
+            truncated: false,
+            hidden_count: 0,
+            modified: modified_secs(metadata),
+            uid: owner_uid(metadata),
+            mode: permission_bits(metadata),
+            inaccessible: false,
+            skipped: 0,
+            skipped_children: 0,
+            is_symlink,
+            symlink_target,
+            in_archive: false,
+            mount_point: false,
+            is_mount_point: false,
+            percent_of_parent: 0.0,
+            size_human: String::new(),
+            size_human_binary: String::new(),
+            scanned_at: None,
+        }
+    }
+
+    /// Describes a single path without scanning or caching it. Directories
+    /// come back without children and with a `size` of 0, but with their
+    /// real `children_count`.
+    pub fn stat(path: &str) -> Result<FileNode, ScanError> {
+        let path = decode_path(path);
+        let metadata = fs::symlink_metadata(&path)?;
+        if !metadata.is_dir() {
+            return Ok(Self::leaf_node(&path, None, &metadata));
+        }
+
+        let entries = fs::read_dir(&path);
+        let node_path = NodePath::of(&path, None);
+        let mut pending = PendingDir {
+            is_mount_point: is_mount_point(&path, &metadata, None),
+            ..PendingDir::new(path, node_path, metadata, None)
+        };
+        match entries {
+            Ok(entries) => pending.entry_count = entries.count(),
+            Err(e) => pending.error = Some(e.into()),
+        }
+        let mut node = pending.into_node(false);
+        node.truncated = node.children_count > 0;
+        Ok(node)
+    }
+
+    /// Whether `path` is an entry listed from inside a scanned archive rather
+    /// than something on disk
+    pub fn in_archive(&self, path: &Path) -> bool {
+        self.with_node(&encode_path(path), |node| node.in_archive)
+            .unwrap_or(false)
+    }
+
+    /// Runs `f` on the cached node for `path`
+    pub fn with_node<R>(&self, path: &str, f: impl FnOnce(&FileNode) -> R) -> Result<R, ScanError> {
+        self.with_chain(path, |_, chain| f(chain[chain.len() - 1]))
+    }
+
+    /// Runs `f` on the cache entry containing `path` and its nodes from the
+    /// root down to the one for `path`, which comes last
+    fn with_chain<R>(
+        &self,
+        path: &str,
+        f: impl FnOnce(&CacheEntry, &[&FileNode]) -> R,
+    ) -> Result<R, ScanError> {
+        self.with_partial_chain(path, |entry, chain, complete| {
+            complete.then(|| f(entry, chain))
+        })?
+        .ok_or(ScanError::NotCached)
+    }
+
+    /// Like `with_chain`, but for paths not (yet) in the cache too: the
+    /// chain then stops at the deepest cached node on the way to `path`,
+    /// and `f` is told the chain is not `complete`
+    fn with_partial_chain<R>(
+        &self,
+        path: &str,
+        f: impl FnOnce(&CacheEntry, &[&FileNode], bool) -> R,
+    ) -> Result<R, ScanError> {
+        // Paths inside a root are looked up as given, the cached tree does
+        // not follow symlinks. Anything else may be an alias of a root.
+        let canonical;
+        let path = if self.contains_str(path) {
+            path
+        } else {
+            canonical = canonicalize(path).ok_or(ScanError::NotCached)?;
+            &canonical
+        };
+
+        // Several roots may contain the path (e.g. `/` and `/home`), the
+        // most specific one wins
+        let entry = self
+            .cache
+            .iter()
+            .filter(|entry| relative_to_root(path, entry.key()).is_some())
+            .max_by_key(|entry| entry.key().len())
+            .ok_or(ScanError::NotCached)?;
+
+        entry.last_access.store(self.tick(), Ordering::Relaxed);
+
+        let relative_path = relative_to_root(path, entry.key()).unwrap();
+        let mut chain = vec![&entry.root];
+        for part in components(relative_path) {
+            let current_node = chain[chain.len() - 1];
+            if let Some(index) = find_child(&current_node.children, part, entry.case_insensitive) {
+                chain.push(&current_node.children[index]);
+            } else {
+                return Ok(f(&entry, &chain, false));
+            }
+        }
+
+        Ok(f(&entry, &chain, true))
+    }
+
+    /// Sends all children of the cached directory at `path` to `channel` in
+    /// `sort` order, `STREAM_BATCH_SIZE` at a time
+    pub fn stream_children(
+        &self,
+        path: &str,
+        sort: SortKey,
+        channel: &Channel<ChildrenBatch>,
+    ) -> Result<(), ScanError> {
+        let ChildrenPage { children, total } = self.get_children_page(path, 0, usize::MAX, sort)?;
+        let mut children = children.into_iter().peekable();
+        loop {
+            let batch: Vec<FileNode> = children.by_ref().take(STREAM_BATCH_SIZE).collect();
+            let done = children.peek().is_none();
+            channel
+                .send(ChildrenBatch {
+                    children: batch,
+                    total,
+                    done,
+                })
+                .map_err(|e| ScanError::Io(e.to_string()))?;
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn directory_info(&self, path: &str) -> Result<DirectoryInfo, ScanError> {
+        self.with_node(path, |node| DirectoryInfo {
+            path: node.path.to_string(),
+            size: node.size,
+            file_count: node.total_files,
+            dir_count: node
+                .iter()
+                .skip(1)
+                .filter(|node| node.is_directory && !node.in_archive)
+                .count() as u64,
+            avg_file_size: node.size.checked_div(node.total_files).unwrap_or(0),
+        })
+    }
+
+    pub fn ancestors(&self, path: &str) -> Result<Vec<Breadcrumb>, ScanError> {
+        self.with_chain(path, |_, chain| {
+            chain
+                .iter()
+                .map(|node| Breadcrumb {
+                    name: node.name.clone(),
+                    path: node.path.to_string(),
+                    size: node.size,
+                })
+                .collect()
+        })
+    }
+
+    pub fn get_result_with_depth(
+        &self,
+        path: &str,
+        max_depth: u32,
+        sort: Option<SortKey>,
+        collapse: Option<CollapseOptions>,
+    ) -> Result<FileNode, ScanError> {
+        let mut node = self.with_chain(path, |entry, chain| FileNode {
+            scanned_at: Some(entry.scanned_at),
+            ..chain[chain.len() - 1].limit_depth(max_depth + 1)
+        })?;
+        // Cached children are already largest first (or in the order they
+        // were read in, see `ScanOptions::preserve_order`), only re-sort on
+        // request
+        if let Some(sort) = sort {
+            node.sort_children(sort);
+        }
+        if let Some(collapse) = collapse {
+            node.collapse_small(collapse);
+        }
+        node.percent_of_parent = 100.0;
+        node.fill_percentages();
+        node.fill_human_sizes();
+        Ok(node)
+    }
+
+    /// Like `get_result_with_depth` without folding, as a `CompactTree`
+    pub fn result_compact(
+        &self,
+        path: &str,
+        max_depth: u32,
+        sort: Option<SortKey>,
+    ) -> Result<CompactTree, ScanError> {
+        self.with_chain(path, |entry, chain| {
+            let node = chain[chain.len() - 1];
+            let mut tree = CompactTree {
+                root_path: node.path.to_string(),
+                separator: std::path::MAIN_SEPARATOR,
+                scanned_at: entry.scanned_at,
+                nodes: Vec::new(),
+            };
+            tree.push(node, None, max_depth + 1, sort);
+            tree
+        })
+    }
+
+    /// Returns `limit` of the children of the cached directory at `path`,
+    /// starting at `offset` in `sort` order
+    pub fn get_children_page(
+        &self,
+        path: &str,
+        offset: usize,
+        limit: usize,
+        sort: SortKey,
+    ) -> Result<ChildrenPage, ScanError> {
+        self.with_chain(path, |entry, chain| {
+            let node = chain[chain.len() - 1];
+            let mut children: Vec<&FileNode> = node.children.iter().collect();
+            // Cached children are already largest first, unless the scan
+            // kept them in the order they were read in
+            if sort != SortKey::SizeDesc || entry.options.preserve_order {
+                children.sort_by(|a, b| sort.compare(a, b));
+            }
+            ChildrenPage {
+                children: children
+                    .into_iter()
+                    .skip(offset)
+                    .take(limit)
+                    .map(|child| FileNode {
+                        truncated: !child.children.is_empty(),
+                        ..child.detached()
+                    })
+                    .collect(),
+                total: node.children.len(),
+            }
+        })
+    }
+
+    /// Puts several cached roots under a synthetic "All Drives" node whose
+    /// size is their sum. Paths inside another of `paths` are left out.
+    /// `max_depth` applies below each root, as in `get_result_with_depth`.
+    pub fn aggregate_roots(&self, paths: &[String], max_depth: u32) -> Result<FileNode, ScanError> {
+        let mut children = paths
+            .iter()
+            .map(|path| self.with_node(path, |node| node.limit_depth(max_depth + 1)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Component-wise ordering puts every path right before the ones
+        // inside it, which are already part of its size
+        children.sort_by_cached_key(|node| PathBuf::from(node.path.to_string()));
+        children.dedup_by(|node, kept| {
+            relative_to_root(&node.path.to_string(), &kept.path.to_string()).is_some()
+        });
+        let mut aggregate = FileNode {
+            name: "All Drives".to_string(),
+            is_directory: true,
+            children_count: children.len(),
+            children,
+            show: true,
+            ..Default::default()
+        };
+        aggregate.refresh_aggregates(0);
+        aggregate.sort_by_size();
+        Ok(aggregate)
+    }
+
+    /// Serializes the cached node at `path` with `max_depth` levels below it
+    /// and without children smaller than `min_size`, like
+    /// `get_result_with_depth` returns it but straight from the cache rather
+    /// than from a copy of the tree. With `dirs_only`, files are left out
+    /// too, but still count towards the `size` and `total_files` of their
+    /// directory.
+    pub fn result_json(
+        &self,
+        path: &str,
+        max_depth: u32,
+        min_size: u64,
+        sort: Option<SortKey>,
+        dirs_only: bool,
+    ) -> Result<String, ScanError> {
+        self.with_chain(path, |entry, chain| {
+            serde_json::to_string(&LimitedView {
+                node: chain[chain.len() - 1],
+                max_depth: max_depth + 1,
+                min_size,
+                dirs_only,
+                sort,
+                percent_of_parent: 100.0,
+                scanned_at: Some(entry.scanned_at),
+            })
+        })?
+        .map_err(|e| ScanError::Io(e.to_string()))
+    }
+
+    /// Whether `path` lies inside one of the cached scan roots
+    fn contains_str(&self, path: &str) -> bool {
+        self.cache
+            .iter()
+            .any(|entry| relative_to_root(path, entry.key()).is_some())
+    }
+
+    /// Like `contains_str`, for a path that may not be canonical yet
+    pub fn is_cached(&self, path: &str) -> bool {
+        self.contains_str(path) || canonicalize(path).is_some_and(|path| self.contains_str(&path))
+    }
+
+    /// Whether `path`, which should be free of symlinks and `..`, lies
+    /// inside one of the cached roots, resolved the same way
+    pub fn contains_path(&self, path: &Path) -> bool {
+        self.cache.iter().any(|entry| {
+            let root = decode_path(entry.key());
+            path.starts_with(fs::canonicalize(&root).unwrap_or(root))
+        })
+    }
+
+    pub fn cached_roots(&self) -> Vec<CachedRoot> {
+        self.cache
+            .iter()
+            .map(|entry| CachedRoot {
+                path: entry.key().clone(),
+                size: entry.root.size,
+                scanned_at: entry.scanned_at,
+                node_count: entry.node_count,
+            })
+            .collect()
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            root_count: 0,
+            total_nodes: 0,
+            estimated_bytes: 0,
+        };
+        for entry in self.cache.iter() {
+            stats.root_count += 1;
+            stats.total_nodes += entry.node_count;
+            stats.estimated_bytes += entry
+                .root
+                .iter()
+                .map(FileNode::estimated_bytes)
+                .sum::<usize>();
+        }
+        stats
+    }
+
+    /// Measures `path` on the scanner's pool without caching anything
+    pub fn directory_size(&self, path: &str) -> Result<DirectorySize, ScanError> {
+        let thread_pool = Arc::clone(&self.thread_pool.read());
+        thread_pool.install(|| Self::measure(&decode_path(path), &ScanOptions::default()))
+    }
+
+    /// Evicts the root cached under `path`, or every root when `path` is `None`
+    pub fn clear(&self, path: Option<&str>) {
+        match path {
+            Some(path) => {
+                let key = canonicalize(path).unwrap_or_else(|| path.to_string());
+                self.cache.remove(&key);
+            }
+            None => self.cache.clear(),
+        }
+    }
+}
+
+// New Tauri commands for on-demand loading using rayon for parallel processing
+#[tauri::command]
+pub async fn build_cache(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    options: Option<ScanOptions>,
+) -> Result<(), ScanError> {
+    // Scanning runs on the scanner's own rayon pool
+    let options = settings::scan_options(&app, options);
+    scanner.build_cache(&path, options, app)
+}
+
+#[tauri::command]
+pub async fn build_cache_many(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    paths: Vec<String>,
+    options: Option<ScanOptions>,
+) -> Result<Vec<ScanResult>, ScanError> {
+    let options = settings::scan_options(&app, options);
+    Ok(scanner.build_cache_many(paths, options, app))
+}
+
+#[tauri::command]
+pub async fn configure_scanner(
+    scanner: State<'_, DiskScanner>,
+    max_threads: Option<usize>,
+    max_cached_roots: Option<usize>,
+) -> Result<(), ScanError> {
+    scanner.set_max_cached_roots(max_cached_roots);
+    scanner.set_max_threads(max_threads)
+}
+
+/// Expands or scans `path` as needed before its results are read, see
+/// `DiskScanner::ensure_cached_tracked`
+fn ensure_cached(scanner: &DiskScanner, app: &AppHandle, path: &str) -> Result<(), ScanError> {
+    let tracker = Arc::new(ProgressTracker::new(app.clone(), None));
+    scanner.ensure_cached_tracked(path, || settings::scan_options(app, None), &tracker)
+}
+
+#[tauri::command]
+pub async fn get_result_with_depth(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+    sort: Option<SortKey>,
+    collapse: Option<CollapseOptions>,
+) -> Result<Response, ScanError> {
+    ensure_cached(&scanner, &app, &path)?;
+    let json = match collapse {
+        // Folding children adds nodes, which takes a copy of the tree
+        Some(_) => {
+            let node = scanner.get_result_with_depth(&path, max_depth, sort, collapse)?;
+            serde_json::to_string(&node).map_err(|e| ScanError::Io(e.to_string()))?
+        }
+        None => scanner.result_json(&path, max_depth, 0, sort, false)?,
+    };
+    Ok(Response::new(json))
+}
+
+#[tauri::command]
+pub async fn get_result_filtered(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+    min_size: u64,
+) -> Result<Response, ScanError> {
+    ensure_cached(&scanner, &app, &path)?;
+    Ok(Response::new(
+        scanner.result_json(&path, max_depth, min_size, None, false)?,
+    ))
+}
+
+/// Like `get_result_with_depth`, but with directories only, for a view of
+/// the folder structure without the files in it
+#[tauri::command]
+pub async fn get_result_dirs_only(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+) -> Result<Response, ScanError> {
+    ensure_cached(&scanner, &app, &path)?;
+    Ok(Response::new(
+        scanner.result_json(&path, max_depth, 0, None, true)?,
+    ))
+}
+
+/// Like `get_result_with_depth`, but as a flat list of nodes without their
+/// full paths, for smaller payloads from wide trees
+#[tauri::command]
+pub async fn get_result_compact(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+    sort: Option<SortKey>,
+) -> Result<CompactTree, ScanError> {
+    ensure_cached(&scanner, &app, &path)?;
+    scanner.result_compact(&path, max_depth, sort)
+}
+
+/// Lists a page of a directory's children, for folders too large to return
+/// in one go
+#[tauri::command]
+pub async fn get_children_page(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    offset: usize,
+    limit: usize,
+    sort: SortKey,
+) -> Result<ChildrenPage, ScanError> {
+    scanner.get_children_page(&path, offset, limit, sort)
+}
+
+/// Streams the children of a directory over `on_batch`, so folders with a
+/// huge number of entries start showing before all of them are sent
+#[tauri::command]
+pub async fn stream_children(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    sort: Option<SortKey>,
+    on_batch: Channel<ChildrenBatch>,
+) -> Result<(), ScanError> {
+    scanner.stream_children(&path, sort.unwrap_or_default(), &on_batch)
+}
+
+/// Details of a single file or directory, without a scan
+#[tauri::command]
+pub async fn stat_path(path: String) -> Result<FileNode, ScanError> {
+    DiskScanner::stat(&path)
+}
+
+/// Combines cached roots into one tree, e.g. to show usage across all drives
+#[tauri::command]
+pub async fn aggregate_roots(
+    scanner: State<'_, DiskScanner>,
+    paths: Vec<String>,
+    max_depth: u32,
+) -> Result<FileNode, ScanError> {
+    scanner.aggregate_roots(&paths, max_depth)
+}
+
+#[tauri::command]
+pub async fn list_cached_roots(
+    scanner: State<'_, DiskScanner>,
+) -> Result<Vec<CachedRoot>, ScanError> {
+    Ok(scanner.cached_roots())
+}
+
+#[tauri::command]
+pub async fn cache_stats(scanner: State<'_, DiskScanner>) -> Result<CacheStats, ScanError> {
+    Ok(scanner.cache_stats())
+}
+
+#[tauri::command]
+pub async fn clear_cache(
+    scanner: State<'_, DiskScanner>,
+    path: Option<String>,
+) -> Result<(), ScanError> {
+    scanner.clear(path.as_deref());
+    Ok(())
+}
+
+/// Sizes and counts of a cached directory, e.g. for "12,034 files averaging
+/// 48 KB"
+#[tauri::command]
+pub async fn get_directory_info(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<DirectoryInfo, ScanError> {
+    scanner.directory_info(&path)
+}
+
+/// Lists the cached nodes from the root down to `path` (included), with
+/// their aggregated sizes, for the breadcrumb bar
+#[tauri::command]
+pub async fn ancestors(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<Vec<Breadcrumb>, ScanError> {
+    scanner.ancestors(&path)
+}
+
+/// Refreshes the size of one cached node after outside changes, without
+/// rescanning its subtree
+#[tauri::command]
+pub async fn recompute_size(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<u64, ScanError> {
+    scanner.recompute_size(&path)
+}
+
+/// Total size of `path`, like `du -s`, without building or caching a tree
+#[tauri::command]
+pub async fn directory_size(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+) -> Result<DirectorySize, ScanError> {
+    scanner.directory_size(&path)
+}
+
+/// Filesystems that hold no user data and are never listed as drives
+#[cfg(target_os = "linux")]
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "securityfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+/// Mount points listed in a `/proc/self/mountinfo` table, in order, leaving
+/// out pseudo filesystems and mount points already listed
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(mountinfo: &str) -> Vec<String> {
+    let mut mount_points = Vec::new();
+    for line in mountinfo.lines() {
+        // The optional fields end with a lone "-", followed by the
+        // filesystem type. The mount point is the fifth field.
+        let Some((mount, filesystem)) = line.split_once(" - ") else {
+            continue;
+        };
+        let Some(mount_point) = mount.split(' ').nth(4) else {
+            continue;
+        };
+        let fs_type = filesystem.split(' ').next().unwrap_or_default();
+        if PSEUDO_FILESYSTEMS.contains(&fs_type) {
+            continue;
+        }
+        let mount_point = unescape_mount_point(mount_point);
+        if !mount_points.contains(&mount_point) {
+            mount_points.push(mount_point);
+        }
+    }
+    mount_points
+}
+
+/// Undoes the octal escapes (`\040` for a space and so on) the kernel uses
+/// for whitespace and backslashes in mount points
+#[cfg(target_os = "linux")]
+pub(crate) fn unescape_mount_point(escaped: &str) -> String {
+    let bytes = escaped.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| bytes[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)))
+            .and_then(|digits| {
+                digits
+                    .iter()
+                    .try_fold(0u8, |code, d| code.checked_mul(8)?.checked_add(d - b'0'))
+            });
+        match code {
+            Some(code) => {
+                unescaped.push(code);
+                i += 4;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).to_string()
+}
+
+#[tauri::command]
+pub async fn get_system_drives() -> Result<Vec<String>, ScanError> {
+    debug!("get_system_drives called");
+    system_drives()
+}
+
+/// Mount points or drive roots worth offering as scan targets
+pub fn system_drives() -> Result<Vec<String>, ScanError> {
+    // Use rayon for parallel processing
+    let result = rayon::scope(|_s| {
+        #[cfg(target_os = "macos")]
+        {
+            let drives = vec!["/".to_string()];
+            debug!(?drives, "macOS system drives");
+            Ok(drives)
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let drives = parse_mountinfo(&fs::read_to_string("/proc/self/mountinfo")?);
+            debug!(?drives, "Linux system drives from /proc/self/mountinfo");
+            Ok(drives)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let drives = vec!["C:\\".to_string()];
+            debug!(?drives, "Windows system drives");
+            Ok(drives)
+        }
+    });
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A scanner with `dir` cached using `options`, and the cache key
+    fn scanned(dir: &Path, options: ScanOptions) -> (DiskScanner, String) {
+        let scanner = DiskScanner::new();
+        let path = encode_path(dir);
+        scanner
+            .build_cache_tracked(&path, options, Arc::new(ProgressTracker::silent()))
+            .unwrap();
+        (scanner, path)
+    }
+
+    #[test]
+    fn lookups_go_to_the_root_containing_the_path() {
+        let dir = TempDir::new().unwrap();
+        for (root, file) in [("a", "in_a"), ("ab", "in_ab")] {
+            fs::create_dir(dir.path().join(root)).unwrap();
+            fs::write(dir.path().join(root).join(file), b"x").unwrap();
+        }
+        let (scanner, a) = scanned(&dir.path().join("a"), ScanOptions::default());
+        let ab = encode_path(&dir.path().join("ab"));
+        assert_eq!(
+            scanner
+                .get_result_with_depth(&ab, 1, None, None)
+                .unwrap_err(),
+            ScanError::NotCached
+        );
+
+        scanner
+            .build_cache_tracked(
+                &ab,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        for (root, file) in [(&a, "in_a"), (&ab, "in_ab")] {
+            let node = scanner.get_result_with_depth(root, 1, None, None).unwrap();
+            assert_eq!(node.path.to_string(), *root);
+            let names: Vec<&str> = node
+                .children
+                .iter()
+                .map(|child| child.name.as_ref())
+                .collect();
+            assert_eq!(names, [file]);
+        }
+    }
+
+    /// Creates a chain of `depth` nested directories called `d` in `root`.
+    /// Each one is made relative to the last, so the chain can go deeper
+    /// than the longest path the system accepts.
+    #[cfg(unix)]
+    fn nested_dirs(root: &Path, depth: usize) {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = CString::new(root.as_os_str().as_bytes()).unwrap();
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY;
+        // SAFETY: `root` is NUL-terminated
+        let mut dir = unsafe { libc::open(root.as_ptr(), flags) };
+        for _ in 0..depth {
+            assert!(dir >= 0, "{}", std::io::Error::last_os_error());
+            // SAFETY: `dir` is an open directory, the name is NUL-terminated
+            // and each descriptor is closed once the next one is open
+            unsafe {
+                assert_eq!(libc::mkdirat(dir, c"d".as_ptr(), 0o755), 0);
+                let next = libc::openat(dir, c"d".as_ptr(), flags);
+                libc::close(dir);
+                dir = next;
+            }
+        }
+        // SAFETY: `dir` is open and not used afterwards
+        unsafe { libc::close(dir) };
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn very_deep_trees_scan_without_overflowing_the_stack() {
+        let dir = TempDir::new().unwrap();
+        nested_dirs(dir.path(), 5000);
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let depth = scanner
+            .with_node(&root, |node| {
+                let mut depth = 0;
+                let mut node = node;
+                while let Some(child) = node.children.first() {
+                    node = child;
+                    depth += 1;
+                }
+                depth
+            })
+            .unwrap();
+        // Directories past the longest path the system takes are reported
+        // as inaccessible rather than scanned
+        assert!(depth > 1000, "{}", depth);
+    }
+
+    #[test]
+    fn single_threaded_scans_find_the_same_totals() {
+        let dir = TempDir::new().unwrap();
+        for sub in ["a", "a/b", "c"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+            for i in 0..20 {
+                fs::write(dir.path().join(sub).join(i.to_string()), vec![0; i * 100]).unwrap();
+            }
+        }
+        let totals = |scanner: &DiskScanner, root: &str| {
+            scanner
+                .with_node(root, |node| {
+                    (node.size, node.apparent_size, node.total_files)
+                })
+                .unwrap()
+        };
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let expected = totals(&scanner, &root);
+        assert_eq!(expected.2, 60);
+
+        let single = DiskScanner::new();
+        single.set_max_threads(Some(1)).unwrap();
+        single
+            .build_cache_tracked(
+                &root,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        assert_eq!(totals(&single, &root), expected);
+
+        let (per_scan, _) = scanned(
+            dir.path(),
+            ScanOptions {
+                max_threads: Some(1),
+                ..ScanOptions::default()
+            },
+        );
+        assert_eq!(totals(&per_scan, &root), expected);
+    }
+
+    #[test]
+    fn per_scan_thread_counts_reuse_their_pool() {
+        let scanner = DiskScanner::new();
+        let options = |max_threads| ScanOptions {
+            max_threads,
+            ..ScanOptions::default()
+        };
+        let two = scanner.pool_for(&options(Some(2))).unwrap();
+        assert_eq!(two.current_num_threads(), 2);
+        assert!(Arc::ptr_eq(
+            &two,
+            &scanner.pool_for(&options(Some(2))).unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            &two,
+            &scanner.pool_for(&options(Some(3))).unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            &scanner.pool_for(&options(None)).unwrap(),
+            &scanner.thread_pool.read()
+        ));
+    }
+
+    #[test]
+    fn cancelled_scans_cache_nothing() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), b"data").unwrap();
+        let root = encode_path(dir.path());
+        let scanner = DiskScanner::new();
+        let tracker = Arc::new(ProgressTracker::silent());
+        tracker.cancel();
+        assert_eq!(
+            scanner.build_cache_tracked(&root, ScanOptions::default(), tracker),
+            Err(ScanError::Cancelled)
+        );
+        assert_eq!(scanner.with_node(&root, |_| ()), Err(ScanError::NotCached));
+    }
+
+    #[test]
+    fn walks_below_the_scan_depth_report_progress_and_stop_when_cancelled() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), b"data").unwrap();
+        fs::write(dir.path().join("a/file"), b"data").unwrap();
+        let options = ScanOptions {
+            max_scan_depth: Some(1),
+            ..ScanOptions::default()
+        };
+        let root = encode_path(dir.path());
+
+        let tracker = Arc::new(ProgressTracker::silent());
+        DiskScanner::new()
+            .build_cache_tracked(&root, options.clone(), Arc::clone(&tracker))
+            .unwrap();
+        assert_eq!(tracker.progress().file_count, 2);
+
+        let scanner = DiskScanner::new();
+        let tracker = Arc::new(ProgressTracker::silent());
+        tracker.cancel();
+        assert_eq!(
+            scanner.build_cache_tracked(&root, options.clone(), tracker),
+            Err(ScanError::Cancelled)
+        );
+        assert_eq!(scanner.with_node(&root, |_| ()), Err(ScanError::NotCached));
+
+        // The walk below the limit checks on its own, once per directory
+        let filter = ScanFilter::new(&options, dir.path()).unwrap();
+        let tracker = ProgressTracker::silent();
+        tracker.cancel();
+        assert!(matches!(
+            DiskScanner::measure_below(&dir.path().join("a"), &filter, &tracker),
+            Err(ScanError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn depth_limits_keep_totals_and_child_counts() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), vec![0; 5000]).unwrap();
+        fs::write(dir.path().join("a/file"), vec![0; 3000]).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let limited = |max_depth| scanner.with_node(&root, |node| node.limit_depth(max_depth));
+        let full = scanner
+            .with_node(&root, |node| node.limit_depth(u32::MAX))
+            .unwrap();
+
+        let top = limited(0).unwrap();
+        assert!(top.children.is_empty());
+        assert!(top.truncated);
+        assert_eq!(top.children_count, 2);
+        assert_eq!(
+            (top.size, top.apparent_size),
+            (full.size, full.apparent_size)
+        );
+        assert_eq!(top.total_files, 2);
+
+        let one = limited(1).unwrap();
+        assert_eq!(one.children.len(), 2);
+        let child = |name: &str| one.children.iter().find(|c| c.name == name).unwrap();
+        let a = child("a");
+        let full_a = full.children.iter().find(|c| c.name == "a").unwrap();
+        assert!(a.children.is_empty() && a.truncated && !a.show);
+        assert_eq!(a.children_count, 2);
+        assert_eq!((a.size, a.apparent_size), (full_a.size, 8000));
+        assert_eq!(a.total_files, 2);
+        let empty = child("empty");
+        assert!(!empty.truncated);
+        assert_eq!(empty.children_count, 0);
+
+        let two = limited(2).unwrap();
+        let a = two.children.iter().find(|c| c.name == "a").unwrap();
+        assert!(!a.truncated);
+        assert_eq!(a.children.len(), a.children_count);
+    }
+
+    #[test]
+    fn only_files_get_an_extension() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("photo1.jpg"), b"data").unwrap();
+        fs::create_dir(dir.path().join("album.d")).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let extension = |name: &str| {
+            scanner.with_node(&format!("{}/{}", root, name), |node| node.extension.clone())
+        };
+        assert_eq!(extension("photo1.jpg"), Ok(Some("jpg".to_string())));
+        assert_eq!(extension("album.d"), Ok(None));
+    }
+
+    #[test]
+    fn least_recently_used_roots_are_evicted_past_the_limit() {
+        let dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+        let roots: Vec<String> = dirs.iter().map(|dir| encode_path(dir.path())).collect();
+        let scanner = DiskScanner::new();
+        scanner.set_max_cached_roots(Some(2));
+        let scan = |root: &str| {
+            scanner
+                .build_cache_tracked(
+                    root,
+                    ScanOptions::default(),
+                    Arc::new(ProgressTracker::silent()),
+                )
+                .unwrap()
+        };
+        scan(&roots[0]);
+        scan(&roots[1]);
+        // Reading the first root makes the second the least recently used
+        scanner
+            .get_result_with_depth(&roots[0], 1, None, None)
+            .unwrap();
+        scan(&roots[2]);
+        assert!(scanner.is_cached(&roots[0]));
+        assert!(!scanner.is_cached(&roots[1]));
+        assert!(scanner.is_cached(&roots[2]));
+
+        scanner.set_max_cached_roots(Some(1));
+        assert!(!scanner.is_cached(&roots[0]));
+        assert!(scanner.is_cached(&roots[2]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directories_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Permissions do not stop root
+        // SAFETY: geteuid cannot fail and has no preconditions
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("file"), b"data").unwrap();
+        fs::write(dir.path().join("open"), b"data").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        let (inaccessible, size) = scanner
+            .with_node(&encode_path(&locked), |node| (node.inaccessible, node.size))
+            .unwrap();
+        assert!(inaccessible);
+        assert_eq!(size, 0);
+        let (skipped, skipped_children, files) = scanner
+            .with_node(&root, |node| {
+                (node.skipped, node.skipped_children, node.total_files)
+            })
+            .unwrap();
+        assert_eq!((skipped, skipped_children, files), (1, 1, 1));
+    }
+
+    #[test]
+    fn huge_totals_saturate() {
+        assert_eq!(
+            saturating_sum([u64::MAX, 1, u64::MAX].into_iter()),
+            u64::MAX
+        );
+        assert_eq!(saturating_sum([1, 2, 3].into_iter()), 6);
+
+        let child = |size| FileNode {
+            size,
+            apparent_size: size,
+            total_files: u64::MAX / 2 + 1,
+            ..FileNode::default()
+        };
+        let mut parent = FileNode {
+            is_directory: true,
+            children: vec![child(u64::MAX - 1), child(u64::MAX / 2)],
+            ..FileNode::default()
+        };
+        parent.refresh_aggregates(u64::MAX);
+        assert_eq!(parent.size, u64::MAX);
+        assert_eq!(parent.apparent_size, u64::MAX);
+        assert_eq!(parent.total_files, u64::MAX);
+        assert_eq!(parent.skipped, u64::MAX);
+        assert_eq!(parent.skipped_children, u32::MAX);
+
+        let mut root = FileNode {
+            is_directory: true,
+            children: vec![parent],
+            ..FileNode::default()
+        };
+        let name = root.children[0].name.clone();
+        update_descendant(&mut root, &[name.as_str()], false, true, |node| {
+            node.children.push(child(u64::MAX));
+            node.refresh_aggregates(0);
+        })
+        .unwrap();
+        assert_eq!(root.size, u64::MAX);
+        assert_eq!(root.total_files, u64::MAX);
+    }
+
+    #[test]
+    fn scanned_paths_match_the_paths_on_disk() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b c")).unwrap();
+        fs::write(dir.path().join("a/b c/file"), b"data").unwrap();
+        fs::write(dir.path().join("top"), b"data").unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+
+        let paths: Vec<String> = scanner
+            .with_node(&root, |node| {
+                node.iter().map(|node| node.path.to_string()).collect()
+            })
+            .unwrap();
+        let mut expected: Vec<String> = ["", "a", "a/b c", "a/b c/file", "top"]
+            .iter()
+            .map(|relative| encode_path(&dir.path().join(relative)))
+            .collect();
+        expected[0] = root.clone();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+        for path in &paths {
+            assert!(decode_path(path).exists(), "{}", path);
+        }
+
+        // Over IPC every node still carries its full path
+        let json = scanner.result_json(&root, 3, 0, None, false).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["path"], root.as_str());
+        let a = value["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|child| child["name"] == "a")
+            .unwrap();
+        assert_eq!(a["children"][0]["path"], format!("{}/a/b c", root));
+    }
+
+    #[test]
+    fn percent_signs_are_shown_as_is_in_names() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("50% off")).unwrap();
+        fs::write(dir.path().join("50% off/100%.txt"), b"data").unwrap();
+        let (scanner, _) = scanned(dir.path(), ScanOptions::default());
+
+        let file = encode_path(&dir.path().join("50% off/100%.txt"));
+        let (name, path) = scanner
+            .with_node(&file, |node| (node.name.clone(), node.path.to_string()))
+            .unwrap();
+        assert_eq!(name, "100%.txt");
+        assert_eq!(decode_path(&path), dir.path().join("50% off/100%.txt"));
+        let chain = scanner.ancestors(&file).unwrap();
+        assert_eq!(chain[chain.len() - 2].name, "50% off");
+    }
+
+    #[test]
+    fn child_lookups_ignore_case_only_when_asked_to() {
+        let child = |name: &str| FileNode {
+            name: name.to_string(),
+            path: NodePath::from("/root".to_string()).join(name),
+            ..FileNode::default()
+        };
+        let children = vec![child("documents"), child("Notes.txt"), child("notes.txt")];
+        assert_eq!(find_child(&children, "Documents", false), None);
+        assert_eq!(find_child(&children, "Documents", true), Some(0));
+        // An exact match wins over one that only differs in case
+        assert_eq!(find_child(&children, "notes.txt", true), Some(2));
+        assert_eq!(find_child(&children, "NOTES.TXT", true), Some(1));
+    }
+
+    #[test]
+    fn case_insensitive_roots_find_paths_in_any_case() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("documents")).unwrap();
+        fs::write(dir.path().join("documents/NOTES.TXT"), b"data").unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let query = format!("{}/Documents/notes.txt", root);
+        if !scanner.cache.get(&root).unwrap().case_insensitive {
+            assert_eq!(scanner.with_node(&query, |_| ()), Err(ScanError::NotCached));
+            // Pretend the filesystem ignores case, as APFS and NTFS do
+            scanner.cache.get_mut(&root).unwrap().case_insensitive = true;
+        }
+        let name = scanner.with_node(&query, |node| node.name.clone());
+        assert_eq!(name.as_deref(), Ok("NOTES.TXT"));
+    }
+
+    #[test]
+    fn expanding_scans_directories_past_the_depth_limit() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::write(dir.path().join("a/b/c/deep"), vec![0; 5000]).unwrap();
+        fs::write(dir.path().join("a/b/file"), vec![0; 3000]).unwrap();
+        fs::write(dir.path().join("top"), vec![0; 1000]).unwrap();
+        let options = ScanOptions {
+            max_scan_depth: Some(1),
+            ..ScanOptions::default()
+        };
+        let (scanner, root) = scanned(dir.path(), options);
+        let totals = |path: &str| {
+            scanner
+                .with_node(path, |node| {
+                    (node.apparent_size, node.total_files, node.truncated)
+                })
+                .unwrap()
+        };
+        // Directories at the limit are measured, not listed
+        assert_eq!(totals(&format!("{}/a", root)), (8000, 2, true));
+        assert_eq!(totals(&root).0, 9000);
+        let deep = format!("{}/a/b/c/deep", root);
+        assert_eq!(scanner.with_node(&deep, |_| ()), Err(ScanError::NotCached));
+
+        fs::write(dir.path().join("a/b/c/new"), vec![0; 2000]).unwrap();
+        let tracker = Arc::new(ProgressTracker::silent());
+        scanner.expand_tracked(&deep, &tracker).unwrap();
+        assert_eq!(totals(&deep), (5000, 1, false));
+        assert_eq!(totals(&format!("{}/a/b", root)), (10000, 3, false));
+        assert_eq!(totals(&format!("{}/a", root)), (10000, 3, false));
+        assert_eq!(totals(&root), (11000, 4, false));
+
+        let missing = format!("{}/a/b/c/missing", root);
+        assert_eq!(
+            scanner.expand_tracked(&missing, &tracker),
+            Err(ScanError::NotCached)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directories_are_reported_when_expanded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // SAFETY: geteuid cannot fail and has no preconditions
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), b"data").unwrap();
+        let options = ScanOptions {
+            max_scan_depth: Some(1),
+            ..ScanOptions::default()
+        };
+        let (scanner, root) = scanned(dir.path(), options);
+        let locked = dir.path().join("a");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let tracker = Arc::new(ProgressTracker::silent());
+        let result = scanner.ensure_cached_tracked(
+            &format!("{}/a/b", root),
+            || panic!("a cached path is not scanned again"),
+            &tracker,
+        );
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(result, Err(ScanError::PermissionDenied));
+
+        scanner
+            .ensure_cached_tracked(&format!("{}/a/b", root), ScanOptions::default, &tracker)
+            .unwrap();
+        let file = format!("{}/a/b/file", root);
+        assert_eq!(scanner.with_node(&file, |node| node.size > 0), Ok(true));
+    }
+
+    #[test]
+    fn compact_trees_round_trip_to_the_full_tree() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::write(dir.path().join("a/b/c/deep"), vec![0; 5000]).unwrap();
+        fs::write(dir.path().join("a/one.txt"), vec![0; 3000]).unwrap();
+        fs::write(dir.path().join("a/100%.txt"), vec![0; 2000]).unwrap();
+        fs::write(dir.path().join("two"), vec![0; 1000]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+
+        for max_depth in [0, 1, 2, 5] {
+            let compact = scanner.result_compact(&root, max_depth, None).unwrap();
+            // Rebuild each node's path from its parent's, as a client would
+            let mut paths: Vec<String> = Vec::new();
+            for node in &compact.nodes {
+                let name = node.path_name.as_ref().unwrap_or(&node.name);
+                let path = match node.parent {
+                    None => compact.root_path.clone(),
+                    Some(parent) if paths[parent].ends_with(compact.separator) => {
+                        format!("{}{}", paths[parent], name)
+                    }
+                    Some(parent) => format!("{}{}{}", paths[parent], compact.separator, name),
+                };
+                paths.push(path);
+            }
+            let rebuilt: Vec<(String, u64, bool, usize, bool, bool)> = compact
+                .nodes
+                .iter()
+                .zip(paths)
+                .map(|(node, path)| {
+                    (
+                        path,
+                        node.size,
+                        node.is_directory,
+                        node.children_count,
+                        node.show,
+                        node.truncated,
+                    )
+                })
+                .collect();
+
+            let full = scanner
+                .get_result_with_depth(&root, max_depth, None, None)
+                .unwrap();
+            let expected: Vec<(String, u64, bool, usize, bool, bool)> = full
+                .iter()
+                .map(|node| {
+                    (
+                        node.path.to_string(),
+                        node.size,
+                        node.is_directory,
+                        node.children_count,
+                        node.show,
+                        node.truncated,
+                    )
+                })
+                .collect();
+            assert_eq!(rebuilt, expected, "max_depth {}", max_depth);
+        }
+    }
+
+    #[test]
+    fn recomputed_sizes_reach_the_ancestors() {
+        let dir = TempDir::new().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join("file"), b"small").unwrap();
+        fs::write(sub.join(".hidden"), vec![0; 10_000]).unwrap();
+        fs::write(dir.path().join("other"), b"other").unwrap();
+        let options = ScanOptions {
+            include_hidden: false,
+            ..ScanOptions::default()
+        };
+        let (scanner, _) = scanned(dir.path(), options.clone());
+
+        fs::write(sub.join("file"), vec![0; 100_000]).unwrap();
+        let size = scanner.recompute_size(&encode_path(&sub)).unwrap();
+
+        // The same totals a fresh scan with the same options finds
+        let (fresh, _) = scanned(dir.path(), options);
+        let totals = |scanner: &DiskScanner, path: &Path| {
+            scanner
+                .with_node(&encode_path(path), |node| {
+                    (node.size, node.apparent_size, node.total_files)
+                })
+                .unwrap()
+        };
+        assert_eq!(totals(&scanner, &sub), totals(&fresh, &sub));
+        assert_eq!(totals(&scanner, dir.path()), totals(&fresh, dir.path()));
+        assert_eq!(size, totals(&fresh, &sub).0);
+        let (_, apparent_size, files) = totals(&scanner, dir.path());
+        assert_eq!((apparent_size, files), (100_005, 2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_with_backslashes_are_found_on_unix() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a\\b"), b"data").unwrap();
+        fs::create_dir(dir.path().join("a")).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let size = scanner.with_node(&format!("{}/a\\b", root), |node| node.apparent_size);
+        assert_eq!(size, Ok(4));
+    }
+
+    #[test]
+    fn small_children_are_hidden_without_changing_the_parent_size() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("big"), vec![0; 100_000]).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(dir.path().join(name), b"small").unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let full = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+
+        let filtered: serde_json::Value =
+            serde_json::from_str(&scanner.result_json(&root, 1, 50_000, None, false).unwrap())
+                .unwrap();
+        let children = filtered["children"].as_array().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0]["name"], "big");
+        assert_eq!(filtered["hidden_count"], 3);
+        assert_eq!(filtered["size"], full.size);
+        assert_eq!(filtered["children_count"], 4);
+
+        let limited = full.limit_depth_filtered(2, 50_000);
+        assert_eq!(limited.children.len(), 1);
+        assert_eq!(limited.hidden_count, 3);
+        assert_eq!(limited.size, full.size);
+    }
+
+    fn sized(name: &str, size: u64, children_count: usize) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            size,
+            children_count,
+            ..FileNode::default()
+        }
+    }
+
+    #[test]
+    fn each_sort_key_orders_the_children() {
+        let mut root = sized("root", 0, 4);
+        root.children = vec![
+            sized("b", 300, 1),
+            sized("a", 300, 5),
+            sized("d", 100, 5),
+            sized("c", 200, 0),
+        ];
+        root.children[0].children = vec![sized("y", 1, 0), sized("x", 2, 0)];
+        let order = |sort: SortKey| {
+            let mut tree = root.clone();
+            tree.sort_children(sort);
+            let names: Vec<String> = tree.children.iter().map(|c| c.name.clone()).collect();
+            (names, tree)
+        };
+
+        let (names, tree) = order(SortKey::SizeDesc);
+        assert_eq!(names, ["a", "b", "c", "d"]);
+        // Every level is re-sorted
+        let nested = &tree.children[1].children;
+        assert_eq!(
+            (nested[0].name.as_str(), nested[1].name.as_str()),
+            ("x", "y")
+        );
+        assert_eq!(order(SortKey::SizeAsc).0, ["d", "c", "a", "b"]);
+        assert_eq!(order(SortKey::NameAsc).0, ["a", "b", "c", "d"]);
+        assert_eq!(order(SortKey::CountDesc).0, ["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn sorted_results_come_from_the_cache() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("b"), vec![0; 20_000]).unwrap();
+        fs::write(dir.path().join("a"), vec![0; 10]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let names = |sort| {
+            let node = scanner.get_result_with_depth(&root, 1, sort, None).unwrap();
+            node.children
+                .iter()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(None), ["b", "a"]);
+        assert_eq!(names(Some(SortKey::NameAsc)), ["a", "b"]);
+        assert_eq!(names(Some(SortKey::SizeAsc)), ["a", "b"]);
+    }
+
+    #[test]
+    fn directories_count_every_file_below_them() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("sub/deep")).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        for name in [
+            "one",
+            "two",
+            "sub/three",
+            "sub/four",
+            "sub/five",
+            "sub/deep/six",
+        ] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let node = scanner.get_result_with_depth(&root, 2, None, None).unwrap();
+        let child = |name: &str| node.children.iter().find(|c| c.name == name).unwrap();
+
+        assert_eq!(node.total_files, 6);
+        assert_eq!(node.children_count, 4);
+        assert_eq!(child("sub").total_files, 4);
+        assert_eq!(child("empty").total_files, 0);
+        assert_eq!(child("one").total_files, 1);
+    }
+
+    #[test]
+    fn cached_roots_are_listed_and_cleared_one_at_a_time() {
+        let dir = TempDir::new().unwrap();
+        for root in ["a", "b"] {
+            fs::create_dir(dir.path().join(root)).unwrap();
+            fs::write(dir.path().join(root).join("file"), vec![0; 5000]).unwrap();
+        }
+        let (scanner, a) = scanned(&dir.path().join("a"), ScanOptions::default());
+        let b = encode_path(&dir.path().join("b"));
+        scanner
+            .build_cache_tracked(
+                &b,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+
+        let mut roots = scanner.cached_roots();
+        roots.sort_by(|x, y| x.path.cmp(&y.path));
+        let listed: Vec<(&str, usize)> = roots
+            .iter()
+            .map(|root| (root.path.as_str(), root.node_count))
+            .collect();
+        assert_eq!(listed, [(a.as_str(), 2), (b.as_str(), 2)]);
+        assert!(roots
+            .iter()
+            .all(|root| root.size > 0 && root.scanned_at > 0));
+
+        // A trailing separator still names the same root
+        scanner.clear(Some(&format!("{}/", a)));
+        let listed: Vec<String> = scanner.cached_roots().into_iter().map(|r| r.path).collect();
+        assert_eq!(listed, std::slice::from_ref(&b));
+        assert!(!scanner.is_cached(&a));
+        assert!(scanner.is_cached(&b));
+
+        scanner.clear(None);
+        assert!(scanner.cached_roots().is_empty());
+    }
+
+    #[test]
+    fn cache_stats_count_every_cached_node() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/sub")).unwrap();
+        fs::create_dir(dir.path().join("b")).unwrap();
+        for name in ["a/one", "a/sub/two", "a/sub/three", "b/four"] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let (scanner, _) = scanned(&dir.path().join("a"), ScanOptions::default());
+        let stats = scanner.cache_stats();
+        assert_eq!((stats.root_count, stats.total_nodes), (1, 5));
+        assert!(stats.estimated_bytes >= 5 * std::mem::size_of::<FileNode>());
+
+        scanner
+            .build_cache_tracked(
+                &encode_path(&dir.path().join("b")),
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        let both = scanner.cache_stats();
+        assert_eq!((both.root_count, both.total_nodes), (2, 7));
+        assert!(both.estimated_bytes > stats.estimated_bytes);
+    }
+
+    #[test]
+    fn directory_sizes_match_a_full_scan() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::create_dir(dir.path().join("c")).unwrap();
+        for (name, size) in [("one", 10), ("a/two", 5000), ("a/b/three", 70_000)] {
+            fs::write(dir.path().join(name), vec![0; size]).unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let full = scanner.get_result_with_depth(&root, 0, None, None).unwrap();
+
+        let measured = DiskScanner::new().directory_size(&root).unwrap();
+        assert_eq!(
+            (measured.size, measured.apparent_size, measured.file_count),
+            (full.size, full.apparent_size, full.total_files)
+        );
+        assert_eq!(measured.apparent_size, 75_010);
+        assert_eq!(measured.dir_count, 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mountinfo_lists_real_filesystems_once() {
+        let mountinfo = r"22 28 0:21 / /sys rw,nosuid,nodev,noexec,relatime shared:7 - sysfs sysfs rw
+23 28 0:22 / /proc rw,nosuid,nodev,noexec,relatime shared:13 - proc proc rw
+28 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+30 28 0:25 / /run rw,nosuid,nodev shared:5 - tmpfs tmpfs rw,size=1620924k
+41 28 259:1 / /boot/efi rw,relatime shared:31 - vfat /dev/nvme0n1p1 rw
+42 28 8:17 / /media/me/My\040Disk rw,nosuid,nodev master:1 shared:2 - exfat /dev/sdb1 rw
+43 28 259:2 /home /home rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+44 43 259:2 /home /home rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+malformed line
+";
+        assert_eq!(
+            parse_mountinfo(mountinfo),
+            ["/", "/boot/efi", "/media/me/My Disk", "/home"]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mount_point_escapes_are_undone() {
+        assert_eq!(unescape_mount_point(r"/a\040b\011c\134d"), "/a b\tc\\d");
+        // Not an escape: too few or non-octal digits
+        assert_eq!(unescape_mount_point(r"/a\04"), r"/a\04");
+        assert_eq!(unescape_mount_point(r"/a\089"), r"/a\089");
+    }
+
+    #[test]
+    fn trailing_separators_and_symlinks_share_one_cache_entry() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("x");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), b"data").unwrap();
+        let with_slash = format!("{}/", encode_path(&target));
+        let (scanner, _) = scanned(Path::new(&with_slash), ScanOptions::default());
+
+        let plain = encode_path(&target);
+        let node = scanner
+            .get_result_with_depth(&plain, 1, None, None)
+            .unwrap();
+        assert_eq!(node.children.len(), 1);
+        scanner
+            .build_cache_tracked(
+                &plain,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&target, &link).unwrap();
+            scanner
+                .build_cache_tracked(
+                    &encode_path(&link),
+                    ScanOptions::default(),
+                    Arc::new(ProgressTracker::silent()),
+                )
+                .unwrap();
+        }
+        assert_eq!(scanner.cached_roots().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_flagged_with_their_target() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("file"), vec![0; 10_000]).unwrap();
+        std::os::unix::fs::symlink("file", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("dangling")).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let node = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+        let child = |name: &str| node.children.iter().find(|c| c.name == name).unwrap();
+
+        let link = child("link");
+        assert!(link.is_symlink && !link.is_directory);
+        assert_eq!(link.symlink_target.as_deref(), Some("file"));
+        // The link is counted, not the file it points to
+        assert!(link.apparent_size < 10_000);
+        let dangling = child("dangling");
+        assert!(dangling.is_symlink);
+        assert_eq!(dangling.symlink_target.as_deref(), Some("missing"));
+        let file = child("file");
+        assert!(!file.is_symlink);
+        assert_eq!(file.symlink_target, None);
+    }
+
+    #[test]
+    fn several_roots_are_scanned_together() {
+        let dir = TempDir::new().unwrap();
+        let roots: Vec<String> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                fs::create_dir(dir.path().join(name)).unwrap();
+                fs::write(dir.path().join(name).join("file"), b"data").unwrap();
+                encode_path(&dir.path().join(name))
+            })
+            .collect();
+        let missing = encode_path(&dir.path().join("missing"));
+        let mut paths = roots.clone();
+        paths.push(missing.clone());
+
+        let scanner = DiskScanner::new();
+        let results =
+            scanner.build_cache_many_tracked(paths.clone(), ScanOptions::default(), || {
+                Arc::new(ProgressTracker::silent())
+            });
+        let outcome: Vec<(&str, bool)> = results
+            .iter()
+            .map(|result| (result.path.as_str(), result.error.is_none()))
+            .collect();
+        assert_eq!(
+            outcome,
+            [
+                (roots[0].as_str(), true),
+                (roots[1].as_str(), true),
+                (missing.as_str(), false)
+            ]
+        );
+        assert!(roots.iter().all(|root| scanner.is_cached(root)));
+        assert_eq!(scanner.cached_roots().len(), 2);
+    }
+
+    #[test]
+    fn aggregates_sum_the_cached_roots() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/inner")).unwrap();
+        fs::create_dir(dir.path().join("b")).unwrap();
+        fs::write(dir.path().join("a/inner/file"), vec![0; 30_000]).unwrap();
+        fs::write(dir.path().join("b/file"), vec![0; 10_000]).unwrap();
+        let (scanner, a) = scanned(&dir.path().join("a"), ScanOptions::default());
+        let b = encode_path(&dir.path().join("b"));
+        scanner
+            .build_cache_tracked(
+                &b,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        let size = |path: &str| scanner.with_node(path, |node| node.size).unwrap();
+
+        // The inner path is part of `a` already and is not counted twice
+        let inner = encode_path(&dir.path().join("a/inner"));
+        let aggregate = scanner
+            .aggregate_roots(&[b.clone(), inner, a.clone()], 0)
+            .unwrap();
+        assert_eq!(aggregate.name, "All Drives");
+        assert_eq!(aggregate.size, size(&a) + size(&b));
+        assert_eq!(aggregate.children_count, 2);
+        let children: Vec<String> = aggregate
+            .children
+            .iter()
+            .map(|c| c.path.to_string())
+            .collect();
+        assert_eq!(children, [a, b]);
+        // Depth limits apply below each root
+        assert_eq!(aggregate.children[0].children.len(), 1);
+        assert!(aggregate.children[0].children[0].children.is_empty());
+        assert!(scanner
+            .aggregate_roots(&[encode_path(&dir.path().join("c"))], 0)
+            .is_err());
+    }
+
+    #[test]
+    fn percentages_of_the_parent_add_up_to_100() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for (name, size) in [("a", 10_000), ("b", 30_000), ("sub/c", 50_000)] {
+            fs::write(dir.path().join(name), vec![0; size]).unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let node = scanner.get_result_with_depth(&root, 2, None, None).unwrap();
+
+        assert_eq!(node.percent_of_parent, 100.0);
+        let total: f32 = node.children.iter().map(|c| c.percent_of_parent).sum();
+        assert!((total - 100.0).abs() < 0.01, "{}", total);
+        let child = |name: &str| node.children.iter().find(|c| c.name == name).unwrap();
+        let expected = child("b").size as f32 / node.size as f32 * 100.0;
+        assert!((child("b").percent_of_parent - expected).abs() < 0.01);
+        assert_eq!(child("empty").percent_of_parent, 0.0);
+        assert_eq!(child("sub").children[0].percent_of_parent, 100.0);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&scanner.result_json(&root, 1, 0, None, false).unwrap()).unwrap();
+        let total: f64 = json["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["percent_of_parent"].as_f64().unwrap())
+            .sum();
+        assert!((total - 100.0).abs() < 0.01, "{}", total);
+    }
+
+    #[test]
+    fn empty_directories_divide_by_nothing() {
+        let mut node = FileNode {
+            is_directory: true,
+            children: vec![FileNode::default()],
+            ..FileNode::default()
+        };
+        node.fill_percentages();
+        assert_eq!(node.children[0].percent_of_parent, 0.0);
+    }
+
+    fn leaf(name: &str, size: u64) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            size,
+            apparent_size: size,
+            total_files: 1,
+            ..FileNode::default()
+        }
+    }
+
+    #[test]
+    fn small_children_fold_into_one_remainder() {
+        let sizes = [
+            ("big", 9000),
+            ("mid", 600),
+            ("a", 200),
+            ("b", 150),
+            ("c", 50),
+        ];
+        let mut node = FileNode {
+            is_directory: true,
+            size: 10_000,
+            children: sizes.iter().map(|&(name, size)| leaf(name, size)).collect(),
+            ..FileNode::default()
+        };
+        node.collapse_small(CollapseOptions {
+            min_percent: Some(5.0),
+            max_children: None,
+        });
+
+        let names: Vec<&str> = node.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["big", "mid", "(3 smaller items)"]);
+        let remainder = &node.children[2];
+        assert_eq!(remainder.size, 200 + 150 + 50);
+        assert_eq!(remainder.apparent_size, 400);
+        assert_eq!((remainder.total_files, remainder.children_count), (3, 3));
+        let total: u64 = node.children.iter().map(|c| c.size).sum();
+        assert_eq!(total, node.size);
+    }
+
+    #[test]
+    fn collapsing_keeps_the_largest_children_and_single_small_ones() {
+        let mut node = FileNode {
+            is_directory: true,
+            size: 1000,
+            children: vec![leaf("a", 500), leaf("b", 300), leaf("c", 200)],
+            ..FileNode::default()
+        };
+        node.collapse_small(CollapseOptions {
+            min_percent: None,
+            max_children: Some(2),
+        });
+        // Folding one child would not make the list any shorter
+        let names: Vec<&str> = node.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+
+        node.collapse_small(CollapseOptions {
+            min_percent: None,
+            max_children: Some(1),
+        });
+        let names: Vec<&str> = node.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["a", "(2 smaller items)"]);
+        assert_eq!(node.children[1].size, 500);
+    }
+
+    #[test]
+    fn children_pages_cover_the_directory_once() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..5 {
+            fs::write(dir.path().join(format!("file{}", i)), b"x").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/inner"), b"x").unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let page = |offset, limit| {
+            let page = scanner
+                .get_children_page(&root, offset, limit, SortKey::NameAsc)
+                .unwrap();
+            let names: Vec<String> = page.children.iter().map(|c| c.name.clone()).collect();
+            (names, page)
+        };
+
+        let (first, whole) = page(0, 4);
+        assert_eq!(first, ["file0", "file1", "file2", "file3"]);
+        assert_eq!(whole.total, 6);
+        let (last, rest) = page(4, 4);
+        assert_eq!(last, ["file4", "sub"]);
+        assert_eq!(rest.total, 6);
+        // Grandchildren are left out, which is marked on their parent
+        let sub = &rest.children[1];
+        assert!(sub.children.is_empty() && sub.truncated);
+        assert!(page(6, 4).0.is_empty());
+        assert!(page(100, 4).0.is_empty());
+        assert!(page(0, 0).0.is_empty());
+    }
+
+    #[test]
+    fn only_directories_with_omitted_contents_are_truncated() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("full/inner")).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("file"), b"x").unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+
+        let node = scanner.get_result_with_depth(&root, 0, None, None).unwrap();
+        let truncated = |name: &str| {
+            let child = node.children.iter().find(|c| c.name == name).unwrap();
+            child.truncated
+        };
+        assert!(truncated("full"));
+        assert!(!truncated("empty"));
+        assert!(!truncated("file"));
+        assert!(!node.truncated);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&scanner.result_json(&root, 0, 0, None, false).unwrap()).unwrap();
+        for child in json["children"].as_array().unwrap() {
+            assert_eq!(child["truncated"], child["name"] == "full", "{}", child);
+        }
+        let deeper = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+        let full = deeper.children.iter().find(|c| c.name == "full").unwrap();
+        assert!(!full.truncated);
+        assert!(!full.children[0].truncated);
+    }
+
+    /// Records the spans opened while it is the subscriber, with their
+    /// `path` field
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _: &tracing::span::Id,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut path = String::new();
+            attrs.record(
+                &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                    if field.name() == "path" {
+                        path = format!("{:?}", value);
+                    }
+                },
+            );
+            self.0
+                .lock()
+                .push((attrs.metadata().name().to_string(), path));
+        }
+    }
+
+    #[test]
+    fn scans_run_in_a_span_naming_the_root() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("file"), b"x").unwrap();
+        let recorder = SpanRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let path = encode_path(dir.path());
+        tracing::subscriber::with_default(subscriber, || {
+            DiskScanner::new()
+                .build_cache_tracked(
+                    &path,
+                    ScanOptions::default(),
+                    Arc::new(ProgressTracker::silent()),
+                )
+                .unwrap();
+        });
+
+        let spans = recorder.0.lock();
+        assert!(
+            spans
+                .iter()
+                .any(|(name, recorded)| name == "build_cache" && recorded.contains(&path)),
+            "{:?}",
+            spans
+        );
+    }
+
+    #[test]
+    fn scan_summaries_count_the_tree() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("sub/deep")).unwrap();
+        for name in ["one", "sub/two", "sub/deep/three"] {
+            fs::write(dir.path().join(name), vec![0; 5000]).unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let summary = scanner
+            .with_node(&root, |node| {
+                ScanSummary::of(&root, node, Duration::from_millis(1500))
+            })
+            .unwrap();
+        let size = scanner.with_node(&root, |node| node.size).unwrap();
+
+        assert_eq!(summary.root, root);
+        assert_eq!(summary.duration_ms, 1500);
+        assert_eq!((summary.total_files, summary.total_dirs), (3, 3));
+        assert_eq!(summary.total_bytes, size);
+        assert_eq!(summary.skipped, 0);
+    }
+
+    #[test]
+    fn stat_describes_a_file_without_a_scan() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("notes.TXT");
+        fs::write(&file, vec![0; 1234]).unwrap();
+        let node = DiskScanner::stat(&encode_path(&file)).unwrap();
+        assert_eq!(node.name, "notes.TXT");
+        assert_eq!(node.path.to_string(), encode_path(&file));
+        assert!(!node.is_directory);
+        assert_eq!(node.apparent_size, 1234);
+        assert_eq!(node.extension.as_deref(), Some("txt"));
+        assert!(node.modified.is_some());
+        #[cfg(unix)]
+        assert!(node.mode.is_some());
+    }
+
+    #[test]
+    fn stat_counts_directory_entries_without_listing_them() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), b"x").unwrap();
+        fs::write(dir.path().join("sub/b"), b"x").unwrap();
+        let node = DiskScanner::stat(&encode_path(dir.path())).unwrap();
+        assert!(node.is_directory);
+        assert!(node.children.is_empty());
+        assert_eq!(node.children_count, 2);
+        assert!(node.truncated);
+    }
+
+    #[test]
+    fn stat_of_a_missing_path_is_not_found() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            DiskScanner::stat(&encode_path(&dir.path().join("missing"))).unwrap_err(),
+            ScanError::NotFound
+        );
+    }
+
+    #[test]
+    fn partial_scan_options_fall_back_to_the_defaults() {
+        let options: ScanOptions = serde_json::from_str(r#"{"include_hidden": false}"#).unwrap();
+        assert!(!options.include_hidden);
+        let defaults = ScanOptions::default();
+        assert_eq!(
+            serde_json::to_value(ScanOptions {
+                include_hidden: true,
+                ..options
+            })
+            .unwrap(),
+            serde_json::to_value(&defaults).unwrap()
+        );
+        let empty: ScanOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            serde_json::to_value(empty).unwrap(),
+            serde_json::to_value(defaults).unwrap()
+        );
+
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(".hidden"), b"x").unwrap();
+        fs::write(dir.path().join("shown"), b"x").unwrap();
+        let partial = serde_json::from_str(r#"{"include_hidden": false}"#).unwrap();
+        let (scanner, root) = scanned(dir.path(), partial);
+        let node = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+        let names: Vec<&str> = node.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["shown"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sparse_files_count_their_allocated_blocks() {
+        let dir = TempDir::new().unwrap();
+        let sparse = dir.path().join("sparse");
+        fs::File::create(&sparse)
+            .unwrap()
+            .set_len(16 << 20)
+            .unwrap();
+        fs::write(dir.path().join("dense"), vec![1; 64 << 10]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let node = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+        let child = |name: &str| node.children.iter().find(|c| c.name == name).unwrap();
+
+        let sparse = child("sparse");
+        assert_eq!(sparse.apparent_size, 16 << 20);
+        assert!(sparse.size < sparse.apparent_size, "{}", sparse.size);
+        let dense = child("dense");
+        assert_eq!(dense.apparent_size, 64 << 10);
+        assert!(dense.size > 0);
+        assert_eq!(node.size, sparse.size + dense.size);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directories_on_other_devices_become_mount_point_stubs() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("mounted")).unwrap();
+        fs::write(dir.path().join("mounted/inside"), vec![0; 20_000]).unwrap();
+        fs::write(dir.path().join("file"), vec![0; 5000]).unwrap();
+        let options = ScanOptions {
+            one_filesystem: true,
+            ..ScanOptions::default()
+        };
+        let filter = ScanFilter::new(&options, dir.path()).unwrap();
+        let sub = fs::metadata(dir.path().join("mounted")).unwrap();
+        assert!(!filter.crosses_device(&sub));
+        // Pretend the root is on a device of its own, so every directory
+        // below it is across a boundary
+        let filter = ScanFilter {
+            device: Some(u64::MAX),
+            ..filter
+        };
+        assert!(filter.crosses_device(&sub));
+
+        let tracker = Arc::new(ProgressTracker::silent());
+        let root_path = NodePath::of(dir.path(), None);
+        let listing =
+            DiskScanner::list_directory(dir.path(), &root_path, &filter, &tracker).unwrap();
+        assert!(listing.subdirs.is_empty());
+        let stub = &listing
+            .files
+            .iter()
+            .find(|(_, node)| node.name == "mounted")
+            .unwrap()
+            .1;
+        assert!(stub.mount_point && stub.is_directory);
+        assert_eq!((stub.size, stub.total_files), (0, 0));
+        let measured = DiskScanner::measure_below(dir.path(), &filter, &tracker).unwrap();
+        assert_eq!((measured.file_count, measured.dir_count), (1, 0));
+        assert_eq!(measured.apparent_size, 5000);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn real_mounts_are_a_device_boundary() {
+        let (Ok(root), Ok(proc)) = (fs::metadata("/"), fs::metadata("/proc")) else {
+            return;
+        };
+        if device_id(&root) == device_id(&proc) {
+            return;
+        }
+        let options = ScanOptions {
+            one_filesystem: true,
+            ..ScanOptions::default()
+        };
+        let filter = ScanFilter::new(&options, Path::new("/")).unwrap();
+        assert!(filter.crosses_device(&proc));
+        assert!(is_mount_point(Path::new("/proc"), &proc, Some(&root)));
+    }
+
+    #[test]
+    fn ancestors_run_from_the_root_to_the_node() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/file"), vec![0; 9000]).unwrap();
+        fs::write(dir.path().join("a/other"), vec![0; 3000]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let a = encode_path(&dir.path().join("a"));
+        let b = encode_path(&dir.path().join("a/b"));
+
+        let chain = scanner.ancestors(&b).unwrap();
+        let paths: Vec<&str> = chain.iter().map(|crumb| crumb.path.as_str()).collect();
+        assert_eq!(paths, [root.as_str(), a.as_str(), b.as_str()]);
+        assert_eq!(chain[1].name, "a");
+        assert_eq!(chain[2].name, "b");
+        for crumb in &chain {
+            assert_eq!(
+                Ok(crumb.size),
+                scanner.with_node(&crumb.path, |node| node.size)
+            );
+        }
+        assert!(chain[0].size >= chain[1].size && chain[1].size > chain[2].size);
+        assert_eq!(scanner.ancestors(&root).unwrap().len(), 1);
+        assert!(scanner
+            .ancestors(&encode_path(&dir.path().join("missing")))
+            .is_err());
+    }
+
+    #[test]
+    fn directory_info_counts_and_averages_the_subtree() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("a/b/one"), vec![0; 8192]).unwrap();
+        fs::write(dir.path().join("a/two"), vec![0; 4096]).unwrap();
+        fs::write(dir.path().join("three"), vec![0; 4096]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+
+        let info = scanner.directory_info(&root).unwrap();
+        assert_eq!((info.file_count, info.dir_count), (3, 3));
+        assert_eq!(
+            info.size,
+            scanner.with_node(&root, |node| node.size).unwrap()
+        );
+        assert_eq!(info.avg_file_size, info.size / 3);
+
+        let a = scanner
+            .directory_info(&encode_path(&dir.path().join("a")))
+            .unwrap();
+        assert_eq!((a.file_count, a.dir_count), (2, 1));
+        assert_eq!(a.avg_file_size, a.size / 2);
+
+        let empty = scanner
+            .directory_info(&encode_path(&dir.path().join("empty")))
+            .unwrap();
+        assert_eq!((empty.file_count, empty.dir_count), (0, 0));
+        assert_eq!(empty.avg_file_size, 0);
+    }
+
+    #[test]
+    fn returned_trees_carry_the_scan_time() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/file"), b"data").unwrap();
+        let now = || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        let before = now();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let after = now();
+
+        let node = scanner.get_result_with_depth(&root, 1, None, None).unwrap();
+        let scanned_at = node.scanned_at.unwrap();
+        assert!((before..=after).contains(&scanned_at));
+        // Only the root of the returned tree says when it was scanned
+        assert!(node.children.iter().all(|child| child.scanned_at.is_none()));
+        let sub = encode_path(&dir.path().join("sub"));
+        let nested = scanner.get_result_with_depth(&sub, 0, None, None).unwrap();
+        assert_eq!(nested.scanned_at, Some(scanned_at));
+        assert_eq!(scanner.cached_roots()[0].scanned_at, scanned_at);
+    }
+
+    #[test]
+    fn nodes_past_the_scan_depth_are_childless_but_fully_sized() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::create_dir(dir.path().join("d")).unwrap();
+        fs::write(dir.path().join("a/b/c/deep"), vec![0; 40_000]).unwrap();
+        fs::write(dir.path().join("a/b/mid"), vec![0; 7000]).unwrap();
+        fs::write(dir.path().join("a/top"), vec![0; 1000]).unwrap();
+        fs::write(dir.path().join("d/file"), vec![0; 3000]).unwrap();
+        let (full, root) = scanned(dir.path(), ScanOptions::default());
+        let limited = |depth| {
+            scanned(
+                dir.path(),
+                ScanOptions {
+                    max_scan_depth: Some(depth),
+                    ..ScanOptions::default()
+                },
+            )
+            .0
+        };
+
+        for depth in 0..3 {
+            let scanner = limited(depth);
+            let node = scanner.with_node(&root, |node| node.clone()).unwrap();
+            let mut cut_off = 0;
+            for node in node.iter() {
+                let expected = full
+                    .with_node(&node.path.to_string(), |node| {
+                        (node.size, node.apparent_size, node.total_files)
+                    })
+                    .unwrap();
+                assert_eq!((node.size, node.apparent_size, node.total_files), expected);
+                if node.truncated {
+                    assert!(node.is_directory && node.children.is_empty());
+                    cut_off += 1;
+                }
+            }
+            // The root itself at 0, `a` and `d` at 1, `b` at 2
+            assert_eq!(cut_off, [1, 2, 1][depth as usize]);
+        }
+    }
+
+    #[test]
+    fn nested_roots_are_not_counted_twice() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("home/me")).unwrap();
+        fs::write(dir.path().join("home/me/file"), vec![0; 30_000]).unwrap();
+        fs::write(dir.path().join("home/other"), vec![0; 10_000]).unwrap();
+        let home = encode_path(&dir.path().join("home"));
+        let me = encode_path(&dir.path().join("home/me"));
+        let build = |scanner: &DiskScanner, path: &str| {
+            scanner
+                .build_cache_tracked(
+                    path,
+                    ScanOptions::default(),
+                    Arc::new(ProgressTracker::silent()),
+                )
+                .unwrap();
+        };
+        let roots = |scanner: &DiskScanner| -> Vec<String> {
+            scanner
+                .cached_roots()
+                .into_iter()
+                .map(|root| root.path)
+                .collect()
+        };
+
+        // Inner root first, then the one containing it, and the other way
+        // around: either way only the outer root stays cached
+        for order in [[&me, &home], [&home, &me]] {
+            let scanner = DiskScanner::new();
+            for path in order {
+                build(&scanner, path);
+            }
+            assert_eq!(roots(&scanner), std::slice::from_ref(&home));
+            let size = |path: &str| scanner.with_node(path, |node| node.size).unwrap();
+            let aggregate = scanner
+                .aggregate_roots(&[me.clone(), home.clone()], 0)
+                .unwrap();
+            assert_eq!(aggregate.size, size(&home));
+            assert_eq!(aggregate.total_files, 2);
+            assert!(size(&me) < size(&home));
+        }
+    }
+
+    #[test]
+    fn children_stream_in_sorted_batches() {
+        let dir = TempDir::new().unwrap();
+        let count = 2 * STREAM_BATCH_SIZE + 7;
+        for i in 0..count {
+            fs::write(dir.path().join(format!("{:04}", i)), b"x").unwrap();
+        }
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let stream = |path: &str| {
+            let batches = Arc::new(Mutex::new(Vec::new()));
+            let received = Arc::clone(&batches);
+            let channel = Channel::new(move |body| {
+                if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+                    received
+                        .lock()
+                        .push(serde_json::from_str::<serde_json::Value>(&json).unwrap());
+                }
+                Ok(())
+            });
+            scanner
+                .stream_children(path, SortKey::NameAsc, &channel)
+                .unwrap();
+            drop(channel);
+            Arc::try_unwrap(batches).unwrap().into_inner()
+        };
+
+        let batches = stream(&root);
+        let sizes: Vec<usize> = batches
+            .iter()
+            .map(|batch| batch["children"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, [STREAM_BATCH_SIZE, STREAM_BATCH_SIZE, 8]);
+        let done: Vec<bool> = batches
+            .iter()
+            .map(|batch| batch["done"].as_bool().unwrap())
+            .collect();
+        assert_eq!(done, [false, false, true]);
+        assert!(batches.iter().all(|batch| batch["total"] == count + 1));
+        let names: Vec<&str> = batches
+            .iter()
+            .flat_map(|batch| batch["children"].as_array().unwrap())
+            .map(|child| child["name"].as_str().unwrap())
+            .collect();
+        assert!(names.is_sorted());
+        assert_eq!(names.last(), Some(&"empty"));
+
+        // An empty directory still gets its end-of-stream marker
+        let empty = stream(&encode_path(&dir.path().join("empty")));
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0]["done"], true);
+        assert_eq!(empty[0]["children"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn borrowed_views_serialize_like_cloned_trees() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..50 {
+            let sub = dir.path().join(format!("dir{}", i));
+            fs::create_dir_all(sub.join("inner")).unwrap();
+            fs::write(sub.join("file"), vec![0; 100 * i]).unwrap();
+            fs::write(sub.join("inner/deep"), vec![0; 5000]).unwrap();
+            fs::write(dir.path().join(format!("file{}", i)), vec![0; 50 * i]).unwrap();
+        }
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+
+        for sort in [None, Some(SortKey::NameAsc), Some(SortKey::SizeAsc)] {
+            for max_depth in 0..3 {
+                let cloned = scanner
+                    .get_result_with_depth(&root, max_depth, sort, None)
+                    .unwrap();
+                let borrowed = scanner
+                    .result_json(&root, max_depth, 0, sort, false)
+                    .unwrap();
+                let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+                assert_eq!(
+                    parse(&borrowed),
+                    parse(&serde_json::to_string(&cloned).unwrap()),
+                    "depth {} sort {:?}",
+                    max_depth,
+                    sort
+                );
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn children_count_includes_entries_that_cannot_be_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // SAFETY: geteuid cannot fail and has no preconditions
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let listable = dir.path().join("listable");
+        fs::create_dir(&listable).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(listable.join(name), b"data").unwrap();
+        }
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(dir.path().join("file"), b"data").unwrap();
+        // Names can be listed but not looked up without search permission
+        fs::set_permissions(&listable, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        fs::set_permissions(&listable, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        let listable = encode_path(&listable);
+        let counts = |path: &str, max_depth| {
+            scanner
+                .with_node(path, |node| {
+                    let node = node.limit_depth(max_depth);
+                    (node.children_count, node.children.len())
+                })
+                .unwrap()
+        };
+        assert_eq!(counts(&listable, u32::MAX), (3, 0));
+        assert_eq!(counts(&listable, 0), (3, 0));
+        assert_eq!(counts(&root, u32::MAX), (3, 3));
+        assert_eq!(counts(&root, 0), (3, 0));
+    }
+
+    #[test]
+    fn files_changing_size_between_scans_keep_totals_consistent() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("logs")).unwrap();
+        let log = dir.path().join("logs/app.log");
+        fs::write(&log, vec![0; 10_000]).unwrap();
+        fs::write(dir.path().join("other"), vec![0; 5000]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let logs = encode_path(&dir.path().join("logs"));
+        let log = encode_path(&log);
+        let tracker = Arc::new(ProgressTracker::silent());
+        // Every directory's totals are the sum of its children's
+        let check = || {
+            scanner
+                .with_node(&root, |root| {
+                    for node in root.iter().filter(|node| node.is_directory) {
+                        let sum =
+                            |f: fn(&FileNode) -> u64| saturating_sum(node.children.iter().map(f));
+                        assert!(node.size >= sum(|c| c.size), "{}", node.path);
+                        assert_eq!(node.apparent_size, sum(|c| c.apparent_size));
+                        assert_eq!(node.total_files, sum(|c| c.total_files));
+                    }
+                    root.apparent_size
+                })
+                .unwrap()
+        };
+        assert_eq!(check(), 15_000);
+
+        // The log grows after the scan, then shrinks well below its
+        // original size
+        fs::write(decode_path(&log), vec![0; 60_000]).unwrap();
+        scanner.rescan_subtree_tracked(&log, &tracker).unwrap();
+        assert_eq!(check(), 65_000);
+        fs::write(decode_path(&log), b"x").unwrap();
+        // Re-measuring the directory leaves its children as they were, but
+        // the ancestors take the smaller size without going negative
+        scanner.recompute_size(&logs).unwrap();
+        let apparent_size = |path: &str| scanner.with_node(path, |node| node.apparent_size);
+        assert_eq!(apparent_size(&log), Ok(60_000));
+        assert_eq!(apparent_size(&logs), Ok(1));
+        assert_eq!(apparent_size(&root), Ok(5001));
+        scanner.rescan_subtree_tracked(&log, &tracker).unwrap();
+        assert_eq!(check(), 5001);
+
+        fs::remove_file(decode_path(&log)).unwrap();
+        scanner.rescan_subtree_tracked(&log, &tracker).unwrap();
+        assert_eq!(check(), 5000);
+    }
+
+    #[test]
+    fn dirs_only_results_leave_out_files_but_not_their_size() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("a/b/deep"), vec![0; 9000]).unwrap();
+        fs::write(dir.path().join("a/file"), vec![0; 3000]).unwrap();
+        fs::write(dir.path().join("top"), vec![0; 1000]).unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let full = serde_json::to_value(
+            scanner
+                .get_result_with_depth(&root, u32::MAX - 1, None, None)
+                .unwrap(),
+        )
+        .unwrap();
+        let dirs: serde_json::Value =
+            serde_json::from_str(&scanner.result_json(&root, 5, 0, None, true).unwrap()).unwrap();
+
+        fn walk<'a>(node: &'a serde_json::Value, found: &mut Vec<&'a serde_json::Value>) {
+            found.push(node);
+            for child in node["children"].as_array().unwrap() {
+                walk(child, found);
+            }
+        }
+        let (mut all, mut kept) = (Vec::new(), Vec::new());
+        walk(&full, &mut all);
+        walk(&dirs, &mut kept);
+        assert!(kept.iter().all(|node| node["is_directory"] == true));
+        assert_eq!(kept.len(), 4);
+        for node in kept {
+            let original = all
+                .iter()
+                .find(|original| original["path"] == node["path"])
+                .unwrap();
+            for field in ["size", "apparent_size", "total_files", "children_count"] {
+                assert_eq!(
+                    node[field], original[field],
+                    "{} of {}",
+                    field, node["path"]
+                );
+            }
+        }
+        assert_eq!(dirs["total_files"], 3);
+        assert_eq!(dirs["apparent_size"], 13_000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skipped_children_are_counted_per_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // SAFETY: geteuid cannot fail and has no preconditions
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let locked = dir.path().join("a/locked");
+        let listable = dir.path().join("a/listable");
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(&listable).unwrap();
+        for name in ["x", "y"] {
+            fs::write(listable.join(name), b"data").unwrap();
+        }
+        fs::write(dir.path().join("a/file"), b"data").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        fs::set_permissions(&listable, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&listable, fs::Permissions::from_mode(0o755)).unwrap();
+        let skipped_children = |path: &Path| {
+            scanner
+                .with_node(&encode_path(path), |node| node.skipped_children)
+                .unwrap()
+        };
+        // `locked` itself could not be listed, the names in `listable` could
+        // but not looked up
+        assert_eq!(skipped_children(&dir.path().join("a")), 1);
+        assert_eq!(skipped_children(&listable), 2);
+        assert_eq!(skipped_children(dir.path()), 0);
+
+        // Kept in depth-limited responses
+        let json: serde_json::Value =
+            serde_json::from_str(&scanner.result_json(&root, 0, 0, None, false).unwrap()).unwrap();
+        assert_eq!(json["children"][0]["skipped_children"], 1);
+    }
+
+    #[test]
+    fn preserved_order_follows_the_directory_listing() {
+        let dir = TempDir::new().unwrap();
+        for (i, name) in ["m", "c", "x", "a", "q", "f"].iter().enumerate() {
+            if i % 2 == 0 {
+                fs::create_dir(dir.path().join(name)).unwrap();
+                fs::write(dir.path().join(name).join("inner"), vec![0; 1000 * i]).unwrap();
+            } else {
+                fs::write(dir.path().join(name), vec![0; 7000 * i]).unwrap();
+            }
+        }
+        let listed: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        let (sorted, root) = scanned(dir.path(), ScanOptions::default());
+        let (kept, _) = scanned(
+            dir.path(),
+            ScanOptions {
+                preserve_order: true,
+                ..ScanOptions::default()
+            },
+        );
+        let children = |scanner: &DiskScanner, sort| {
+            scanner
+                .get_result_with_depth(&root, 0, sort, None)
+                .unwrap()
+                .children
+                .into_iter()
+                .map(|child| (child.name, child.size, child.apparent_size))
+                .collect::<Vec<_>>()
+        };
+
+        let in_order = children(&kept, None);
+        let names: Vec<&str> = in_order.iter().map(|(name, ..)| name.as_str()).collect();
+        assert_eq!(names, listed);
+        // Same nodes and sizes, only the order differs
+        let mut by_size = in_order.clone();
+        by_size.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        assert_eq!(by_size, children(&sorted, Some(SortKey::SizeDesc)));
+        assert_eq!(
+            children(&kept, Some(SortKey::SizeDesc)),
+            children(&sorted, Some(SortKey::SizeDesc))
+        );
+        assert_eq!(
+            kept.with_node(&root, |node| node.size),
+            sorted.with_node(&root, |node| node.size)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directories_on_their_own_device_are_flagged_as_mount_points() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let (scanner, _) = scanned(dir.path(), ScanOptions::default());
+        let sub = encode_path(&dir.path().join("sub"));
+        assert_eq!(
+            scanner.with_node(&sub, |node| node.is_mount_point),
+            Ok(false)
+        );
+        assert!(is_mount_point(
+            Path::new("/"),
+            &fs::metadata("/").unwrap(),
+            None
+        ));
+
+        // Wherever something is mounted in /dev, like /dev/pts, the scan
+        // flags exactly the directories whose device differs from /dev
+        let Ok(dev) = fs::metadata("/dev") else {
+            return;
+        };
+        let (scanner, dev_path) = scanned(
+            Path::new("/dev"),
+            ScanOptions {
+                max_scan_depth: Some(1),
+                ..ScanOptions::default()
+            },
+        );
+        let flagged = scanner
+            .with_node(&dev_path, |node| {
+                node.children
+                    .iter()
+                    .filter(|child| child.is_directory && !child.is_symlink)
+                    .map(|child| {
+                        let metadata = fs::symlink_metadata(decode_path(&child.path.to_string()));
+                        let mounted = metadata.is_ok_and(|m| device_id(&m) != device_id(&dev));
+                        (child.is_mount_point, mounted)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap();
+        for (flag, mounted) in flagged {
+            assert_eq!(flag, mounted);
+        }
+    }
+
+    #[test]
+    fn drift_counts_changes_since_the_scan() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/old")).unwrap();
+        for name in ["a/old/1", "a/old/2", "a/keep", "a/grows", "gone"] {
+            fs::write(dir.path().join(name), b"data").unwrap();
+        }
+        fs::write(dir.path().join("replaced"), b"data").unwrap();
+        let (scanner, root) = scanned(dir.path(), ScanOptions::default());
+        let drift = |path: &str| {
+            let drift = scanner.drift(path).unwrap();
+            (drift.added, drift.removed, drift.size_changed)
+        };
+        assert_eq!(drift(&root), (0, 0, 0));
+
+        fs::remove_dir_all(dir.path().join("a/old")).unwrap();
+        fs::remove_file(dir.path().join("gone")).unwrap();
+        fs::write(dir.path().join("a/grows"), b"more data").unwrap();
+        fs::create_dir_all(dir.path().join("new/deep")).unwrap();
+        fs::write(dir.path().join("new/deep/file"), b"data").unwrap();
+        fs::write(dir.path().join("a/added"), b"data").unwrap();
+        fs::remove_file(dir.path().join("replaced")).unwrap();
+        fs::create_dir(dir.path().join("replaced")).unwrap();
+
+        // `old` and `gone` removed, `new` and `added` added (a directory
+        // counts once), `grows` changed, and `replaced` counts both ways
+        assert_eq!(drift(&root), (3, 3, 1));
+        assert_eq!(drift(&encode_path(&dir.path().join("a"))), (1, 1, 1));
+        // Nothing was rescanned
+        assert_eq!(scanner.with_node(&root, |node| node.total_files), Ok(6));
+    }
+
+    #[test]
+    fn scanners_keep_separate_caches() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("file"), b"data").unwrap();
+        let (first, root) = scanned(dir.path(), ScanOptions::default());
+        let second = DiskScanner::new();
+        assert!(first.is_cached(&root));
+        assert!(!second.is_cached(&root));
+        assert_eq!(
+            second.get_result_with_depth(&root, 0, None, None).err(),
+            Some(ScanError::NotCached)
+        );
+
+        // Clearing one leaves the other alone
+        second
+            .build_cache_tracked(
+                &root,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        first.clear(None);
+        assert!(!first.is_cached(&root));
+        assert_eq!(second.with_node(&root, |node| node.total_files), Ok(1));
+    }
 }
@@ -0,0 +1,433 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::ScanError;
+use crate::paths::decode_path;
+
+/// Kinds of storage that are slow to scan. Local fixed disks have none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DriveKind {
+    Network,
+    Optical,
+    Removable,
+}
+
+/// Returned by `precheck_scan` when scanning a path is likely to be slow
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanWarning {
+    pub kind: DriveKind,
+    pub message: String,
+}
+
+impl ScanWarning {
+    fn new(kind: DriveKind) -> Self {
+        let message = match kind {
+            DriveKind::Network => {
+                "This folder is on a network share. Scanning it reads every directory over \
+                 the network, which can take a long time and load the server."
+            }
+            DriveKind::Optical => {
+                "This folder is on an optical disc, scanning it may take a while."
+            }
+            DriveKind::Removable => {
+                "This folder is on a removable drive, scanning it may be slower than usual."
+            }
+        };
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Classifies the drive `path` is on. `None` for local fixed disks and
+/// whenever the platform cannot tell.
+pub fn drive_kind(path: &Path) -> Option<DriveKind> {
+    platform::drive_kind(path)
+}
+
+/// Whether `path` is the root of a volume, be it a drive or a folder that
+/// one is mounted on
+#[cfg(windows)]
+pub fn is_volume_root(path: &Path) -> bool {
+    platform::is_volume_root(path)
+}
+
+/// Whether reading files on the drive `path` is on updates their access
+/// time, i.e. it is not mounted `noatime`
+pub fn tracks_access_time(path: &Path) -> bool {
+    platform::tracks_access_time(path)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{CStr, CString};
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use super::DriveKind;
+
+    const OPTICAL_FILESYSTEMS: &[&str] = &["cd9660", "cddafs", "udf"];
+
+    /// `MNT_REMOVABLE` from `<sys/mount.h>`
+    const MNT_REMOVABLE: u32 = 0x0000_0200;
+
+    fn statfs(path: &Path) -> Option<libc::statfs> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stats = MaybeUninit::<libc::statfs>::zeroed();
+        // SAFETY: `path` is NUL-terminated and `stats` is large enough
+        if unsafe { libc::statfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: statfs succeeded, so it filled in `stats`
+        Some(unsafe { stats.assume_init() })
+    }
+
+    pub fn tracks_access_time(path: &Path) -> bool {
+        statfs(path).is_some_and(|stats| stats.f_flags & libc::MNT_NOATIME as u32 == 0)
+    }
+
+    pub fn drive_kind(path: &Path) -> Option<DriveKind> {
+        let stats = statfs(path)?;
+        // SAFETY: the kernel NUL-terminates the type name
+        let fs_type = unsafe { CStr::from_ptr(stats.f_fstypename.as_ptr()) }.to_string_lossy();
+        if OPTICAL_FILESYSTEMS.contains(&&*fs_type) {
+            Some(DriveKind::Optical)
+        } else if stats.f_flags & libc::MNT_LOCAL as u32 == 0 {
+            Some(DriveKind::Network)
+        } else if stats.f_flags & MNT_REMOVABLE != 0 {
+            Some(DriveKind::Removable)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::Storage::FileSystem::{GetDriveTypeW, GetVolumePathNameW};
+    use windows_sys::Win32::System::Registry::{
+        RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD,
+    };
+    use windows_sys::Win32::System::WindowsProgramming::{
+        DRIVE_CDROM, DRIVE_REMOTE, DRIVE_REMOVABLE,
+    };
+
+    use super::DriveKind;
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// NTFS keeps last access times unless `NtfsDisableLastAccessUpdate`
+    /// turns them off system-wide. When the setting cannot be read, access
+    /// times are not trusted.
+    pub fn tracks_access_time(_path: &Path) -> bool {
+        let key = wide(r"SYSTEM\CurrentControlSet\Control\FileSystem");
+        let value = wide("NtfsDisableLastAccessUpdate");
+        let mut setting = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        // SAFETY: both names are NUL-terminated and `setting` holds the
+        // `size` bytes of a DWORD, the type is not asked for
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_DWORD,
+                std::ptr::null_mut(),
+                &mut setting as *mut u32 as *mut c_void,
+                &mut size,
+            )
+        };
+        status == ERROR_SUCCESS && last_access_enabled(setting)
+    }
+
+    /// Reads an `NtfsDisableLastAccessUpdate` value. Its lowest bit turns
+    /// updates off, the highest only marks the value as managed by Windows
+    /// rather than the user.
+    fn last_access_enabled(setting: u32) -> bool {
+        setting & 1 == 0
+    }
+
+    /// Root of the volume `path` is on, NUL-terminated
+    fn volume_root(path: &Path) -> Option<Vec<u16>> {
+        let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        // The volume root is never longer than the path itself
+        let mut root = vec![0u16; path.len().max(261)];
+        // SAFETY: `path` is NUL-terminated and `root` holds `root.len()` units
+        let found =
+            unsafe { GetVolumePathNameW(path.as_ptr(), root.as_mut_ptr(), root.len() as u32) };
+        (found != 0).then_some(root)
+    }
+
+    /// Whether `path` is where a volume is mounted, be it a drive root or a
+    /// folder mount point
+    pub fn is_volume_root(path: &Path) -> bool {
+        let Some(root) = volume_root(path) else {
+            return false;
+        };
+        let end = root
+            .iter()
+            .position(|&unit| unit == 0)
+            .unwrap_or(root.len());
+        let root = String::from_utf16_lossy(&root[..end]);
+        let path = path.to_string_lossy();
+        root.trim_end_matches('\\')
+            .eq_ignore_ascii_case(path.trim_end_matches('\\'))
+    }
+
+    pub fn drive_kind(path: &Path) -> Option<DriveKind> {
+        let root = volume_root(path)?;
+        // SAFETY: GetVolumePathNameW NUL-terminated `root`
+        match unsafe { GetDriveTypeW(root.as_ptr()) } {
+            DRIVE_REMOTE => Some(DriveKind::Network),
+            DRIVE_CDROM => Some(DriveKind::Optical),
+            DRIVE_REMOVABLE => Some(DriveKind::Removable),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn last_access_setting_is_its_lowest_bit() {
+            assert!(last_access_enabled(0));
+            assert!(!last_access_enabled(1));
+            assert!(last_access_enabled(0x8000_0000));
+            assert!(!last_access_enabled(0x8000_0001));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::DriveKind;
+    use crate::disk_scanner::unescape_mount_point;
+
+    const NETWORK_FILESYSTEMS: &[&str] = &[
+        "9p",
+        "afs",
+        "ceph",
+        "cifs",
+        "davfs",
+        "fuse.davfs2",
+        "fuse.rclone",
+        "fuse.sshfs",
+        "glusterfs",
+        "ncpfs",
+        "nfs",
+        "nfs4",
+        "smb3",
+        "smbfs",
+    ];
+
+    const OPTICAL_FILESYSTEMS: &[&str] = &["iso9660", "udf"];
+
+    pub fn drive_kind(path: &Path) -> Option<DriveKind> {
+        let path = fs::canonicalize(path).ok()?;
+        let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+        kind_of(&mountinfo, &path)
+    }
+
+    /// Kind of the mount `path` is on, as listed in `mountinfo`
+    fn kind_of(mountinfo: &str, path: &Path) -> Option<DriveKind> {
+        let (fs_type, source, _) = mount_of(mountinfo, path)?;
+        if NETWORK_FILESYSTEMS.contains(&fs_type) {
+            Some(DriveKind::Network)
+        } else if OPTICAL_FILESYSTEMS.contains(&fs_type) {
+            Some(DriveKind::Optical)
+        } else if is_removable(source) {
+            Some(DriveKind::Removable)
+        } else {
+            None
+        }
+    }
+
+    /// Relatime (the default) still updates the access time of a file
+    /// that was not read since it was last modified
+    pub fn tracks_access_time(path: &Path) -> bool {
+        let Ok(path) = fs::canonicalize(path) else {
+            return false;
+        };
+        fs::read_to_string("/proc/self/mountinfo")
+            .is_ok_and(|mountinfo| records_access_time(&mountinfo, &path))
+    }
+
+    /// Whether the mount `path` is on, as listed in `mountinfo`, is not
+    /// mounted `noatime`
+    fn records_access_time(mountinfo: &str, path: &Path) -> bool {
+        mount_of(mountinfo, path)
+            .is_some_and(|(_, _, options)| !options.split(',').any(|o| o == "noatime"))
+    }
+
+    /// Filesystem type, source and mount options of the mount `path` is on.
+    /// Of several mounts on the same mount point, the last one hides the
+    /// others.
+    fn mount_of<'a>(mountinfo: &'a str, path: &Path) -> Option<(&'a str, &'a str, &'a str)> {
+        mountinfo
+            .lines()
+            .filter_map(|line| {
+                let (mount, filesystem) = line.split_once(" - ")?;
+                let mut mount_fields = mount.split(' ').skip(4);
+                let mount_point = PathBuf::from(unescape_mount_point(mount_fields.next()?));
+                let options = mount_fields.next().unwrap_or_default();
+                let mut fields = filesystem.split(' ');
+                let fs_type = fields.next()?;
+                let source = fields.next().unwrap_or_default();
+                Some((mount_point, (fs_type, source, options)))
+            })
+            .filter(|(mount_point, _)| path.starts_with(mount_point))
+            .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+            .map(|(_, mount)| mount)
+    }
+
+    /// Whether the block device `source` is flagged removable by the kernel.
+    /// Partitions carry the flag on the disk they belong to.
+    fn is_removable(source: &str) -> bool {
+        let Some(device) = fs::canonicalize(source)
+            .ok()
+            .filter(|device| device.starts_with("/dev"))
+            .and_then(|device| device.file_name().map(|name| name.to_owned()))
+        else {
+            return false;
+        };
+        let block = Path::new("/sys/class/block").join(device);
+        [block.join("removable"), block.join("../removable")]
+            .iter()
+            .any(|flag| fs::read_to_string(flag).is_ok_and(|flag| flag.trim() == "1"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::drives::ScanWarning;
+
+        const MOUNTINFO: &str = r"28 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+51 28 0:50 / /mnt/nas rw,relatime shared:40 - nfs4 nas:/export/photos rw,vers=4.2
+52 28 0:51 / /mnt/nas\040share rw,noatime shared:41 - cifs //nas/share rw
+53 28 11:0 / /media/me/DISC ro,nosuid shared:42 - iso9660 /dev/sr0 ro
+";
+
+        #[test]
+        fn network_mounts_are_classified_as_network() {
+            for path in ["/mnt/nas", "/mnt/nas/2024/beach.jpg", "/mnt/nas share/x"] {
+                assert_eq!(
+                    kind_of(MOUNTINFO, Path::new(path)),
+                    Some(DriveKind::Network),
+                    "{}",
+                    path
+                );
+            }
+            let warning = ScanWarning::new(DriveKind::Network);
+            assert_eq!(warning.kind, DriveKind::Network);
+            assert!(warning.message.contains("network share"));
+        }
+
+        #[test]
+        fn local_and_optical_mounts_are_told_apart() {
+            // A sibling whose name starts the same is not inside the mount
+            assert_eq!(kind_of(MOUNTINFO, Path::new("/mnt/nasty")), None);
+            assert_eq!(kind_of(MOUNTINFO, Path::new("/home/me")), None);
+            assert_eq!(
+                kind_of(MOUNTINFO, Path::new("/media/me/DISC/video")),
+                Some(DriveKind::Optical)
+            );
+        }
+
+        #[test]
+        fn noatime_mounts_do_not_record_access_times() {
+            assert!(!records_access_time(
+                MOUNTINFO,
+                Path::new("/mnt/nas share/x")
+            ));
+            assert!(records_access_time(MOUNTINFO, Path::new("/mnt/nas/x")));
+            assert!(records_access_time(MOUNTINFO, Path::new("/home/me")));
+            assert!(!records_access_time("", Path::new("/home/me")));
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    use std::path::Path;
+
+    use super::DriveKind;
+
+    pub fn drive_kind(_path: &Path) -> Option<DriveKind> {
+        None
+    }
+
+    pub fn tracks_access_time(_path: &Path) -> bool {
+        false
+    }
+}
+
+/// Space available to the user on the drive `path` is on, `None` when it
+/// cannot be queried
+pub fn available_space(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::mem::MaybeUninit;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stats = MaybeUninit::<libc::statvfs>::zeroed();
+        // SAFETY: `path` is NUL-terminated and `stats` is large enough
+        if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: statvfs succeeded, so it filled in `stats`
+        let stats = unsafe { stats.assume_init() };
+        // The field types differ between platforms
+        #[allow(clippy::useless_conversion)]
+        Some(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+        let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut available = 0u64;
+        // SAFETY: `path` is NUL-terminated, the totals not asked for are null
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                path.as_ptr(),
+                &mut available,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        (ok != 0).then_some(available)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Checks whether scanning `path` is likely to be slow because of the drive
+/// it is on, so the frontend can ask before hammering a network share
+#[tauri::command]
+pub async fn precheck_scan(path: String) -> Result<Option<ScanWarning>, ScanError> {
+    let path = decode_path(&path);
+    if !path.exists() {
+        return Err(ScanError::NotFound);
+    }
+    Ok(drive_kind(&path).map(ScanWarning::new))
+}
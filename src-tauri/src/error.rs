@@ -0,0 +1,134 @@
+use std::fmt;
+use std::io;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errors returned by scanning, cache lookups, file operations and the
+/// commands built on them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanError {
+    NotFound,
+    NotADirectory,
+    PermissionDenied,
+    Io(String),
+    Cancelled,
+    NotCached,
+    UnknownJob,
+    InvalidPattern(String),
+    /// Refused to keep the user's data safe, e.g. deleting a filesystem root
+    Refused(String),
+    InvalidArgument(String),
+    Unsupported(String),
+}
+
+impl ScanError {
+    /// `e` as a `ScanError`, with `context` (e.g. "Failed to delete /a")
+    /// put in front of messages that do not get a variant of their own
+    pub fn io_with(context: impl fmt::Display, e: io::Error) -> Self {
+        match Self::from(e) {
+            ScanError::Io(message) => ScanError::Io(format!("{}: {}", context, message)),
+            other => other,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ScanError::NotFound => "NotFound",
+            ScanError::NotADirectory => "NotADirectory",
+            ScanError::PermissionDenied => "PermissionDenied",
+            ScanError::Io(_) => "Io",
+            ScanError::Cancelled => "Cancelled",
+            ScanError::NotCached => "NotCached",
+            ScanError::UnknownJob => "UnknownJob",
+            ScanError::InvalidPattern(_) => "InvalidPattern",
+            ScanError::Refused(_) => "Refused",
+            ScanError::InvalidArgument(_) => "InvalidArgument",
+            ScanError::Unsupported(_) => "Unsupported",
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::NotFound => write!(f, "Path does not exist"),
+            ScanError::NotADirectory => write!(f, "Path is not a directory"),
+            ScanError::PermissionDenied => write!(f, "Permission denied"),
+            ScanError::Io(message) => write!(f, "{}", message),
+            ScanError::Cancelled => write!(f, "Scan was cancelled"),
+            ScanError::NotCached => write!(f, "Path not found in cache"),
+            ScanError::UnknownJob => write!(f, "No scan job with this id"),
+            ScanError::InvalidPattern(message) => write!(f, "Invalid pattern: {}", message),
+            ScanError::Refused(message)
+            | ScanError::InvalidArgument(message)
+            | ScanError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl From<io::Error> for ScanError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => ScanError::NotFound,
+            io::ErrorKind::NotADirectory => ScanError::NotADirectory,
+            io::ErrorKind::PermissionDenied => ScanError::PermissionDenied,
+            _ => ScanError::Io(e.to_string()),
+        }
+    }
+}
+
+/// Serialized as `{ kind, message }` so the frontend can both match on the
+/// variant and display it
+impl Serialize for ScanError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ScanError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_map_to_variants() {
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(ScanError::from(not_found), ScanError::NotFound);
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(ScanError::from(denied), ScanError::PermissionDenied);
+    }
+
+    #[test]
+    fn context_is_only_added_to_plain_io_errors() {
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            ScanError::io_with("Failed to delete /a", denied),
+            ScanError::PermissionDenied
+        );
+        let other = io::Error::other("disk on fire");
+        assert_eq!(
+            ScanError::io_with("Failed to delete /a", other),
+            ScanError::Io("Failed to delete /a: disk on fire".to_string())
+        );
+    }
+
+    #[test]
+    fn serializes_kind_and_message() {
+        let json = serde_json::to_value(ScanError::Refused("No".to_string())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "kind": "Refused", "message": "No" })
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_set_off_from_the_message() {
+        let error = ScanError::InvalidPattern("[a: unclosed class".to_string());
+        assert_eq!(error.to_string(), "Invalid pattern: [a: unclosed class");
+    }
+}
@@ -0,0 +1,445 @@
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use tauri::State;
+
+use crate::disk_scanner::{saturating_sum, DiskScanner, FileNode};
+use crate::error::ScanError;
+use crate::settings::SizeUnits;
+
+fn create_output(out_file: &str) -> Result<BufWriter<File>, ScanError> {
+    File::create(out_file)
+        .map(BufWriter::new)
+        .map_err(|e| ScanError::Io(format!("Failed to create {}: {}", out_file, e)))
+}
+
+/// Writes the cached subtree at `path` to `out_file` as JSON, in the same
+/// `FileNode` shape used over IPC. `max_depth` follows `get_result_with_depth`.
+#[tauri::command]
+pub async fn export_json(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    out_file: String,
+    max_depth: Option<u32>,
+) -> Result<(), ScanError> {
+    scanner.with_node(&path, |node| {
+        let mut writer = create_output(&out_file)?;
+        write_json(&mut writer, node, max_depth)?;
+        Ok(writer.flush()?)
+    })?
+}
+
+/// Writes the JSON `export_json` describes for `node`
+fn write_json(
+    writer: &mut impl Write,
+    node: &FileNode,
+    max_depth: Option<u32>,
+) -> Result<(), ScanError> {
+    match max_depth {
+        Some(max_depth) => serde_json::to_writer(writer, &node.limit_depth(max_depth + 1)),
+        None => serde_json::to_writer(writer, node),
+    }
+    .map_err(|e| ScanError::Io(e.to_string()))
+}
+
+/// Writes every node of the cached subtree at `path` to `out_file` as CSV,
+/// one row per node in depth-first order. Directories carry their
+/// aggregated size.
+#[tauri::command]
+pub async fn export_csv(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    out_file: String,
+) -> Result<(), ScanError> {
+    scanner.with_node(&path, |node| write_csv(create_output(&out_file)?, node))?
+}
+
+/// Writes the rows `export_csv` describes for `node`
+fn write_csv(writer: impl Write, node: &FileNode) -> Result<(), ScanError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer
+        .write_record(["path", "size", "is_directory", "modified"])
+        .map_err(|e| ScanError::Io(e.to_string()))?;
+    for node in node.iter() {
+        writer
+            .write_record([
+                node.path.to_string(),
+                node.size.to_string(),
+                node.is_directory.to_string(),
+                node.modified.map(|m| m.to_string()).unwrap_or_default(),
+            ])
+            .map_err(|e| ScanError::Io(e.to_string()))?;
+    }
+    Ok(writer.flush()?)
+}
+
+/// Writes `node` in ncdu's export format: a file is an object, a directory
+/// an array of its own object followed by its children. Entries listed from
+/// inside archives are not on disk and are left out.
+fn write_ncdu_node(writer: &mut impl Write, node: &FileNode, name: &str) -> Result<(), ScanError> {
+    let mut info = json!({ "name": name });
+    if let Some(modified) = node.modified {
+        info["mtime"] = modified.into();
+    }
+    if !node.is_directory {
+        info["asize"] = node.apparent_size.into();
+        info["dsize"] = node.size.into();
+        if node.is_symlink {
+            info["notreg"] = true.into();
+        }
+        return serde_json::to_writer(writer, &info).map_err(|e| ScanError::Io(e.to_string()));
+    }
+    if node.inaccessible {
+        info["read_error"] = true.into();
+    }
+    if node.mount_point {
+        info["excluded"] = "otherfs".into();
+    }
+
+    writer.write_all(b"[")?;
+    serde_json::to_writer(&mut *writer, &info).map_err(|e| ScanError::Io(e.to_string()))?;
+    for child in node.children.iter().filter(|child| !child.in_archive) {
+        writer.write_all(b",")?;
+        write_ncdu_node(writer, child, &child.name)?;
+    }
+    Ok(writer.write_all(b"]")?)
+}
+
+/// Writes the cached subtree at `path` to `out_file` in the JSON format of
+/// `ncdu -o`, so it can be browsed with `ncdu -f`
+#[tauri::command]
+pub async fn export_ncdu(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    out_file: String,
+) -> Result<(), ScanError> {
+    scanner.with_node(&path, |node| {
+        let mut writer = create_output(&out_file)?;
+        write_ncdu(&mut writer, node)?;
+        Ok(writer.flush()?)
+    })?
+}
+
+/// Writes the export `export_ncdu` describes for `node`
+fn write_ncdu(writer: &mut impl Write, node: &FileNode) -> Result<(), ScanError> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let metadata = json!({
+        "progname": "maka",
+        "progver": env!("CARGO_PKG_VERSION"),
+        "timestamp": timestamp,
+    });
+    write!(writer, "[1,0,{},", metadata)?;
+    // The top-level directory is named by its full path
+    write_ncdu_node(writer, node, &node.path.to_string())?;
+    Ok(writer.write_all(b"]")?)
+}
+
+/// Quotes `text` as a DOT string
+fn dot_string(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Writes `node` as the DOT node `n{id}`, with edges to its `top_n` largest
+/// children and theirs, `depth` levels down. The other children are folded
+/// into one node. Returns the next unused id.
+fn write_dot_node(
+    writer: &mut impl Write,
+    node: &FileNode,
+    name: &str,
+    id: usize,
+    depth: u32,
+    top_n: usize,
+) -> Result<usize, ScanError> {
+    let label = format!("{}\n{}", name, SizeUnits::default().format(node.size));
+    let shape = if node.is_directory { "folder" } else { "box" };
+    writeln!(
+        writer,
+        "  n{} [label={}, shape={}];",
+        id,
+        dot_string(&label),
+        shape
+    )?;
+    let mut next = id + 1;
+    if depth == 0 {
+        return Ok(next);
+    }
+
+    let mut children: Vec<&FileNode> = node.children.iter().collect();
+    children.sort_by_key(|child| Reverse(child.size));
+    let (shown, folded) = children.split_at(children.len().min(top_n));
+    for child in shown {
+        writeln!(writer, "  n{} -> n{};", id, next)?;
+        next = write_dot_node(writer, child, &child.name, next, depth - 1, top_n)?;
+    }
+    if !folded.is_empty() {
+        let size = saturating_sum(folded.iter().map(|child| child.size));
+        let label = format!(
+            "({} smaller {})\n{}",
+            folded.len(),
+            if folded.len() == 1 { "item" } else { "items" },
+            SizeUnits::default().format(size)
+        );
+        writeln!(
+            writer,
+            "  n{} [label={}, style=dashed];",
+            next,
+            dot_string(&label)
+        )?;
+        writeln!(writer, "  n{} -> n{};", id, next)?;
+        next += 1;
+    }
+    Ok(next)
+}
+
+/// Writes the largest branches of the cached subtree at `path` to `out_file`
+/// as a Graphviz graph: the `top_n` largest children of each directory, down
+/// to `max_depth` levels below it, labeled with their sizes. The remaining
+/// children of a directory are folded into a single node.
+#[tauri::command]
+pub async fn export_dot(
+    scanner: State<'_, DiskScanner>,
+    path: String,
+    max_depth: u32,
+    top_n: usize,
+    out_file: String,
+) -> Result<(), ScanError> {
+    scanner.with_node(&path, |node| {
+        let mut writer = create_output(&out_file)?;
+        write_dot(&mut writer, node, max_depth, top_n)?;
+        Ok(writer.flush()?)
+    })?
+}
+
+/// Writes the graph `export_dot` describes for `node`
+fn write_dot(
+    writer: &mut impl Write,
+    node: &FileNode,
+    max_depth: u32,
+    top_n: usize,
+) -> Result<(), ScanError> {
+    writeln!(writer, "digraph maka {{")?;
+    writeln!(writer, "  rankdir=LR;")?;
+    write_dot_node(writer, node, &node.path.to_string(), 0, max_depth, top_n)?;
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use regex::Regex;
+
+    use super::*;
+    use crate::paths::NodePath;
+
+    fn node(name: &str, size: u64, children: Vec<FileNode>) -> FileNode {
+        FileNode {
+            name: name.to_string(),
+            path: NodePath::from(format!("/{}", name)),
+            size,
+            is_directory: !children.is_empty(),
+            children,
+            ..FileNode::default()
+        }
+    }
+
+    /// Checks `dot` against the subset of the DOT grammar `write_dot` uses:
+    /// node statements with a quoted label and edges between declared
+    /// nodes, inside one `digraph`. Returns the labels, unescaped.
+    fn parse_dot(dot: &str) -> Vec<String> {
+        let node_stmt =
+            Regex::new(r#"^  n(\d+) \[label="((?:[^"\\]|\\.)*)", (?:shape|style)=\w+\];$"#)
+                .unwrap();
+        let edge_stmt = Regex::new(r"^  n(\d+) -> n(\d+);$").unwrap();
+        let lines: Vec<&str> = dot.lines().collect();
+        assert_eq!(lines.first(), Some(&"digraph maka {"));
+        assert_eq!(lines.last(), Some(&"}"));
+
+        let mut declared = HashSet::new();
+        let mut labels = Vec::new();
+        for line in &lines[1..lines.len() - 1] {
+            if let Some(captures) = node_stmt.captures(line) {
+                assert!(declared.insert(captures[1].to_string()), "{}", line);
+                labels.push(captures[2].replace("\\n", "\n").replace("\\\"", "\""));
+            } else if let Some(captures) = edge_stmt.captures(line) {
+                // Children are written right after the edge leading to them
+                assert!(declared.contains(&captures[1]), "{}", line);
+            } else {
+                assert_eq!(*line, "  rankdir=LR;");
+            }
+        }
+        labels
+    }
+
+    #[test]
+    fn dot_export_shows_the_largest_children_and_folds_the_rest() {
+        let tree = node(
+            "root",
+            6000,
+            vec![
+                node("big", 3000, vec![node("inner", 3000, Vec::new())]),
+                node("mid \"quoted\"", 2000, Vec::new()),
+                node("small", 600, Vec::new()),
+                node("tiny", 400, Vec::new()),
+            ],
+        );
+        let mut dot = Vec::new();
+        write_dot(&mut dot, &tree, 1, 2).unwrap();
+        let labels = parse_dot(&String::from_utf8(dot).unwrap());
+        assert_eq!(
+            labels,
+            [
+                "/root\n5.9 KiB",
+                "big\n2.9 KiB",
+                "mid \"quoted\"\n2.0 KiB",
+                "(2 smaller items)\n1000 B",
+            ]
+        );
+    }
+
+    #[test]
+    fn dot_export_saturates_folded_sizes() {
+        let children = (0..3)
+            .map(|i| node(&i.to_string(), u64::MAX, Vec::new()))
+            .collect();
+        let tree = node("root", u64::MAX, children);
+        let mut dot = Vec::new();
+        write_dot(&mut dot, &tree, 1, 1).unwrap();
+        let labels = parse_dot(&String::from_utf8(dot).unwrap());
+        let folded = SizeUnits::default().format(u64::MAX);
+        assert_eq!(
+            labels.last(),
+            Some(&format!("(2 smaller items)\n{}", folded))
+        );
+    }
+
+    #[test]
+    fn json_exports_parse_back_to_the_same_tree() {
+        let mut tree = node(
+            "root",
+            5000,
+            vec![
+                node("docs", 3000, vec![node("report.pdf", 3000, Vec::new())]),
+                node("notes.txt", 2000, Vec::new()),
+            ],
+        );
+        tree.modified = Some(1_700_000_000);
+        tree.total_files = 2;
+
+        let mut json = Vec::new();
+        write_json(&mut json, &tree, None).unwrap();
+        let parsed: FileNode = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(&tree).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_exports_stop_at_the_depth_limit() {
+        let tree = node(
+            "root",
+            3000,
+            vec![node(
+                "docs",
+                3000,
+                vec![node("report.pdf", 3000, Vec::new())],
+            )],
+        );
+        let mut json = Vec::new();
+        write_json(&mut json, &tree, Some(0)).unwrap();
+        let parsed: FileNode = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed.children.len(), 1);
+        let docs = &parsed.children[0];
+        assert_eq!(docs.name, "docs");
+        assert_eq!(docs.size, 3000);
+        assert!(docs.children.is_empty());
+        assert!(docs.truncated);
+    }
+
+    #[test]
+    fn csv_exports_one_row_per_node() {
+        let mut tree = node(
+            "root",
+            5000,
+            vec![
+                node("a,b", 3000, vec![node("c", 3000, Vec::new())]),
+                node("d", 2000, Vec::new()),
+            ],
+        );
+        tree.modified = Some(1_700_000_000);
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &tree).unwrap();
+
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["path", "size", "is_directory", "modified"]
+        );
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), tree.iter().count());
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], vec!["/root", "5000", "true", "1700000000"]);
+        assert!(rows
+            .iter()
+            .any(|row| row == vec!["/a,b", "3000", "true", ""]));
+        assert!(String::from_utf8(csv).unwrap().contains("\"/a,b\""));
+    }
+
+    #[test]
+    fn ncdu_exports_nest_directories_as_arrays() {
+        let mut tree = node(
+            "root",
+            5000,
+            vec![
+                node("docs", 3000, vec![node("report.pdf", 3000, Vec::new())]),
+                node("notes.txt", 2000, Vec::new()),
+                FileNode {
+                    in_archive: true,
+                    ..node("inside.zip", 100, Vec::new())
+                },
+            ],
+        );
+        tree.children[1].apparent_size = 1800;
+        tree.children[1].modified = Some(1_700_000_000);
+        let mut out = Vec::new();
+        write_ncdu(&mut out, &tree).unwrap();
+
+        let export: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let top = export.as_array().unwrap();
+        assert_eq!(top.len(), 4);
+        assert_eq!((top[0].as_u64(), top[1].as_u64()), (Some(1), Some(0)));
+        assert_eq!(top[2]["progname"], "maka");
+        assert!(top[2]["timestamp"].as_u64().unwrap() > 0);
+
+        // The root directory, named by its path, with its two children on
+        // disk after it
+        let root = top[3].as_array().unwrap();
+        assert_eq!(root.len(), 3);
+        assert_eq!(root[0]["name"], "/root");
+        let docs = root[1].as_array().unwrap();
+        assert_eq!(docs[0]["name"], "docs");
+        assert_eq!(docs[1]["name"], "report.pdf");
+        assert_eq!(docs[1]["dsize"], 3000);
+        assert_eq!(
+            root[2],
+            json!({
+                "name": "notes.txt",
+                "asize": 1800,
+                "dsize": 2000,
+                "mtime": 1_700_000_000,
+            })
+        );
+    }
+}
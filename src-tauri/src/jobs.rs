@@ -0,0 +1,304 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Manager, State};
+
+use crate::disk_scanner::{system_drives, DiskScanner, ProgressTracker, ScanOptions, ScanProgress};
+use crate::error::ScanError;
+use crate::settings;
+
+/// Where a scan job is at
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state")]
+pub enum JobState {
+    /// Waiting for the warm-up scans before it, see `warm_up`
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+    Failed {
+        error: ScanError,
+    },
+}
+
+/// Status of a scan job, as returned by `scan_status`
+#[derive(Clone, Serialize)]
+pub struct JobStatus {
+    pub job_id: u64,
+    pub path: String,
+    #[serde(flatten)]
+    pub state: JobState,
+    pub progress: ScanProgress,
+}
+
+struct ScanJob {
+    path: String,
+    tracker: Arc<ProgressTracker>,
+    state: Mutex<JobState>,
+}
+
+/// Scans started with `start_scan`, each running on its own thread with its
+/// own progress and cancellation. Finished jobs are kept so their outcome
+/// can still be queried.
+#[derive(Default)]
+pub struct ScanJobs {
+    jobs: DashMap<u64, Arc<ScanJob>>,
+    next_id: AtomicU64,
+}
+
+impl ScanJobs {
+    /// Adds a job for `path` in `state`, its tracker made by `tracker` from
+    /// the new job's id
+    fn register(
+        &self,
+        path: String,
+        state: JobState,
+        tracker: impl FnOnce(u64) -> ProgressTracker,
+    ) -> (u64, Arc<ScanJob>) {
+        let job_id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = Arc::new(ScanJob {
+            path,
+            tracker: Arc::new(tracker(job_id)),
+            state: Mutex::new(state),
+        });
+        self.jobs.insert(job_id, Arc::clone(&job));
+        (job_id, job)
+    }
+
+    /// Starts scanning `path` on a thread of its own, see `start_scan`
+    fn start(
+        &self,
+        app: &AppHandle,
+        path: String,
+        options: ScanOptions,
+        on_progress: Option<Channel<ScanProgress>>,
+    ) -> u64 {
+        let (job_id, job) = self.register(path, JobState::Running, |job_id| {
+            ProgressTracker::for_job(app.clone(), on_progress, job_id)
+        });
+        let app = app.clone();
+        thread::spawn(move || run(&app.state::<DiskScanner>(), &job, options));
+        job_id
+    }
+
+    /// Queues a low-priority scan of every system drive so their data is
+    /// cached by the time it is asked for. Each drive gets its own job with
+    /// a single scanning thread, reporting to the `scan-progress` event. The
+    /// jobs run one after another on one thread, and a drive inside one
+    /// scanned before it is not scanned again.
+    pub fn warm_up(&self, app: &AppHandle) -> Result<Vec<u64>, ScanError> {
+        let options = ScanOptions {
+            max_threads: Some(1),
+            one_filesystem: true,
+            ..settings::load(app).scan_options()
+        };
+        let (job_ids, queue): (Vec<u64>, Vec<Arc<ScanJob>>) = system_drives()?
+            .into_iter()
+            .map(|drive| {
+                self.register(drive, JobState::Queued, |job_id| {
+                    ProgressTracker::for_job(app.clone(), None, job_id)
+                })
+            })
+            .unzip();
+        let app = app.clone();
+        thread::spawn(move || run_queue(&app.state::<DiskScanner>(), &queue, &options));
+        Ok(job_ids)
+    }
+
+    fn get(&self, job_id: u64) -> Result<Arc<ScanJob>, ScanError> {
+        self.jobs
+            .get(&job_id)
+            .map(|job| Arc::clone(&job))
+            .ok_or(ScanError::UnknownJob)
+    }
+}
+
+/// Runs `job`, scanning and caching its path, and records how it ended
+fn run(scanner: &DiskScanner, job: &ScanJob, options: ScanOptions) {
+    *job.state.lock() = JobState::Running;
+    let state = match scanner.build_cache_tracked(&job.path, options, Arc::clone(&job.tracker)) {
+        Ok(()) => JobState::Completed,
+        Err(ScanError::Cancelled) => JobState::Cancelled,
+        Err(error) => JobState::Failed { error },
+    };
+    *job.state.lock() = state;
+}
+
+/// Runs the queued `jobs` in order. Jobs cancelled while queued are not
+/// started, and those whose path is already cached complete right away.
+fn run_queue(scanner: &DiskScanner, jobs: &[Arc<ScanJob>], options: &ScanOptions) {
+    for job in jobs {
+        if job.tracker.is_cancelled() {
+            *job.state.lock() = JobState::Cancelled;
+        } else if scanner.is_cached(&job.path) {
+            *job.state.lock() = JobState::Completed;
+        } else {
+            run(scanner, job, options.clone());
+        }
+    }
+}
+
+/// Scans and caches `path` in the background, returning the id used to
+/// follow or cancel the job. Progress goes to `on_progress` when given,
+/// otherwise to the `scan-progress` event.
+#[tauri::command]
+pub async fn start_scan(
+    app: AppHandle,
+    jobs: State<'_, ScanJobs>,
+    path: String,
+    options: Option<ScanOptions>,
+    on_progress: Option<Channel<ScanProgress>>,
+) -> Result<u64, ScanError> {
+    let options = settings::scan_options(&app, options);
+    Ok(jobs.start(&app, path, options, on_progress))
+}
+
+/// Starts background scans of the system drives, returning their job ids
+/// so they can be followed or cancelled like any other scan
+#[tauri::command]
+pub async fn warm_up(app: AppHandle, jobs: State<'_, ScanJobs>) -> Result<Vec<u64>, ScanError> {
+    jobs.warm_up(&app)
+}
+
+#[tauri::command]
+pub async fn scan_status(jobs: State<'_, ScanJobs>, job_id: u64) -> Result<JobStatus, ScanError> {
+    let job = jobs.get(job_id)?;
+    let state = job.state.lock().clone();
+    Ok(JobStatus {
+        job_id,
+        path: job.path.clone(),
+        state,
+        progress: job.tracker.progress(),
+    })
+}
+
+/// Asks a running scan to stop. It ends up `Cancelled` shortly after,
+/// leaving the cache untouched.
+#[tauri::command]
+pub async fn cancel_scan(jobs: State<'_, ScanJobs>, job_id: u64) -> Result<(), ScanError> {
+    jobs.get(job_id)?.tracker.cancel();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::paths::encode_path;
+
+    fn queue(jobs: &ScanJobs, paths: &[String]) -> Vec<Arc<ScanJob>> {
+        paths
+            .iter()
+            .map(|path| {
+                jobs.register(path.clone(), JobState::Queued, |_| {
+                    ProgressTracker::silent()
+                })
+                .1
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queued_scans_populate_the_cache_in_order() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("nested/file"), b"data").unwrap();
+        let root = encode_path(dir.path());
+        let nested = encode_path(&dir.path().join("nested"));
+        let scanner = DiskScanner::new();
+        let jobs = ScanJobs::default();
+        let queued = queue(&jobs, &[root.clone(), nested.clone()]);
+
+        run_queue(&scanner, &queued, &ScanOptions::default());
+        for job in &queued {
+            assert!(matches!(*job.state.lock(), JobState::Completed));
+        }
+        // The nested drive was covered by the first scan, not scanned again
+        assert_eq!(queued[1].tracker.progress().file_count, 0);
+        assert_eq!(queued[0].tracker.progress().file_count, 1);
+        assert!(scanner.is_cached(&nested));
+        assert_eq!(scanner.with_node(&nested, |node| node.total_files), Ok(1));
+    }
+
+    #[test]
+    fn cancelled_queued_scans_never_start() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let paths = [encode_path(first.path()), encode_path(second.path())];
+        let scanner = DiskScanner::new();
+        let jobs = ScanJobs::default();
+        let queued = queue(&jobs, &paths);
+        queued[1].tracker.cancel();
+
+        run_queue(&scanner, &queued, &ScanOptions::default());
+        assert!(matches!(*queued[0].state.lock(), JobState::Completed));
+        assert!(matches!(*queued[1].state.lock(), JobState::Cancelled));
+        assert!(scanner.is_cached(&paths[0]));
+        assert!(!scanner.is_cached(&paths[1]));
+    }
+
+    #[test]
+    fn failed_scans_record_their_error() {
+        let dir = TempDir::new().unwrap();
+        let scanner = DiskScanner::new();
+        let jobs = ScanJobs::default();
+        let (job_id, job) = jobs.register(
+            encode_path(&dir.path().join("missing")),
+            JobState::Running,
+            |_| ProgressTracker::silent(),
+        );
+        run(&scanner, &job, ScanOptions::default());
+        assert!(matches!(
+            *jobs.get(job_id).unwrap().state.lock(),
+            JobState::Failed {
+                error: ScanError::NotFound
+            }
+        ));
+        assert!(matches!(jobs.get(job_id + 1), Err(ScanError::UnknownJob)));
+    }
+
+    #[test]
+    fn concurrent_jobs_finish_independently() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        for (dir, count) in [(&first, 3), (&second, 2)] {
+            for i in 0..count {
+                fs::write(dir.path().join(i.to_string()), b"data").unwrap();
+            }
+        }
+        let scanner = DiskScanner::new();
+        let jobs = ScanJobs::default();
+        let (ids, started): (Vec<u64>, Vec<Arc<ScanJob>>) = [&first, &second, &first]
+            .iter()
+            .map(|dir| {
+                jobs.register(encode_path(dir.path()), JobState::Running, |_| {
+                    ProgressTracker::silent()
+                })
+            })
+            .unzip();
+        started[2].tracker.cancel();
+
+        thread::scope(|scope| {
+            for job in &started {
+                scope.spawn(|| run(&scanner, job, ScanOptions::default()));
+            }
+        });
+        assert_eq!(ids, [1, 2, 3]);
+        let state = |job_id| jobs.get(job_id).unwrap().state.lock().clone();
+        assert!(matches!(state(1), JobState::Completed));
+        assert!(matches!(state(2), JobState::Completed));
+        assert!(matches!(state(3), JobState::Cancelled));
+        assert_eq!(started[0].tracker.progress().file_count, 3);
+        assert_eq!(started[1].tracker.progress().file_count, 2);
+        assert!(scanner.is_cached(&encode_path(first.path())));
+        assert!(scanner.is_cached(&encode_path(second.path())));
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::ScanError;
+use crate::paths::decode_path;
+
+/// Command showing `path` selected in the platform's file manager
+#[cfg(target_os = "macos")]
+fn reveal_commands(path: &Path) -> Vec<Command> {
+    let mut open = Command::new("open");
+    open.arg("-R").arg(path);
+    vec![open]
+}
+
+/// Command showing `path` selected in the platform's file manager
+#[cfg(target_os = "windows")]
+fn reveal_commands(path: &Path) -> Vec<Command> {
+    let mut select = std::ffi::OsString::from("/select,");
+    select.push(path);
+    let mut explorer = Command::new("explorer");
+    explorer.arg(select);
+    vec![explorer]
+}
+
+/// Commands showing `path` in the desktop's file manager, in order of
+/// preference: the FileManager1 D-Bus interface selects the item, opening
+/// the parent directory is the fallback
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal_commands(path: &Path) -> Vec<Command> {
+    let mut show_items = Command::new("dbus-send");
+    show_items.args([
+        "--session",
+        "--print-reply",
+        "--dest=org.freedesktop.FileManager1",
+        "/org/freedesktop/FileManager1",
+        "org.freedesktop.FileManager1.ShowItems",
+        &format!("array:string:file://{}", crate::paths::uri_path(path)),
+        "string:",
+    ]);
+    let mut open_parent = Command::new("xdg-open");
+    open_parent.arg(path.parent().unwrap_or(path));
+    vec![show_items, open_parent]
+}
+
+/// Command opening `path` with its default application
+fn open_command(path: &Path) -> Command {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    // Explorer hands files to their associated application
+    #[cfg(target_os = "windows")]
+    let mut command = Command::new("explorer");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
+
+/// Runs the first of `commands` that succeeds
+fn launch(commands: Vec<Command>) -> Result<(), ScanError> {
+    let mut last_error = String::from("No command to run");
+    for mut command in commands {
+        match command.stdout(Stdio::null()).stderr(Stdio::null()).status() {
+            Ok(status) if status.success() => return Ok(()),
+            // Explorer reports failure even when it opened the window
+            Ok(_) if cfg!(target_os = "windows") => return Ok(()),
+            Ok(status) => {
+                last_error = format!("{:?} exited with {}", command.get_program(), status)
+            }
+            Err(e) => last_error = format!("Failed to run {:?}: {}", command.get_program(), e),
+        }
+    }
+    Err(ScanError::Io(last_error))
+}
+
+/// Shows `path` in Finder, Explorer or the desktop's file manager
+#[tauri::command]
+pub async fn reveal_in_file_manager(path: String) -> Result<(), ScanError> {
+    launch(reveal_commands(&existing(&path)?))
+}
+
+/// Absolute form of `path`, which must exist
+fn existing(path: &str) -> Result<PathBuf, ScanError> {
+    let decoded = decode_path(path);
+    if !decoded.exists() {
+        return Err(ScanError::NotFound);
+    }
+    Ok(std::path::absolute(&decoded)?)
+}
+
+/// Opens the file at `path` with its default application. Directories are
+/// refused, `reveal_in_file_manager` is meant for those.
+#[tauri::command]
+pub async fn open_file(path: String) -> Result<(), ScanError> {
+    launch(vec![open_command(&openable(&path)?)])
+}
+
+/// Absolute form of `path`, which must exist and not be a directory
+fn openable(path: &str) -> Result<PathBuf, ScanError> {
+    let absolute = existing(path)?;
+    if absolute.is_dir() {
+        return Err(ScanError::InvalidArgument(format!(
+            "Cannot open {}: it is a directory",
+            path
+        )));
+    }
+    Ok(absolute)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn missing_paths_are_not_revealed() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        assert_eq!(
+            existing(missing.to_str().unwrap()).unwrap_err(),
+            ScanError::NotFound
+        );
+        assert_eq!(existing(dir.path().to_str().unwrap()).unwrap(), dir.path());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn reveal_selects_the_item_in_finder() {
+        let commands = reveal_commands(Path::new("/Users/me/file"));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].get_program(), "open");
+        assert_eq!(args(&commands[0]), ["-R", "/Users/me/file"]);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn reveal_selects_the_item_in_explorer() {
+        let commands = reveal_commands(Path::new(r"C:\Users\me\file"));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].get_program(), "explorer");
+        assert_eq!(args(&commands[0]), [r"/select,C:\Users\me\file"]);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn reveal_asks_the_file_manager_then_opens_the_parent() {
+        let commands = reveal_commands(Path::new("/home/me/file"));
+        let programs: Vec<_> = commands.iter().map(Command::get_program).collect();
+        assert_eq!(programs, ["dbus-send", "xdg-open"]);
+        let show_items = args(&commands[0]);
+        assert!(show_items.contains(&"org.freedesktop.FileManager1.ShowItems".to_string()));
+        assert!(show_items.contains(&"array:string:file:///home/me/file".to_string()));
+        assert_eq!(args(&commands[1]), ["/home/me"]);
+    }
+
+    #[test]
+    fn directories_and_missing_files_are_not_opened() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, b"data").unwrap();
+        assert_eq!(openable(file.to_str().unwrap()).unwrap(), file);
+        assert!(matches!(
+            openable(dir.path().to_str().unwrap()),
+            Err(ScanError::InvalidArgument(message)) if message.contains("directory")
+        ));
+        assert_eq!(
+            openable(dir.path().join("missing").to_str().unwrap()).unwrap_err(),
+            ScanError::NotFound
+        );
+    }
+
+    #[test]
+    fn files_open_with_the_platform_handler() {
+        let command = open_command(Path::new("/data/movie.mp4"));
+        let program = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(windows) {
+            "explorer"
+        } else {
+            "xdg-open"
+        };
+        assert_eq!(command.get_program(), program);
+        assert_eq!(args(&command), ["/data/movie.mp4"]);
+    }
+}
@@ -0,0 +1,125 @@
+mod analysis;
+mod archive;
+mod diff;
+mod disk_ops;
+pub mod disk_scanner;
+mod drives;
+mod error;
+mod export;
+mod jobs;
+mod launcher;
+mod paths;
+mod permissions;
+mod settings;
+mod trash;
+mod watcher;
+
+use tauri::Manager;
+use tracing_subscriber::EnvFilter;
+
+use disk_scanner::{
+    aggregate_roots, ancestors, build_cache, build_cache_many, cache_stats, clear_cache,
+    configure_scanner, directory_size, get_children_page, get_directory_info, get_result_compact,
+    get_result_dirs_only, get_result_filtered, get_result_with_depth, get_system_drives,
+    list_cached_roots, recompute_size, stat_path, stream_children, DiskScanner,
+};
+
+pub fn run() {
+    // Logs go to stderr, filtered by RUST_LOG (e.g. `RUST_LOG=maka=debug`)
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_macos_permissions::init())
+        .manage(DiskScanner::new())
+        .manage(watcher::Watchers::default())
+        .manage(jobs::ScanJobs::default())
+        .manage(disk_ops::DeletionPlans::default())
+        .setup(|app| {
+            let settings = settings::load(app.handle());
+            app.state::<DiskScanner>()
+                .set_max_threads(settings.max_threads)?;
+            if settings.warm_up_on_launch {
+                if let Err(e) = app.state::<jobs::ScanJobs>().warm_up(app.handle()) {
+                    tracing::warn!(error = %e, "Failed to warm up the cache");
+                }
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_system_drives,
+            drives::precheck_scan,
+            permissions::select_directory,
+            permissions::select_directories,
+            permissions::permission_report,
+            permissions::is_elevated,
+            launcher::reveal_in_file_manager,
+            launcher::open_file,
+            paths::display_path,
+            disk_ops::prepare_deletion,
+            disk_ops::delete_items,
+            disk_ops::delete_items_elevated,
+            disk_ops::native_trash,
+            disk_ops::secure_delete,
+            disk_ops::move_items,
+            analysis::largest_files,
+            analysis::largest_dirs,
+            analysis::files_in_size_range,
+            analysis::search,
+            analysis::search_advanced,
+            analysis::size_map,
+            analysis::stale_files,
+            analysis::recent_files,
+            analysis::find_empty_dirs,
+            analysis::find_duplicate_dirs,
+            analysis::find_junk,
+            analysis::never_opened,
+            analysis::reclaimable_report,
+            analysis::usage_by_category,
+            analysis::usage_by_owner,
+            analysis::size_histogram,
+            build_cache,
+            build_cache_many,
+            jobs::start_scan,
+            jobs::scan_status,
+            jobs::cancel_scan,
+            jobs::warm_up,
+            configure_scanner,
+            settings::load_settings,
+            settings::save_settings,
+            settings::list_protected_paths,
+            settings::add_protected_path,
+            settings::remove_protected_path,
+            diff::diff_trees,
+            diff::cache_drift,
+            export::export_csv,
+            export::export_json,
+            export::export_ncdu,
+            export::export_dot,
+            watcher::watch_path,
+            watcher::unwatch_path,
+            get_result_with_depth,
+            get_result_filtered,
+            get_result_dirs_only,
+            get_result_compact,
+            aggregate_roots,
+            get_children_page,
+            stream_children,
+            stat_path,
+            get_directory_info,
+            ancestors,
+            list_cached_roots,
+            clear_cache,
+            cache_stats,
+            directory_size,
+            recompute_size,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,544 @@
+use std::ffi::OsStr;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::ScanError;
+
+#[cfg(unix)]
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// Path separator check: `/` everywhere, and `\` as well on Windows, where
+/// paths coming back from the frontend may use either. On other platforms
+/// `\` is an ordinary character in file names.
+pub fn is_separator(c: char) -> bool {
+    c == '/' || cfg!(windows) && c == '\\'
+}
+
+/// Returns the part of `path` below `root`, or `None` when `path` is not
+/// inside `root`. Unlike a plain string prefix check, `/foo` is not treated
+/// as containing `/foobar`.
+pub fn relative_to_root<'a>(path: &'a str, root: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(root)?;
+    if rest.is_empty() || root.ends_with(is_separator) || rest.starts_with(is_separator) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Splits a path relative to a cached root into its non-empty components
+pub fn components(relative_path: &str) -> impl Iterator<Item = &str> {
+    relative_path.split(is_separator).filter(|p| !p.is_empty())
+}
+
+/// Path of a node in a cached tree. A node only stores its own name and
+/// shares the path of its parent, so the prefixes of millions of nodes are
+/// not repeated in memory. The full path is put together on demand and
+/// serializes as a plain string, in the form `encode_path` gives.
+#[derive(Clone, Default)]
+pub struct NodePath(Option<Arc<PathSegment>>);
+
+struct PathSegment {
+    parent: NodePath,
+    /// Encoded name of the node (see `encoded_name`), or a whole path for
+    /// the first segment
+    name: Box<str>,
+}
+
+impl NodePath {
+    /// Path of the node for `path`: the one of `parent` joined with the
+    /// node's name, or the whole of `path` for a node without a parent
+    pub fn of(path: &Path, parent: Option<&NodePath>) -> Self {
+        match parent {
+            Some(parent) => parent.join(&encoded_name(path)),
+            None => Self::from(encode_path(path)),
+        }
+    }
+
+    /// Path of the child called `name` (as given by `encoded_name`)
+    pub fn join(&self, name: &str) -> Self {
+        Self(Some(Arc::new(PathSegment {
+            parent: self.clone(),
+            name: name.into(),
+        })))
+    }
+
+    /// Encoded name of the node, the last component of its path
+    pub fn name(&self) -> &str {
+        self.0.as_ref().map_or("", |segment| {
+            segment.name.rsplit(is_separator).next().unwrap_or_default()
+        })
+    }
+
+    /// Memory taken by this node's own segment, not counting the ones it
+    /// shares with its ancestors
+    pub fn estimated_bytes(&self) -> usize {
+        self.0.as_ref().map_or(0, |segment| {
+            std::mem::size_of::<PathSegment>()
+                + 2 * std::mem::size_of::<usize>()
+                + segment.name.len()
+        })
+    }
+}
+
+impl From<String> for NodePath {
+    fn from(path: String) -> Self {
+        if path.is_empty() {
+            Self::default()
+        } else {
+            Self::default().join(&path)
+        }
+    }
+}
+
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Vec::new();
+        let mut current = self;
+        while let Some(segment) = &current.0 {
+            names.push(&*segment.name);
+            current = &segment.parent;
+        }
+        let mut previous: Option<&str> = None;
+        for name in names.into_iter().rev() {
+            // Roots such as `/` and `C:\` already end with a separator
+            if previous.is_some_and(|previous| !previous.ends_with(is_separator)) {
+                f.write_char(MAIN_SEPARATOR)?;
+            }
+            f.write_str(name)?;
+            previous = Some(name);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl PartialEq for NodePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl Eq for NodePath {}
+
+impl PartialOrd for NodePath {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NodePath {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.to_string().cmp(&other.to_string())
+    }
+}
+
+impl Serialize for NodePath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodePath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Name shown for a node. Filesystem roots such as `/` or `C:\` have no file
+/// name, so they are named after the full path instead. Unlike in paths, a
+/// `%` is kept as is and undecodable bytes are replaced.
+pub fn node_name(path: &Path) -> String {
+    file_name(path).to_string_lossy().into_owned()
+}
+
+/// Name of a node as it appears in its path, see `encode_os_str`
+pub fn encoded_name(path: &Path) -> String {
+    encode_os_str(file_name(path))
+}
+
+/// Name shown for the node whose path ends in the encoded `name`
+pub fn display_name(name: &str) -> String {
+    decode_path(name).to_string_lossy().into_owned()
+}
+
+fn file_name(path: &Path) -> &OsStr {
+    path.file_name().unwrap_or(path.as_os_str())
+}
+
+/// Lowercased extension of the file at `path`, e.g. `jpg` for `photo1.JPG`
+pub fn file_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// String form of a path used in `FileNode`s and over IPC, see
+/// `encode_os_str`. On Windows the `\\?\` prefix is left out.
+pub fn encode_path(path: &Path) -> String {
+    let encoded = encode_os_str(path.as_os_str());
+    if cfg!(windows) {
+        strip_extended_length(encoded)
+    } else {
+        encoded
+    }
+}
+
+/// Gives an absolute Windows path the `\\?\` extended-length prefix, with
+/// `\` separators throughout, so that `read_dir` and `metadata` keep working
+/// past `MAX_PATH` (260 characters). Relative and already prefixed paths,
+/// and all paths on other platforms, are returned unchanged.
+pub fn extended_length(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(path) = path.to_str().filter(|_| path.is_absolute()) {
+        if !path.starts_with(r"\\?\") {
+            let path = path.replace('/', r"\");
+            return PathBuf::from(match path.strip_prefix(r"\\") {
+                Some(share) => format!(r"\\?\UNC\{}", share),
+                None => format!(r"\\?\{}", path),
+            });
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Turns `\\?\C:\dir` back into `C:\dir` and `\\?\UNC\server\share` into
+/// `\\server\share`
+fn strip_extended_length(path: String) -> String {
+    match path.strip_prefix(r"\\?\") {
+        Some(rest) => match rest.strip_prefix(r"UNC\") {
+            Some(share) => format!(r"\\{}", share),
+            None => rest.to_string(),
+        },
+        None => path,
+    }
+}
+
+/// Path as users would type it in a terminal: without the `\\?\` prefix,
+/// with `\` separators on Windows and undecodable bytes replaced
+fn display_form(path: &str) -> String {
+    let path = strip_extended_length(decode_path(path).to_string_lossy().into_owned());
+    if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path
+    }
+}
+
+/// Formats a node path for "Copy path", so it can be pasted into a terminal
+#[tauri::command]
+pub async fn display_path(path: String) -> Result<String, ScanError> {
+    Ok(display_form(&path))
+}
+
+/// Converts a file name or path to a string without losing data. Valid
+/// UTF-8 is kept as is, except that every `%` becomes `%25`, and invalid
+/// bytes are percent-encoded, so that `decode_path` gives back the original
+/// bytes whatever the name looked like.
+pub fn encode_os_str(s: &OsStr) -> String {
+    #[cfg(unix)]
+    {
+        let bytes = s.as_bytes();
+        if let Ok(valid) = std::str::from_utf8(bytes) {
+            return valid.replace('%', "%25");
+        }
+        let mut encoded = String::with_capacity(bytes.len());
+        for chunk in bytes.utf8_chunks() {
+            encoded.push_str(&chunk.valid().replace('%', "%25"));
+            for byte in chunk.invalid() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        encoded
+    }
+    #[cfg(not(unix))]
+    {
+        // Windows names are UTF-16 and practically always valid Unicode
+        s.to_string_lossy().to_string()
+    }
+}
+
+/// Turns a path string from `encode_path` back into the real path. Strings
+/// with a `%` that is not followed by two hex digits cannot have been
+/// encoded and are taken literally.
+pub fn decode_path(path: &str) -> PathBuf {
+    #[cfg(unix)]
+    if path.contains('%') {
+        if let Some(bytes) = percent_decode(path) {
+            return PathBuf::from(OsString::from_vec(bytes));
+        }
+    }
+    PathBuf::from(path)
+}
+
+#[cfg(unix)]
+fn percent_decode(encoded: &str) -> Option<Vec<u8>> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Resolves symlinks, `.` and `..` so that aliases of a directory (`/a/b/`,
+/// `/a/./b` or a symlink to it) all give the same cache key. The `\\?\`
+/// prefix `fs::canonicalize` adds on Windows is dropped again.
+pub fn canonicalize(path: &str) -> Option<String> {
+    Some(encode_path(&fs::canonicalize(decode_path(path)).ok()?))
+}
+
+/// Percent-encodes an absolute path as used in `file://` URIs and the
+/// `Path=` key of `.trashinfo` files
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn uri_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_paths_rebuild_the_full_path() {
+        let root = NodePath::of(Path::new("/"), None);
+        let home = NodePath::of(Path::new("/home"), Some(&root));
+        let file = NodePath::of(Path::new("/home/a b%.txt"), Some(&home));
+        assert_eq!(root.to_string(), "/");
+        assert_eq!(home.to_string(), "/home");
+        assert_eq!(file.to_string(), encode_path(Path::new("/home/a b%.txt")));
+        assert_eq!(file, NodePath::from("/home/a b%25.txt".to_string()));
+
+        let nested = NodePath::from("/srv/data".to_string()).join("x");
+        assert_eq!(nested.to_string(), "/srv/data/x");
+        assert_eq!(NodePath::from(String::new()).to_string(), "");
+    }
+
+    #[test]
+    fn node_paths_serialize_as_plain_strings() {
+        let path = NodePath::from("/srv".to_string()).join("log");
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, "\"/srv/log\"");
+        let back: NodePath = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, path);
+    }
+
+    #[test]
+    fn extensions_are_lowercased() {
+        assert_eq!(
+            file_extension(Path::new("photo1.jpg")).as_deref(),
+            Some("jpg")
+        );
+        assert_eq!(
+            file_extension(Path::new("/a/B.TAR.GZ")).as_deref(),
+            Some("gz")
+        );
+        assert_eq!(file_extension(Path::new("Makefile")), None);
+        assert_eq!(file_extension(Path::new(".bashrc")), None);
+    }
+
+    #[test]
+    fn roots_only_match_whole_components() {
+        assert_eq!(relative_to_root("/ab/c", "/a"), None);
+        assert_eq!(relative_to_root("/a/c", "/a"), Some("/c"));
+        assert_eq!(relative_to_root("/a", "/a"), Some(""));
+        assert_eq!(relative_to_root("/a", "/"), Some("a"));
+    }
+
+    #[test]
+    fn filesystem_roots_are_named_after_their_path() {
+        for root in ["/", "C:\\"] {
+            assert!(!node_name(Path::new(root)).is_empty(), "{}", root);
+        }
+        if cfg!(windows) {
+            assert_eq!(node_name(Path::new("C:\\")), "C:\\");
+        } else {
+            assert_eq!(node_name(Path::new("/")), "/");
+        }
+        assert_eq!(node_name(Path::new("/Volumes/Backup")), "Backup");
+    }
+
+    #[test]
+    fn names_keep_percent_signs_that_paths_escape() {
+        let dir = NodePath::of(Path::new("/tmp"), None);
+        let file = NodePath::of(Path::new("/tmp/100%.txt"), Some(&dir));
+        assert_eq!(node_name(Path::new("/tmp/100%.txt")), "100%.txt");
+        assert_eq!(display_name(file.name()), "100%.txt");
+        assert_eq!(decode_path(&file.to_string()), Path::new("/tmp/100%.txt"));
+        if cfg!(unix) {
+            assert_eq!(file.name(), "100%25.txt");
+        }
+        // A node cached under its whole path is named after its last component
+        assert_eq!(NodePath::from("/tmp/sub/50%25".to_string()).name(), "50%25");
+    }
+
+    #[test]
+    fn extended_length_prefixes_are_stripped() {
+        assert_eq!(strip_extended_length(r"\\?\C:\dir".to_string()), r"C:\dir");
+        assert_eq!(
+            strip_extended_length(r"\\?\UNC\server\share\dir".to_string()),
+            r"\\server\share\dir"
+        );
+        assert_eq!(strip_extended_length("/home/me".to_string()), "/home/me");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths_get_the_extended_length_prefix() {
+        let long = format!(r"C:\{}\file.txt", ["component"; 30].join("/"));
+        assert!(long.len() > 260);
+        let extended = extended_length(Path::new(&long));
+        let extended = extended.to_str().unwrap();
+        assert!(extended.starts_with(r"\\?\C:\component\component"));
+        assert!(!extended[4..].contains('/'));
+        assert_eq!(encode_path(Path::new(extended)), long.replace('/', r"\"));
+        assert_eq!(
+            extended_length(Path::new(r"\\server\share\dir")),
+            Path::new(r"\\?\UNC\server\share\dir")
+        );
+        // Relative and already prefixed paths are left alone
+        assert_eq!(
+            extended_length(Path::new(r"dir\file")),
+            Path::new(r"dir\file")
+        );
+        assert_eq!(extended_length(Path::new(extended)), Path::new(extended));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn trees_deeper_than_max_path_are_scanned() {
+        use std::sync::Arc;
+
+        use crate::disk_scanner::{DiskScanner, ProgressTracker, ScanOptions};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let deep = (0..30).fold(dir.path().to_path_buf(), |path, i| {
+            path.join(format!("component{:02}", i))
+        });
+        std::fs::create_dir_all(extended_length(&deep)).unwrap();
+        std::fs::write(extended_length(&deep.join("file.txt")), b"data").unwrap();
+        assert!(deep.as_os_str().len() > 260);
+
+        let scanner = DiskScanner::new();
+        let root = encode_path(dir.path());
+        scanner
+            .build_cache_tracked(
+                &root,
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        let file = encode_path(&deep.join("file.txt"));
+        assert!(!file.starts_with(r"\\?\"));
+        assert_eq!(scanner.with_node(&file, |node| node.apparent_size), Ok(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_are_not_prefixed_outside_windows() {
+        let long = format!("/{}", ["component"; 30].join("/"));
+        assert_eq!(extended_length(Path::new(&long)), Path::new(&long));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn backslashes_separate_on_windows() {
+        assert_eq!(
+            relative_to_root(r"C:\Users\me\Documents", r"C:\"),
+            Some(r"Users\me\Documents")
+        );
+        assert_eq!(relative_to_root(r"C:\Users\me", r"C:\Users"), Some(r"\me"));
+        let parts: Vec<&str> = components(r"Users\me/Documents").collect();
+        assert_eq!(parts, ["Users", "me", "Documents"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backslashes_are_part_of_names_on_unix() {
+        assert_eq!(relative_to_root("/a\\b", "/a"), None);
+        let parts: Vec<&str> = components("dir\\name/file").collect();
+        assert_eq!(parts, ["dir\\name", "file"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn display_paths_use_backslashes_without_the_prefix() {
+        assert_eq!(
+            display_form(r"\\?\C:\Users/me\a b.txt"),
+            r"C:\Users\me\a b.txt"
+        );
+        assert_eq!(
+            display_form(r"\\?\UNC\server\share/dir"),
+            r"\\server\share\dir"
+        );
+        assert_eq!(display_form("C:/dir/file.txt"), r"C:\dir\file.txt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn display_paths_are_left_as_typed_on_unix() {
+        assert_eq!(display_form("/home/me/a b.txt"), "/home/me/a b.txt");
+        assert_eq!(display_form("/tmp/dir\\name"), "/tmp/dir\\name");
+        assert_eq!(display_form("/tmp/100%25"), "/tmp/100%");
+        // Undecodable bytes are replaced rather than shown escaped
+        assert_eq!(display_form("/tmp/a%FF"), "/tmp/a\u{FFFD}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn encoding_round_trips_any_name() {
+        let names: [&[u8]; 6] = [
+            b"plain",
+            b"a\xFFb",
+            b"a%FF",
+            b"100%",
+            b"%25",
+            b"caf\xC3\xA9 \xE9t\xE9",
+        ];
+        for name in names {
+            let path = Path::new(OsStr::from_bytes(name));
+            let encoded = encode_path(path);
+            assert_eq!(decode_path(&encoded), path, "{:?} as {}", path, encoded);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn encoding_escapes_percent_signs() {
+        assert_eq!(encode_os_str(OsStr::new("a%FF")), "a%25FF");
+        assert_eq!(encode_os_str(OsStr::from_bytes(b"a\xFF")), "a%FF");
+        assert_eq!(encode_os_str(OsStr::new("plain")), "plain");
+    }
+
+    #[test]
+    fn malformed_escapes_are_taken_literally() {
+        assert_eq!(decode_path("/tmp/100%"), Path::new("/tmp/100%"));
+        assert_eq!(decode_path("/tmp/%zz"), Path::new("/tmp/%zz"));
+    }
+}
@@ -1,16 +1,291 @@
-use tauri_plugin_dialog::{DialogExt, FilePath};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 
-#[tauri::command]
-pub async fn select_directory(app: tauri::AppHandle) -> Result<Option<String>, String> {
-    let file_path = app
-        .dialog()
+use serde::Serialize;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_dialog::{DialogExt, FileDialogBuilder, FilePath};
+use tokio::sync::oneshot;
+
+use crate::error::ScanError;
+use crate::paths::encode_path;
+
+/// Linux directories a normal user usually cannot read, probed by
+/// `permission_report` along with every home directory
+const PROTECTED_DIRS: &[&str] = &[
+    "/root",
+    "/lost+found",
+    "/etc/ssl/private",
+    "/var/cache/ldconfig",
+    "/var/lib/private",
+    "/var/log/private",
+    "/var/spool/cron",
+];
+
+/// Directories a scan would be denied access to, see `permission_report`
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionReport {
+    /// Probed directories that could not be read
+    pub inaccessible: Vec<String>,
+    /// Number of existing directories probed
+    pub checked: usize,
+}
+
+/// Tries to list each of `dirs`, reporting the ones permission is denied
+/// for. Directories that do not exist are not counted.
+fn probe_dirs(dirs: impl IntoIterator<Item = PathBuf>) -> PermissionReport {
+    let mut report = PermissionReport {
+        inaccessible: Vec::new(),
+        checked: 0,
+    };
+    for dir in dirs {
+        match fs::read_dir(&dir) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                report.inaccessible.push(encode_path(&dir));
+            }
+            Err(_) => continue,
+        }
+        report.checked += 1;
+    }
+    report
+}
+
+/// Whether the process token is elevated, i.e. has administrator rights
+/// under UAC
+#[cfg(windows)]
+fn elevated() -> io::Result<bool> {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let mut token: HANDLE = std::ptr::null_mut();
+    // SAFETY: the pseudo handle of the current process is always valid
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut length = 0;
+    // SAFETY: `elevation` is as large as the size passed along with it
+    let found = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut length,
+        )
+    };
+    let error = io::Error::last_os_error();
+    // SAFETY: `token` was opened above and is not used afterwards
+    unsafe { CloseHandle(token) };
+    if found == 0 {
+        return Err(error);
+    }
+    Ok(elevation.TokenIsElevated != 0)
+}
+
+/// Whether the process runs as root
+#[cfg(unix)]
+fn elevated() -> io::Result<bool> {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    Ok(unsafe { libc::geteuid() } == 0)
+}
+
+fn protected_dirs() -> Vec<PathBuf> {
+    let homes = fs::read_dir("/home")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path());
+    PROTECTED_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(homes)
+        .collect()
+}
+
+fn folder_dialog<R: Runtime>(app: &AppHandle<R>) -> FileDialogBuilder<R> {
+    app.dialog()
         .file()
         .set_title("Select a Directory to Scan")
         .set_directory("/")
-        .blocking_pick_folder();
+}
 
-    Ok(file_path.map(|p| match p {
-        FilePath::Path(path_buf) => path_buf.to_string_lossy().to_string(),
+fn file_path_to_string(file_path: FilePath) -> String {
+    match file_path {
+        FilePath::Path(path_buf) => encode_path(&path_buf),
         FilePath::Url(url) => url.to_string(),
-    }))
+    }
+}
+
+#[tauri::command]
+pub async fn select_directory(app: AppHandle) -> Result<Option<String>, ScanError> {
+    // Wait on the dialog callback instead of blocking an async worker thread
+    let (sender, receiver) = oneshot::channel();
+    folder_dialog(&app).pick_folder(move |file_path| {
+        let _ = sender.send(file_path);
+    });
+
+    Ok(picked_folder(receiver.await))
+}
+
+/// The folder the dialog answered with, none when it was cancelled
+fn picked_folder(answer: Result<Option<FilePath>, oneshot::error::RecvError>) -> Option<String> {
+    // A dropped sender means the dialog went away, treat it as cancelled
+    answer.unwrap_or(None).map(file_path_to_string)
+}
+
+/// Lets the user pick several directories at once. Cancelling yields an
+/// empty list.
+#[tauri::command]
+pub async fn select_directories(app: AppHandle) -> Result<Vec<String>, ScanError> {
+    let (sender, receiver) = oneshot::channel();
+    folder_dialog(&app).pick_folders(move |file_paths| {
+        let _ = sender.send(file_paths);
+    });
+
+    Ok(picked_paths(receiver.await.unwrap_or(None)))
+}
+
+/// The paths picked in a multiple selection dialog, none when it was
+/// cancelled
+fn picked_paths(file_paths: Option<Vec<FilePath>>) -> Vec<String> {
+    file_paths
+        .unwrap_or_default()
+        .into_iter()
+        .map(file_path_to_string)
+        .collect()
+}
+
+/// Checks whether scanning `/` would run into directories the app may not
+/// read, so the UI can suggest running with elevated privileges. Needs no
+/// privileges itself. Only Linux is probed: macOS has Full Disk Access for
+/// this, and elsewhere the report is empty.
+#[tauri::command]
+pub async fn permission_report() -> Result<PermissionReport, ScanError> {
+    let dirs = if cfg!(target_os = "linux") {
+        protected_dirs()
+    } else {
+        Vec::new()
+    };
+    Ok(probe_dirs(dirs))
+}
+
+/// Whether the app runs with administrator rights (an elevated token on
+/// Windows, root elsewhere), so the UI can offer to restart elevated
+/// before scanning a whole system drive
+#[tauri::command]
+pub async fn is_elevated() -> Result<bool, ScanError> {
+    Ok(elevated()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn the_picked_folder_arrives_whenever_the_dialog_answers() {
+        let (sender, receiver) = oneshot::channel();
+        let dialog = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let _ = sender.send(Some(FilePath::Path(PathBuf::from("/home/user"))));
+        });
+        assert_eq!(
+            picked_folder(receiver.blocking_recv()),
+            Some(encode_path(Path::new("/home/user")))
+        );
+        dialog.join().unwrap();
+    }
+
+    #[test]
+    fn closed_or_cancelled_dialogs_pick_nothing() {
+        let (sender, receiver) = oneshot::channel::<Option<FilePath>>();
+        assert!(sender.send(None).is_ok());
+        assert_eq!(picked_folder(receiver.blocking_recv()), None);
+        let (sender, receiver) = oneshot::channel::<Option<FilePath>>();
+        drop(sender);
+        assert_eq!(picked_folder(receiver.blocking_recv()), None);
+    }
+
+    #[test]
+    fn cancelled_selections_are_empty() {
+        assert!(picked_paths(None).is_empty());
+        assert!(picked_paths(Some(Vec::new())).is_empty());
+    }
+
+    #[test]
+    fn picked_directories_are_returned_in_order() {
+        let picked = picked_paths(Some(vec![
+            FilePath::Path(PathBuf::from("/home/user/b")),
+            FilePath::Path(PathBuf::from("/home/user/a")),
+        ]));
+        assert_eq!(
+            picked,
+            [
+                encode_path(Path::new("/home/user/b")),
+                encode_path(Path::new("/home/user/a")),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directories_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let locked = dir.path().join("locked");
+        let open = dir.path().join("open");
+        fs::create_dir(&locked).unwrap();
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let report = probe_dirs([locked.clone(), open, dir.path().join("missing")]);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // The missing directory is not counted
+        assert_eq!(report.checked, 2);
+        // Permissions do not stop root
+        if elevated().unwrap() {
+            assert!(report.inaccessible.is_empty());
+        } else {
+            assert_eq!(report.inaccessible, [encode_path(&locked)]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn elevation_matches_what_permissions_allow() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = fs::read_dir(&locked).is_ok();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // Only root reads directories without permissions
+        assert_eq!(elevated().unwrap(), readable);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn elevation_can_be_queried() {
+        // Either answer is fine, the token query itself must not fail
+        assert!(elevated().is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn home_directories_are_probed() {
+        let dirs = protected_dirs();
+        assert!(dirs.contains(&PathBuf::from("/root")));
+        for home in fs::read_dir("/home").into_iter().flatten().flatten() {
+            assert!(dirs.contains(&home.path()));
+        }
+    }
 }
@@ -0,0 +1,345 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::disk_scanner::{DiskScanner, ScanOptions};
+use crate::error::ScanError;
+use crate::paths::{canonicalize, decode_path};
+
+/// Name of the settings file in the app config directory
+const SETTINGS_FILE: &str = "settings.json";
+
+/// How sizes are displayed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SizeUnits {
+    /// Powers of 1024 (KiB, MiB, ...)
+    #[default]
+    Binary,
+    /// Powers of 1000 (KB, MB, ...)
+    Decimal,
+}
+
+impl SizeUnits {
+    /// Formats a byte count with one decimal, e.g. `4.2 GB` or `4.0 GiB`.
+    /// Counts below the first unit stay in bytes.
+    pub fn format(self, bytes: u64) -> String {
+        let (base, units) = match self {
+            Self::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB"]),
+            Self::Decimal => (1000.0, ["B", "KB", "MB", "GB", "TB", "PB"]),
+        };
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        // Move up a unit as soon as rounding would print e.g. `1000.0 KB`
+        while unit + 1 < units.len() && value >= base - 0.05 {
+            value /= base;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", bytes)
+        } else {
+            format!("{:.1} {}", value, units[unit])
+        }
+    }
+}
+
+/// User preferences kept between launches. Fields missing from the file
+/// (e.g. written by an older version) take their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    pub units: SizeUnits,
+    pub include_hidden: bool,
+    pub exclude_globs: Vec<String>,
+    /// Cap on scanning threads, `None` for one per logical CPU
+    pub max_threads: Option<usize>,
+    /// Whether system drives are scanned in the background on launch
+    pub warm_up_on_launch: bool,
+    /// Paths that are never deleted or moved, along with everything in them
+    pub protected_paths: Vec<String>,
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        Self {
+            units: SizeUnits::default(),
+            include_hidden: true,
+            exclude_globs: Vec::new(),
+            max_threads: None,
+            warm_up_on_launch: false,
+            protected_paths: Vec::new(),
+        }
+    }
+}
+
+impl ScanSettings {
+    /// Options for scans that are not given any explicitly
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            include_hidden: self.include_hidden,
+            exclude_globs: self.exclude_globs.clone(),
+            max_threads: self.max_threads,
+            ..ScanOptions::default()
+        }
+    }
+}
+
+fn settings_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, ScanError> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(SETTINGS_FILE))
+        .map_err(|e| ScanError::Io(format!("Failed to find the config directory: {}", e)))
+}
+
+/// Reads the stored settings, falling back to the defaults when there are
+/// none yet or the file cannot be parsed
+pub fn load<R: Runtime>(app: &AppHandle<R>) -> ScanSettings {
+    settings_path(app).map_or_else(|_| ScanSettings::default(), |path| read(&path))
+}
+
+/// Options for a scan, `options` when given and the stored settings otherwise
+pub fn scan_options<R: Runtime>(app: &AppHandle<R>, options: Option<ScanOptions>) -> ScanOptions {
+    options.unwrap_or_else(|| load(app).scan_options())
+}
+
+fn save<R: Runtime>(app: &AppHandle<R>, settings: &ScanSettings) -> Result<(), ScanError> {
+    write(&settings_path(app)?, settings)
+}
+
+fn read(path: &Path) -> ScanSettings {
+    read_stored(path).unwrap_or_default()
+}
+
+/// Reads the settings stored at `path`, the defaults if there are none yet.
+/// Unlike `read`, a file that cannot be read or parsed is an error.
+fn read_stored(path: &Path) -> Result<ScanSettings, ScanError> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ScanSettings::default()),
+        Err(e) => {
+            return Err(ScanError::io_with(
+                format!("Failed to read {}", path.display()),
+                e,
+            ))
+        }
+    };
+    serde_json::from_str(&json)
+        .map_err(|e| ScanError::Io(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Like `read_stored`, for the settings file of `app`
+fn load_stored<R: Runtime>(app: &AppHandle<R>) -> Result<ScanSettings, ScanError> {
+    settings_path(app).map_or_else(|_| Ok(ScanSettings::default()), |path| read_stored(&path))
+}
+
+/// Writes `settings` to `path`, keeping the protected paths stored there.
+/// Those only change through `add_protected_path` and
+/// `remove_protected_path`, so settings saved without them cannot clear
+/// them.
+fn write_keeping_protected(path: &Path, mut settings: ScanSettings) -> Result<(), ScanError> {
+    settings.protected_paths = read_stored(path)?.protected_paths;
+    write(path, &settings)
+}
+
+fn write(path: &Path, settings: &ScanSettings) -> Result<(), ScanError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| ScanError::io_with(format!("Failed to create {}", dir.display()), e))?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| ScanError::Io(e.to_string()))?;
+    fs::write(path, json)
+        .map_err(|e| ScanError::io_with(format!("Failed to write {}", path.display()), e))
+}
+
+#[tauri::command]
+pub async fn load_settings(app: AppHandle) -> Result<ScanSettings, ScanError> {
+    Ok(load(&app))
+}
+
+/// Stores `settings` and applies the ones the scanner uses right away. The
+/// protected paths already stored are kept, whatever `settings` holds.
+#[tauri::command]
+pub async fn save_settings(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    settings: ScanSettings,
+) -> Result<(), ScanError> {
+    let max_threads = settings.max_threads;
+    write_keeping_protected(&settings_path(&app)?, settings)?;
+    scanner.set_max_threads(max_threads)
+}
+
+/// Resolved protected paths, see `ScanSettings::protected_paths`. When the
+/// settings file cannot be read, which paths are protected is unknown and
+/// deletions are refused rather than run unprotected.
+pub fn protected_paths<R: Runtime>(app: &AppHandle<R>) -> Result<Vec<PathBuf>, ScanError> {
+    let settings = load_stored(app).map_err(|e| {
+        ScanError::Refused(format!(
+            "Refusing to delete anything while the protected paths are unknown: {}",
+            e
+        ))
+    })?;
+    Ok(settings
+        .protected_paths
+        .iter()
+        .map(|path| {
+            let path = decode_path(path);
+            fs::canonicalize(&path).unwrap_or(path)
+        })
+        .collect())
+}
+
+/// Refuses `path` when it is one of `protected`, inside one of them or
+/// contains one, since removing a parent removes what is below it too
+pub fn check_protected(protected: &[PathBuf], path: &Path) -> Result<(), ScanError> {
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    match protected.iter().find(|protected| {
+        path.starts_with(protected)
+            || resolved.starts_with(protected)
+            || protected.starts_with(path)
+            || protected.starts_with(&resolved)
+    }) {
+        Some(protected) => Err(ScanError::Refused(format!(
+            "Refusing to touch {}: {} is protected",
+            path.display(),
+            protected.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn list_protected_paths(app: AppHandle) -> Result<Vec<String>, ScanError> {
+    Ok(load_stored(&app)?.protected_paths)
+}
+
+/// Protects `path` from deletion and returns the updated list
+#[tauri::command]
+pub async fn add_protected_path(app: AppHandle, path: String) -> Result<Vec<String>, ScanError> {
+    let path = canonicalize(&path).unwrap_or(path);
+    let mut settings = load_stored(&app)?;
+    if !settings.protected_paths.contains(&path) {
+        settings.protected_paths.push(path);
+        save(&app, &settings)?;
+    }
+    Ok(settings.protected_paths)
+}
+
+/// Lifts the protection of `path` and returns the updated list
+#[tauri::command]
+pub async fn remove_protected_path(app: AppHandle, path: String) -> Result<Vec<String>, ScanError> {
+    let canonical = canonicalize(&path);
+    let mut settings = load_stored(&app)?;
+    settings
+        .protected_paths
+        .retain(|protected| *protected != path && Some(protected) != canonical.as_ref());
+    save(&app, &settings)?;
+    Ok(settings.protected_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn sizes_format_in_either_unit() {
+        assert_eq!(SizeUnits::Decimal.format(1536), "1.5 KB");
+        assert_eq!(SizeUnits::Binary.format(1536), "1.5 KiB");
+        assert_eq!(SizeUnits::Decimal.format(999), "999 B");
+        assert_eq!(SizeUnits::Binary.format(1023), "1023 B");
+        // Rounds up into the next unit rather than printing `1000.0 KB`
+        assert_eq!(SizeUnits::Decimal.format(999_960), "1.0 MB");
+        assert_eq!(SizeUnits::Binary.format(u64::MAX), "16384.0 PiB");
+    }
+
+    #[test]
+    fn settings_round_trip_through_the_config_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config").join(SETTINGS_FILE);
+        let settings = ScanSettings {
+            units: SizeUnits::Decimal,
+            include_hidden: false,
+            exclude_globs: vec!["**/node_modules".to_string()],
+            max_threads: Some(2),
+            warm_up_on_launch: true,
+            protected_paths: vec!["/srv".to_string()],
+        };
+        write(&path, &settings).unwrap();
+        let read = read(&path);
+        assert_eq!(read.units, SizeUnits::Decimal);
+        assert!(!read.include_hidden);
+        assert_eq!(read.exclude_globs, settings.exclude_globs);
+        assert_eq!(read.max_threads, Some(2));
+        assert!(read.warm_up_on_launch);
+        assert_eq!(read.protected_paths, settings.protected_paths);
+    }
+
+    #[test]
+    fn missing_or_broken_files_give_the_defaults() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        assert!(read(&path).include_hidden);
+        fs::write(&path, "{ not json").unwrap();
+        assert!(read(&path).include_hidden);
+        fs::write(&path, r#"{"include_hidden": false}"#).unwrap();
+        let partial = read(&path);
+        assert!(!partial.include_hidden);
+        assert_eq!(partial.units, SizeUnits::Binary);
+    }
+
+    #[test]
+    fn saving_settings_keeps_the_stored_protected_paths() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        let protected = vec!["/srv".to_string()];
+        write(
+            &path,
+            &ScanSettings {
+                protected_paths: protected.clone(),
+                ..ScanSettings::default()
+            },
+        )
+        .unwrap();
+
+        // As sent by a frontend that leaves the list out
+        let settings: ScanSettings = serde_json::from_str(r#"{"units": "Decimal"}"#).unwrap();
+        write_keeping_protected(&path, settings).unwrap();
+        let stored = read(&path);
+        assert_eq!(stored.units, SizeUnits::Decimal);
+        assert_eq!(stored.protected_paths, protected);
+    }
+
+    #[test]
+    fn broken_files_keep_their_protected_paths() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        assert!(read_stored(&path).unwrap().protected_paths.is_empty());
+
+        fs::write(&path, r#"{"protected_paths": ["/srv"], "#).unwrap();
+        assert!(matches!(read_stored(&path), Err(ScanError::Io(_))));
+        // Saving over it would lose the list, so nothing is written
+        assert!(write_keeping_protected(&path, ScanSettings::default()).is_err());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            r#"{"protected_paths": ["/srv"], "#
+        );
+    }
+
+    #[test]
+    fn stored_settings_become_the_default_scan_options() {
+        let settings = ScanSettings {
+            include_hidden: false,
+            exclude_globs: vec!["*.tmp".to_string()],
+            max_threads: Some(3),
+            ..ScanSettings::default()
+        };
+        let options = settings.scan_options();
+        assert!(!options.include_hidden);
+        assert_eq!(options.exclude_globs, ["*.tmp"]);
+        assert_eq!(options.max_threads, Some(3));
+    }
+}
@@ -0,0 +1,267 @@
+use std::io;
+use std::path::Path;
+
+/// Moves `path` to the trash of the current platform, keeping whatever
+/// metadata its trash UI needs to restore the item
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    platform::move_to_trash(path)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    use objc2_foundation::{NSFileManager, NSString, NSURL};
+
+    pub fn move_to_trash(path: &Path) -> io::Result<()> {
+        let url = NSURL::fileURLWithPath(&NSString::from_str(&path.to_string_lossy()));
+        NSFileManager::defaultManager()
+            .trashItemAtURL_resultingItemURL_error(&url, None)
+            .map_err(|e| io::Error::other(e.localizedDescription().to_string()))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use windows_sys::Win32::UI::Shell::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE,
+        SHFILEOPSTRUCTW,
+    };
+
+    pub fn move_to_trash(path: &Path) -> io::Result<()> {
+        // The shell wants a plain absolute path (no `\\?\` prefix), given as
+        // a list terminated by an empty entry
+        let path = std::path::absolute(path)?;
+        let mut from: Vec<u16> = path.as_os_str().encode_wide().collect();
+        from.extend([0, 0]);
+
+        let mut operation = SHFILEOPSTRUCTW {
+            hwnd: std::ptr::null_mut(),
+            wFunc: FO_DELETE,
+            pFrom: from.as_ptr(),
+            pTo: std::ptr::null(),
+            fFlags: (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as u16,
+            fAnyOperationsAborted: 0,
+            hNameMappings: std::ptr::null_mut(),
+            lpszProgressTitle: std::ptr::null(),
+        };
+        // SAFETY: `from` is double NUL-terminated and outlives the call, the
+        // other pointers are null, which the shell accepts for them
+        let result = unsafe { SHFileOperationW(&mut operation) };
+        if result != 0 {
+            return Err(io::Error::other(format!(
+                "SHFileOperation failed with code {:#x}",
+                result
+            )));
+        }
+        if operation.fAnyOperationsAborted != 0 {
+            return Err(io::Error::other("Moving to the Recycle Bin was aborted"));
+        }
+        Ok(())
+    }
+}
+
+/// Follows the freedesktop.org trash specification
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+
+    use crate::paths::uri_path;
+
+    /// `$XDG_DATA_HOME/Trash`, defaulting to `~/.local/share/Trash`
+    fn home_trash() -> Option<PathBuf> {
+        let non_empty = |var: &str| std::env::var_os(var).filter(|value| !value.is_empty());
+        non_empty("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+            .map(|data| data.join("Trash"))
+    }
+
+    /// Topmost directory of the filesystem `path` is on
+    fn mount_top(path: &Path, dev: u64) -> &Path {
+        let mut top = path;
+        while let Some(parent) = top.parent() {
+            match fs::symlink_metadata(parent) {
+                Ok(metadata) if metadata.dev() == dev => top = parent,
+                _ => break,
+            }
+        }
+        top
+    }
+
+    /// `home_trash` if it is on the same filesystem as `path`, so the item
+    /// can be renamed into it, otherwise `$topdir/.Trash-$uid`
+    fn trash_dir(path: &Path, dev: u64, home_trash: Option<PathBuf>) -> io::Result<PathBuf> {
+        if let Some(trash) = home_trash {
+            fs::create_dir_all(&trash)?;
+            if fs::metadata(&trash)?.dev() == dev {
+                return Ok(trash);
+            }
+        }
+        // SAFETY: getuid cannot fail and has no preconditions
+        let uid = unsafe { libc::getuid() };
+        Ok(mount_top(path, dev).join(format!(".Trash-{}", uid)))
+    }
+
+    /// Current local time as `YYYY-MM-DDThh:mm:ss`
+    fn deletion_date() -> String {
+        // SAFETY: `tm` is plain data, for which all zeroes is a valid value
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        // SAFETY: time accepts a null pointer, and localtime_r only writes
+        // to the `tm` it is given, unlike localtime
+        unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&now, &mut tm);
+        }
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+
+    pub fn move_to_trash(path: &Path) -> io::Result<()> {
+        trash_into(path, home_trash()).map(|_| ())
+    }
+
+    /// Moves `path` into the trash directory for it, see `trash_dir`, and
+    /// returns where it ended up
+    fn trash_into(path: &Path, home_trash: Option<PathBuf>) -> io::Result<PathBuf> {
+        let path = std::path::absolute(path)?;
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot trash a filesystem root",
+            )
+        })?;
+        let trash = trash_dir(&path, fs::symlink_metadata(&path)?.dev(), home_trash)?;
+        let files = trash.join("files");
+        let info = trash.join("info");
+        fs::create_dir_all(&files)?;
+        fs::create_dir_all(&info)?;
+
+        for n in 1u32.. {
+            let mut trashed_name = name.to_os_string();
+            if n > 1 {
+                trashed_name.push(format!(".{}", n));
+            }
+            let mut info_name = trashed_name.clone();
+            info_name.push(".trashinfo");
+
+            // Creating the info file first reserves the name, as the spec
+            // requires
+            let info_path = info.join(info_name);
+            let mut info_file = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            let trashed = files.join(&trashed_name);
+            if fs::symlink_metadata(&trashed).is_ok() {
+                let _ = fs::remove_file(&info_path);
+                continue;
+            }
+
+            let moved = write!(
+                info_file,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                uri_path(&path),
+                deletion_date()
+            )
+            .and_then(|()| fs::rename(&path, &trashed));
+            if let Err(e) = moved {
+                let _ = fs::remove_file(&info_path);
+                return Err(e);
+            }
+            return Ok(trashed);
+        }
+        unreachable!("Ran out of trash names")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use tempfile::TempDir;
+
+        use super::*;
+
+        #[test]
+        fn items_are_trashed_with_their_restore_info() {
+            let dir = TempDir::new().unwrap();
+            let trash = dir.path().join("Trash");
+            let item = dir.path().join("my file");
+            fs::write(&item, b"data").unwrap();
+
+            let trashed = trash_into(&item, Some(trash.clone())).unwrap();
+            assert_eq!(trashed, trash.join("files/my file"));
+            assert!(!item.exists());
+            assert_eq!(fs::read(&trashed).unwrap(), b"data");
+
+            let info = fs::read_to_string(trash.join("info/my file.trashinfo")).unwrap();
+            let lines: Vec<&str> = info.lines().collect();
+            assert_eq!(lines[0], "[Trash Info]");
+            assert_eq!(lines[1], format!("Path={}", uri_path(&item)));
+            let date = lines[2].strip_prefix("DeletionDate=").unwrap();
+            assert_eq!(date.len(), "2024-01-01T00:00:00".len());
+            assert_eq!(date.as_bytes()[10], b'T');
+        }
+
+        #[test]
+        fn trashed_names_do_not_collide() {
+            let dir = TempDir::new().unwrap();
+            let trash = dir.path().join("Trash");
+            for _ in 0..2 {
+                let item = dir.path().join("file");
+                fs::write(&item, b"data").unwrap();
+                trash_into(&item, Some(trash.clone())).unwrap();
+            }
+            assert!(trash.join("files/file").exists());
+            assert!(trash.join("files/file.2").exists());
+            assert!(trash.join("info/file.2.trashinfo").exists());
+        }
+
+        #[test]
+        fn filesystem_roots_are_not_trashed() {
+            let error = trash_into(Path::new("/"), None).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[cfg(any(target_os = "macos", windows))]
+    #[test]
+    fn trashed_files_leave_their_directory() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("trashed");
+        std::fs::write(&file, b"data").unwrap();
+        move_to_trash(&file).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn missing_files_cannot_be_trashed() {
+        let dir = TempDir::new().unwrap();
+        assert!(move_to_trash(&dir.path().join("missing")).is_err());
+    }
+}
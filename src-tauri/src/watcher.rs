@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::warn;
+
+use crate::disk_scanner::{DiskScanner, ProgressTracker};
+use crate::error::ScanError;
+use crate::paths::{canonicalize, decode_path, encode_path};
+
+/// Quiet period after the last filesystem event before rescanning
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest a batch of events is held back, so a file that is written to
+/// without pause still gets its changes applied
+const MAX_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// Payload of the `cache-updated` event
+#[derive(Clone, Serialize)]
+pub struct CacheUpdate {
+    pub root: String,
+    pub paths: Vec<String>,
+}
+
+/// Payload of the `node-removed` event
+#[derive(Clone, Serialize)]
+pub struct NodeRemoved {
+    pub root: String,
+    pub path: String,
+}
+
+/// Active filesystem watchers keyed by watched root
+#[derive(Default)]
+pub struct Watchers {
+    watchers: Mutex<HashMap<String, RecommendedWatcher>>,
+}
+
+/// Directories to rescan for a batch of changed paths: the parent of each
+/// path, clamped to the watched root, without any directory already covered
+/// by another one in the batch
+fn affected_dirs(root: &Path, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| match path.parent() {
+            Some(parent) if parent.starts_with(root) => parent.to_path_buf(),
+            _ => root.to_path_buf(),
+        })
+        .collect();
+    // Component-wise ordering puts every directory right before its
+    // descendants, so comparing against the last kept one is enough
+    dirs.sort();
+    dirs.dedup();
+
+    let mut affected: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !affected.last().is_some_and(|kept| dir.starts_with(kept)) {
+            affected.push(dir);
+        }
+    }
+    affected
+}
+
+/// Waits for the next filesystem event and collects the paths of the events
+/// that follow it until none arrive for `debounce`, or `max_wait` has passed
+/// since the first. Returns `None` once the sender has been dropped.
+fn next_batch(
+    receiver: &mpsc::Receiver<notify::Result<Event>>,
+    debounce: Duration,
+    max_wait: Duration,
+) -> Option<Vec<PathBuf>> {
+    let first = receiver.recv().ok()?;
+    let started = Instant::now();
+    let mut paths = Vec::new();
+    let mut next = Some(first);
+    while let Some(result) = next {
+        if let Ok(event) = result {
+            if !event.kind.is_access() {
+                paths.extend(event.paths);
+            }
+        }
+        let remaining = max_wait.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        next = receiver.recv_timeout(debounce.min(remaining)).ok();
+    }
+    Some(paths)
+}
+
+/// Paths dropped from and directories rescanned in the cache for a batch
+/// of changes under `root`
+#[derive(Debug, Default)]
+struct AppliedChanges {
+    removed: Vec<String>,
+    updated: Vec<String>,
+}
+
+/// Drops the paths that are gone from the cache and rescans the directories
+/// of the others. Rescans report to no one, they are not scans the user
+/// started.
+fn apply_changes(scanner: &DiskScanner, root: &Path, paths: Vec<PathBuf>) -> AppliedChanges {
+    let mut applied = AppliedChanges::default();
+    let (mut removed, changed): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .into_iter()
+        .partition(|path| fs::symlink_metadata(path).is_err());
+    // Parents sort before their descendants, which are then no longer
+    // cached and not reported on their own
+    removed.sort();
+    removed.dedup();
+    for path in removed {
+        let path = encode_path(&path);
+        if scanner.remove_node(&path).is_ok() {
+            applied.removed.push(path);
+        }
+    }
+    if changed.is_empty() {
+        return applied;
+    }
+
+    let tracker = Arc::new(ProgressTracker::silent());
+    applied.updated = affected_dirs(root, changed)
+        .into_iter()
+        .map(|dir| encode_path(&dir))
+        .filter(|dir| match scanner.rescan_subtree_tracked(dir, &tracker) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(path = %dir, error = %e, "Failed to rescan changed directory");
+                false
+            }
+        })
+        .collect();
+    applied
+}
+
+/// Debounces events for one watched root and applies them to the cache,
+/// emitting `node-removed` for each path dropped and `cache-updated` for
+/// the rescanned directories. Returns once the watcher, and with it the
+/// sender, has been dropped.
+fn process_events(app: AppHandle, root: String, receiver: mpsc::Receiver<notify::Result<Event>>) {
+    while let Some(paths) = next_batch(&receiver, DEBOUNCE, MAX_DEBOUNCE) {
+        if paths.is_empty() {
+            continue;
+        }
+        let scanner = app.state::<DiskScanner>();
+        let applied = apply_changes(&scanner, &decode_path(&root), paths);
+        for path in applied.removed {
+            let _ = app.emit(
+                "node-removed",
+                NodeRemoved {
+                    root: root.clone(),
+                    path,
+                },
+            );
+        }
+        if !applied.updated.is_empty() {
+            let _ = app.emit(
+                "cache-updated",
+                CacheUpdate {
+                    root: root.clone(),
+                    paths: applied.updated,
+                },
+            );
+        }
+    }
+}
+
+/// Keeps the cached tree for `path` up to date with changes on disk,
+/// emitting `node-removed` for deleted entries and `cache-updated` after
+/// each batch of other changes is applied
+#[tauri::command]
+pub async fn watch_path(
+    app: AppHandle,
+    scanner: State<'_, DiskScanner>,
+    watchers: State<'_, Watchers>,
+    path: String,
+) -> Result<(), ScanError> {
+    let path = canonicalize(&path).unwrap_or(path);
+    if !scanner.contains_path(&decode_path(&path)) {
+        return Err(ScanError::NotCached);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| ScanError::Io(e.to_string()))?;
+    watcher
+        .watch(&decode_path(&path), RecursiveMode::Recursive)
+        .map_err(|e| ScanError::Io(e.to_string()))?;
+
+    let root = path.clone();
+    thread::spawn(move || process_events(app, root, receiver));
+    // Replacing an existing watcher for the same path stops the old one
+    watchers.watchers.lock().insert(path, watcher);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unwatch_path(watchers: State<'_, Watchers>, path: String) -> Result<(), ScanError> {
+    let path = canonicalize(&path).unwrap_or(path);
+    watchers.watchers.lock().remove(&path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{EventKind, ModifyKind};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::disk_scanner::ScanOptions;
+
+    /// A scanner with `dir` cached, and the canonical path of `dir`
+    fn scanned(dir: &Path) -> (DiskScanner, PathBuf) {
+        let dir = fs::canonicalize(dir).unwrap();
+        let scanner = DiskScanner::new();
+        scanner
+            .build_cache_tracked(
+                &encode_path(&dir),
+                ScanOptions::default(),
+                Arc::new(ProgressTracker::silent()),
+            )
+            .unwrap();
+        (scanner, dir)
+    }
+
+    fn apparent_size(scanner: &DiskScanner, path: &Path) -> u64 {
+        scanner
+            .with_node(&encode_path(path), |node| node.apparent_size)
+            .unwrap()
+    }
+
+    #[test]
+    fn affected_dirs_keep_only_the_outermost_directories() {
+        let root = Path::new("/r");
+        let dirs = affected_dirs(
+            root,
+            vec![
+                PathBuf::from("/r/a/b/file"),
+                PathBuf::from("/r/a/file"),
+                PathBuf::from("/r/ab/file"),
+                PathBuf::from("/r"),
+            ],
+        );
+        assert_eq!(dirs, [PathBuf::from("/r")]);
+        let dirs = affected_dirs(
+            root,
+            vec![PathBuf::from("/r/a/b/file"), PathBuf::from("/r/ab/file")],
+        );
+        assert_eq!(dirs, [PathBuf::from("/r/a/b"), PathBuf::from("/r/ab")]);
+    }
+
+    #[test]
+    fn new_files_under_a_watched_dir_grow_the_cache() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/old"), b"old").unwrap();
+        let (scanner, root) = scanned(dir.path());
+        assert_eq!(apparent_size(&scanner, &root), 3);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).unwrap();
+        watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+        fs::write(root.join("sub/new"), vec![0; 1000]).unwrap();
+
+        let paths = next_batch(&receiver, Duration::from_millis(200), MAX_DEBOUNCE).unwrap();
+        let applied = apply_changes(&scanner, &root, paths);
+        assert_eq!(applied.updated, [encode_path(&root.join("sub"))]);
+        assert_eq!(apparent_size(&scanner, &root), 1003);
+        assert_eq!(apparent_size(&scanner, &root.join("sub/new")), 1000);
+    }
+
+    #[test]
+    fn deleted_files_are_dropped_from_the_cache() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("gone"), b"data").unwrap();
+        fs::write(dir.path().join("kept"), b"kept").unwrap();
+        let (scanner, root) = scanned(dir.path());
+        fs::remove_file(root.join("gone")).unwrap();
+
+        let applied = apply_changes(&scanner, &root, vec![root.join("gone")]);
+        assert_eq!(applied.removed, [encode_path(&root.join("gone"))]);
+        assert!(applied.updated.is_empty());
+        assert_eq!(apparent_size(&scanner, &root), 4);
+    }
+
+    #[test]
+    fn watched_deletions_shrink_the_cache_once_per_tree() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("tree/sub")).unwrap();
+        fs::write(dir.path().join("tree/sub/file"), vec![0; 1000]).unwrap();
+        fs::write(dir.path().join("tree/file"), vec![0; 1000]).unwrap();
+        fs::write(dir.path().join("kept"), b"kept").unwrap();
+        let (scanner, root) = scanned(dir.path());
+        assert_eq!(apparent_size(&scanner, &root), 2004);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).unwrap();
+        watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+        fs::remove_dir_all(root.join("tree")).unwrap();
+
+        let paths = next_batch(&receiver, Duration::from_millis(200), MAX_DEBOUNCE).unwrap();
+        let applied = apply_changes(&scanner, &root, paths);
+        // What was inside the tree went with it and is not reported apart
+        assert_eq!(applied.removed, [encode_path(&root.join("tree"))]);
+        assert_eq!(apparent_size(&scanner, &root), 4);
+        assert_eq!(
+            scanner.with_node(&encode_path(&root.join("tree")), |_| ()),
+            Err(ScanError::NotCached)
+        );
+    }
+
+    #[test]
+    fn batches_are_flushed_after_the_maximum_wait() {
+        let (sender, receiver) = mpsc::channel();
+        // Keeps writing well past the maximum wait, never leaving a quiet
+        // period long enough to end the batch
+        let writer = thread::spawn(move || {
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(2) {
+                let event = Event::new(EventKind::Modify(ModifyKind::Any)).add_path("/file".into());
+                if sender.send(Ok(event)).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let started = Instant::now();
+        let paths = next_batch(
+            &receiver,
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!paths.is_empty());
+        drop(receiver);
+        writer.join().unwrap();
+    }
+}
@@ -27,6 +27,10 @@ async function invoke<T>(cmd: string, args?: any): Promise<T> {
     return await tauriInvoke<T>(cmd, args)
   } catch (error) {
     console.error(`=== [Frontend] Error invoking Tauri command ${cmd}:`, error)
+    // Scan errors arrive as { kind, message }, surface the message
+    if (error && typeof error === 'object' && 'message' in error) {
+      throw (error as { message: string }).message
+    }
     throw error
   }
 }
@@ -36,16 +40,33 @@ interface FileNode {
   path: string
   size: number
   is_directory: boolean
+  extension?: string | null
   children: FileNode[]
   children_count: number
   show: boolean
+  scanned_at?: number | null
+  size_human?: string
+  size_human_binary?: string
+}
+
+interface DeletionPlan {
+  total_bytes: number
+  file_count: number
+  dir_count: number
+  token: string
 }
 
 interface ScanProgress {
+  job_id: number | null
   scanned_size: number
   file_count: number
 }
 
+interface ScanWarning {
+  kind: 'Network' | 'Optical' | 'Removable'
+  message: string
+}
+
 function App() {
   const [currentPath, setCurrentPath] = useState<string>('')
   const [currentData, setCurrentData] = useState<FileNode | null>(null)
@@ -59,15 +80,23 @@ function App() {
   const [bannerDismissed, setBannerDismissed] = useState(false)
   const [itemsToDelete, setItemsToDelete] = useState<FileNode[]>([])
   const [showDeleteConfirm, setShowDeleteConfirm] = useState(false)
+  const [deletionPlan, setDeletionPlan] = useState<DeletionPlan | null>(null)
   const [isDraggingOver, setIsDraggingOver] = useState(false)
   const [draggedNodes, setDraggedNodes] = useState<Set<string>>(new Set())
   const [isDeleteZoneExpanded, setIsDeleteZoneExpanded] = useState(false)
   const [isDeleting, setIsDeleting] = useState(false)
   const [scanProgress, setScanProgress] = useState<ScanProgress | null>(null)
+  const [units, setUnits] = useState<'Binary' | 'Decimal'>('Binary')
+
+  useEffect(() => {
+    invoke<{ units: 'Binary' | 'Decimal' }>('load_settings')
+      .then(settings => setUnits(settings.units))
+      .catch(err => console.error('Failed to load settings:', err))
+  }, [])
 
   const handleCopyPath = useCallback(async (path: string) => {
     try {
-      await navigator.clipboard.writeText(path)
+      await navigator.clipboard.writeText(await invoke<string>('display_path', { path }))
       setCopied(true)
       setTimeout(() => setCopied(false), 2000)
     } catch (err) {
@@ -115,7 +144,10 @@ function App() {
     try {
       const { listen } = await import('@tauri-apps/api/event')
       const unlisten = await listen<ScanProgress>('scan-progress', (event) => {
-        setScanProgress(event.payload)
+        // Background jobs, like the warm-up, report here too
+        if (event.payload.job_id === null) {
+          setScanProgress(event.payload)
+        }
       })
       try {
         await invoke<string>('build_cache', { path })
@@ -158,6 +190,10 @@ function App() {
 
       const selectedPath = await invoke<string | null>('select_directory')
       if (selectedPath) {
+        const warning = await invoke<ScanWarning | null>('precheck_scan', { path: selectedPath })
+        if (warning?.kind === 'Network' && !window.confirm(`${warning.message}\n\nScan anyway?`)) {
+          return
+        }
         await buildCache(selectedPath)
         await loadDirectoryChildrenWithDepth(selectedPath, maxDepth)
       }
@@ -231,13 +267,42 @@ function App() {
     })
   }
 
+  // Computes what will be removed, so the confirmation shows the real totals
+  // and only the previewed set can be deleted
+  const openDeleteConfirm = async () => {
+    try {
+      const paths = itemsToDelete.map(item => item.path)
+      setDeletionPlan(await invoke<DeletionPlan>('prepare_deletion', { paths }))
+      setShowDeleteConfirm(true)
+    } catch (err) {
+      setError(`Failed to prepare deletion: ${err}`)
+    }
+  }
+
   const handleConfirmDelete = async () => {
+    if (!deletionPlan) return
     try {
       setIsDeleting(true)
       const paths = itemsToDelete.map(item => item.path)
 
-      // Delete from filesystem
-      await invoke('delete_items', { paths })
+      // Delete from filesystem. Files owned by other users can only be
+      // removed as root, which is offered but never done without asking.
+      try {
+        await invoke('delete_items', { paths, token: deletionPlan.token })
+      } catch (err) {
+        if (!String(err).includes('Permission denied') ||
+            !window.confirm(`${err}\n\nRetry deleting as administrator?`)) {
+          throw err
+        }
+        // The first token was used up, the retry needs one of its own.
+        // Paths the first attempt already removed are planned as empty.
+        const { token } = await invoke<DeletionPlan>('prepare_deletion', { paths })
+        const results = await invoke<{ path: string, error: { kind: string, message: string } | null }[]>('delete_items_elevated', { paths, token })
+        const failed = results.filter(result => result.error !== null)
+        if (failed.length > 0) {
+          throw failed.map(result => `${result.path}: ${result.error?.message}`).join('\n')
+        }
+      }
 
       // Remove deleted nodes from current data tree
       if (currentData) {
@@ -289,9 +354,14 @@ function App() {
     } finally {
       setIsDeleting(false)
       setShowDeleteConfirm(false)
+      setDeletionPlan(null)
     }
   }
 
+  // Returned trees carry their sizes pre-formatted in both units
+  const formatNodeSize = (node: FileNode) =>
+    (units === 'Decimal' ? node.size_human : node.size_human_binary) || formatSize(node.size)
+
   const formatSize = (bytes: number) => {
     if (bytes === 0) return '0 B'
     const k = 1024
@@ -447,7 +517,7 @@ function App() {
                 >
                   <Maximize2 size={14} />
                 </button>
-                <div className="delete-summary-icon" onClick={() => setShowDeleteConfirm(true)}>
+                <div className="delete-summary-icon" onClick={openDeleteConfirm}>
                   <Trash2 size={20} className="text-red-400" />
                 </div>
                 <div className="delete-summary-info">
@@ -481,7 +551,7 @@ function App() {
               {/* Left: Delete Button */}
               <div className="flex-1 flex justify-start">
                 <button
-                  onClick={() => setShowDeleteConfirm(true)}
+                  onClick={openDeleteConfirm}
                   className="btn btn-danger"
                   disabled={itemsToDelete.length === 0}
                 >
@@ -560,6 +630,12 @@ function App() {
                 <AlertTriangle className="text-red-500" size={24} />
                 <h3>Confirm Deletion</h3>
               </div>
+              {deletionPlan && (
+                <p>
+                  {formatSize(deletionPlan.total_bytes)} in {deletionPlan.file_count} files
+                  and {deletionPlan.dir_count} folders will be permanently deleted.
+                </p>
+              )}
               <div className="modal-footer">
                 <button
                   onClick={handleConfirmDelete}
@@ -576,7 +652,10 @@ function App() {
                   )}
                 </button>
                 <button
-                  onClick={() => setShowDeleteConfirm(false)}
+                  onClick={() => {
+                    setShowDeleteConfirm(false)
+                    setDeletionPlan(null)
+                  }}
                   className="btn btn-secondary"
                   disabled={isDeleting}
                 >
@@ -708,7 +787,7 @@ function App() {
 
                   <div className="stat-item">
                     <span className="stat-label">Size</span>
-                    <span className="stat-value">{formatSize(selectedNode.size)}</span>
+                    <span className="stat-value">{formatNodeSize(selectedNode)}</span>
                   </div>
 
                   {selectedNode.is_directory && (
@@ -747,7 +826,7 @@ function App() {
                                 </span>
                               </div>
                               <span className="text-xs text-gray-500 whitespace-nowrap font-mono ml-2">
-                                {formatSize(item.size)}
+                                {formatNodeSize(item)}
                               </span>
                             </div>
                           ))}